@@ -108,9 +108,9 @@ const BrowseAddresses = () => {
                 method: "Get",
             },
         );
-        const addresses = await response.json();
+        const page = await response.json();
 
-        setFancies(addresses);
+        setFancies(page.items);
     };
 
     const resetFilters = () => {
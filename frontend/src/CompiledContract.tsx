@@ -48,11 +48,11 @@ const CompiledContract = () => {
     };
 
     const searchAddresses = async () => {
-        const response = await backendFetch("/api/fancy/mylist", {
+        const response = await backendFetch("/api/fancy/mylist?limit=1000", {
             method: "Get",
         });
-        const addresses = await response.json();
-        setAvailableAddresses(addresses);
+        const page = await response.json();
+        setAvailableAddresses(page.items);
     };
 
     useEffect(() => {
@@ -29,6 +29,7 @@ export interface ContractCompiledBytecode {
 }
 export interface ContractCompiledEvm {
     bytecode: ContractCompiledBytecode;
+    deployedBytecode?: ContractCompiledBytecode;
 }
 export interface ContractCompiledInt {
     evm: ContractCompiledEvm;
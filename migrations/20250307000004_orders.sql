@@ -0,0 +1,18 @@
+CREATE TABLE orders (
+    uid                 UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id             UUID NOT NULL,
+    kind                TEXT NOT NULL,
+    pattern             TEXT NOT NULL,
+    difficulty          DOUBLE PRECISION NOT NULL,
+    price               BIGINT NOT NULL,
+    budget              BIGINT NOT NULL,
+    status              TEXT NOT NULL,
+    created             TIMESTAMP NOT NULL DEFAULT now(),
+    updated             TIMESTAMP NOT NULL DEFAULT now(),
+    address             VARCHAR(42) NULL,
+    CONSTRAINT orders_fk FOREIGN KEY (user_id) REFERENCES users (uid),
+    CONSTRAINT orders_fk1 FOREIGN KEY (address) REFERENCES fancy (address)
+);
+
+CREATE INDEX orders_user_id_idx ON orders (user_id);
+CREATE INDEX orders_status_idx ON orders (status);
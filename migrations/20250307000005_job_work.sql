@@ -0,0 +1,12 @@
+CREATE TABLE job_work (
+    uid                 UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id              UUID NOT NULL,
+    factory             TEXT NULL,
+    public_key_base     TEXT NULL,
+    salt_prefix         TEXT NOT NULL,
+    min_score           DOUBLE PRECISION NOT NULL,
+    created             TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT job_work_fk FOREIGN KEY (job_id) REFERENCES job_info (uid) ON DELETE CASCADE
+);
+
+CREATE INDEX job_work_job_id_idx ON job_work (job_id);
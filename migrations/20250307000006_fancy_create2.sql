@@ -0,0 +1 @@
+ALTER TABLE fancy ADD COLUMN init_code_hash TEXT NULL;
@@ -0,0 +1,4 @@
+ALTER TABLE contract_factory ADD COLUMN kind TEXT NOT NULL DEFAULT 'solady';
+ALTER TABLE contract_factory ADD COLUMN proxy_init_code_hash TEXT NOT NULL DEFAULT '0x21c35dbe1b344a2488cf3321d6ce542f8e9f305544ff09e4993a62319a497c1f';
+ALTER TABLE contract_factory ADD COLUMN salt_guard TEXT NOT NULL DEFAULT 'none';
+ALTER TABLE contract_factory ADD COLUMN guard_sender TEXT NULL;
@@ -0,0 +1,11 @@
+CREATE TABLE fancy_secret_reveal (
+    uid                 UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+    address             VARCHAR(42) NOT NULL,
+    user_id             UUID NOT NULL,
+    remote_addr         TEXT NULL,
+    revealed            TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT fancy_secret_reveal_fk FOREIGN KEY (address) REFERENCES fancy (address) ON DELETE CASCADE,
+    CONSTRAINT fancy_secret_reveal_fk1 FOREIGN KEY (user_id) REFERENCES users (uid)
+);
+
+CREATE INDEX fancy_secret_reveal_address_idx ON fancy_secret_reveal (address);
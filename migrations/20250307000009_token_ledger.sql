@@ -0,0 +1,28 @@
+-- every token movement is one transaction (tx_id) of rows summing to zero
+-- account is either 'user' (with user_id) or one of system accounts: treasury, sales, escrow
+CREATE TABLE token_ledger (
+    uid                 UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+    tx_id               UUID NOT NULL,
+    account             TEXT NOT NULL,
+    user_id             UUID NULL,
+    amount              BIGINT NOT NULL,
+    kind                TEXT NOT NULL,
+    address             VARCHAR(42) NULL,
+    order_id            UUID NULL,
+    note                TEXT NULL,
+    created             TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT token_ledger_fk FOREIGN KEY (user_id) REFERENCES users (uid),
+    CONSTRAINT token_ledger_account_check CHECK ((account = 'user') = (user_id IS NOT NULL))
+);
+
+CREATE INDEX token_ledger_user_id_idx ON token_ledger (user_id, created);
+CREATE INDEX token_ledger_tx_id_idx ON token_ledger (tx_id);
+
+-- balances from before the ledger existed are booked as opening entries
+WITH opening AS (
+    SELECT uid AS user_id, tokens, gen_random_uuid() AS tx_id FROM users WHERE tokens <> 0
+)
+INSERT INTO token_ledger (tx_id, account, user_id, amount, kind, note)
+SELECT tx_id, 'user', user_id, tokens, 'opening', 'Balance before ledger' FROM opening
+UNION ALL
+SELECT tx_id, 'treasury', NULL, -tokens, 'opening', 'Balance before ledger' FROM opening;
@@ -0,0 +1,5 @@
+-- deployed was stored as text, while it is written as timestamp
+ALTER TABLE contract ALTER COLUMN deployed TYPE TIMESTAMP USING deployed::timestamp;
+
+ALTER TABLE contract ADD COLUMN deploy_error TEXT NULL;
+ALTER TABLE contract ADD COLUMN deploy_block BIGINT NULL;
@@ -0,0 +1,4 @@
+-- deploy workers lease contracts, so several of them can run at once
+ALTER TABLE contract ADD COLUMN deploy_claimed_until TIMESTAMP NULL;
+
+CREATE INDEX contract_deploy_status_network_idx ON contract (deploy_status, network);
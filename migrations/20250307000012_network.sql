@@ -0,0 +1,22 @@
+CREATE TABLE network
+(
+    name                TEXT NOT NULL PRIMARY KEY,
+    chain_id            BIGINT NOT NULL UNIQUE,
+    rpc_urls            TEXT[] NOT NULL DEFAULT '{}',
+    explorer_url        TEXT NULL,
+    gas_strategy        TEXT NOT NULL DEFAULT 'legacy',
+    gas_multiplier      DOUBLE PRECISION NOT NULL DEFAULT 1.2,
+    deployer_key_env    TEXT NOT NULL DEFAULT 'DEPLOYER_PRIVATE_KEY',
+    enabled             BOOLEAN NOT NULL DEFAULT TRUE,
+    added               TIMESTAMP NOT NULL
+);
+
+-- factories deployed on given network
+CREATE TABLE network_factory
+(
+    network             TEXT NOT NULL,
+    factory             TEXT NOT NULL,
+    PRIMARY KEY (network, factory),
+    CONSTRAINT network_factory_fk1 FOREIGN KEY (network) REFERENCES network (name) ON DELETE CASCADE,
+    CONSTRAINT network_factory_fk2 FOREIGN KEY (factory) REFERENCES contract_factory (address) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- exact compiler build used for the contract, settings are stored as json like data
+ALTER TABLE contract ADD COLUMN compiler_version TEXT NULL;
+ALTER TABLE contract ADD COLUMN compiler_settings TEXT NULL;
@@ -0,0 +1,21 @@
+-- Etherscan compatible api of the network explorer, the key is read from the named environment variable
+ALTER TABLE network ADD COLUMN explorer_api_url TEXT NULL;
+ALTER TABLE network ADD COLUMN explorer_key_env TEXT NOT NULL DEFAULT 'ETHERSCAN_API_KEY';
+
+-- source verification of deployed contract, one row per explorer
+CREATE TABLE contract_verification
+(
+    contract_id         UUID NOT NULL,
+    explorer            TEXT NOT NULL,
+    status              TEXT NOT NULL,
+    guid                TEXT NULL,
+    attempts            INT NOT NULL DEFAULT 0,
+    error               TEXT NULL,
+    next_check          TIMESTAMP NOT NULL,
+    created             TIMESTAMP NOT NULL,
+    updated             TIMESTAMP NOT NULL,
+    PRIMARY KEY (contract_id, explorer),
+    CONSTRAINT contract_verification_fk1 FOREIGN KEY (contract_id) REFERENCES contract (contract_id) ON DELETE CASCADE
+);
+
+CREATE INDEX contract_verification_status_idx ON contract_verification (status, next_check);
@@ -0,0 +1,13 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- prefix search
+CREATE INDEX fancy_address_pattern_idx ON fancy (address varchar_pattern_ops);
+-- suffix search, reversed address turns it into prefix search
+CREATE INDEX fancy_address_reverse_idx ON fancy (reverse(address) text_pattern_ops);
+-- substring search
+CREATE INDEX fancy_address_trgm_idx ON fancy USING gin (address gin_trgm_ops);
+
+-- listing order with address as tie breaker
+CREATE INDEX fancy_score_address_idx ON fancy (score, address);
+CREATE INDEX fancy_created_address_idx ON fancy (created, address);
+CREATE INDEX fancy_price_address_idx ON fancy (price, address);
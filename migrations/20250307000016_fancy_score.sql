@@ -0,0 +1,14 @@
+-- every category score of an address, existing rows are filled by ScoreFancy
+CREATE TABLE fancy_score (
+    address             VARCHAR(42) NOT NULL,
+    category            TEXT NOT NULL,
+    score               DOUBLE PRECISION NOT NULL,
+    difficulty          DOUBLE PRECISION NOT NULL,
+    -- 1 for the winning category, 2 for the second best and so on
+    category_rank       INT NOT NULL,
+    CONSTRAINT fancy_score_pk PRIMARY KEY (address, category),
+    CONSTRAINT fancy_score_fk FOREIGN KEY (address) REFERENCES fancy (address) ON DELETE CASCADE
+);
+
+CREATE INDEX fancy_score_category_idx ON fancy_score (category, difficulty, address);
+CREATE INDEX fancy_score_category_rank_idx ON fancy_score (category, category_rank, difficulty, address);
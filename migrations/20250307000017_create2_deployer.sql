@@ -0,0 +1,6 @@
+-- CREATE2 deployers share the table with CREATE3 factories but are never handed out as work targets
+ALTER TABLE contract_factory ADD COLUMN create2_deployer BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE contract_factory cf SET create2_deployer = TRUE
+WHERE EXISTS (SELECT 1 FROM fancy f WHERE f.factory = cf.address AND f.init_code_hash IS NOT NULL)
+  AND NOT EXISTS (SELECT 1 FROM fancy f WHERE f.factory = cf.address AND f.init_code_hash IS NULL);
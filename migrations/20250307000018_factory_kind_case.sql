@@ -0,0 +1,2 @@
+-- factory kinds and salt guards are stored lowercase
+UPDATE contract_factory SET kind = LOWER(kind), salt_guard = LOWER(salt_guard);
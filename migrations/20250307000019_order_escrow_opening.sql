@@ -0,0 +1,17 @@
+-- orders placed before the ledger existed hold their price in escrow without a reservation entry,
+-- the price was already taken from the user balance, so it is booked from treasury like opening balances
+WITH opening AS (
+    SELECT o.uid AS order_id, o.price, gen_random_uuid() AS tx_id
+    FROM orders o
+    WHERE NOT EXISTS (
+        SELECT 1 FROM token_ledger tl WHERE tl.order_id = o.uid AND tl.kind = 'orderReserve'
+    )
+    AND (
+        o.status = 'open'
+        OR EXISTS (SELECT 1 FROM token_ledger tl WHERE tl.order_id = o.uid AND tl.account = 'escrow')
+    )
+)
+INSERT INTO token_ledger (tx_id, account, user_id, amount, kind, order_id, note)
+SELECT tx_id, 'escrow', NULL::uuid, price, 'opening', order_id, 'Order escrow before ledger' FROM opening
+UNION ALL
+SELECT tx_id, 'treasury', NULL, -price, 'opening', order_id, 'Order escrow before ledger' FROM opening;
@@ -1,6 +1,9 @@
 pub mod contract;
 pub mod fancy;
+pub mod network;
 pub mod oauth;
 pub mod scope;
+#[cfg(test)]
+mod tests;
 pub mod user;
 pub mod utils;
@@ -1,5 +1,12 @@
-use crate::solc::compile_solc;
-use actix_web::{web, HttpResponse};
+use crate::config::get_trusted_proxies;
+use crate::db::model::UserDbObj;
+use crate::get_logged_user_or_null;
+use crate::solc::libraries::list_libraries;
+use crate::solc::manager::list_solc_versions;
+use crate::solc::queue::CompileError;
+use crate::solc::CompilerSettings;
+use actix_session::Session;
+use actix_web::{web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
@@ -9,20 +16,77 @@ use crate::ServerData;
 #[serde(rename_all = "camelCase")]
 pub struct CompileData {
     pub sources: BTreeMap<String, String>,
+    /// Exact solc version, resolved from pragmas when not given
+    pub version: Option<String>,
+    #[serde(default)]
+    pub settings: CompilerSettings,
+}
+
+/// Ip of the client, forwarded ip is used only when the peer is a trusted proxy
+fn client_ip(req: &HttpRequest) -> String {
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    if peer.is_some_and(|ip| get_trusted_proxies().contains(&ip)) {
+        if let Some(ip) = req.connection_info().realip_remote_addr() {
+            return ip.to_string();
+        }
+    }
+    peer.map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string())
 }
 
 pub async fn handle_compile(
     server_data: web::Data<Box<ServerData>>,
     deploy_data: web::Json<CompileData>,
+    session: Session,
+    req: HttpRequest,
 ) -> HttpResponse {
-    let _conn = server_data.db_connection.lock().await;
+    if let Err(e) = deploy_data.settings.validate() {
+        return HttpResponse::BadRequest().body(e);
+    }
+
+    // anonymous compilations are limited per ip and use installed compilers only
+    let user = get_logged_user_or_null!(session);
+    let user_key = match &user {
+        Some(user) => user.uid.to_string(),
+        None => client_ip(&req),
+    };
 
-    log::info!("Compiling contract: {:#?}", deploy_data.sources);
-    match compile_solc(deploy_data.sources.clone(), "0.8.28").await {
+    let deploy_data = deploy_data.into_inner();
+    match server_data
+        .compile_queue
+        .compile(
+            &user_key,
+            user.is_some(),
+            deploy_data.sources,
+            deploy_data.version.as_deref(),
+            &deploy_data.settings,
+        )
+        .await
+    {
         Ok(res) => HttpResponse::Ok().json(res),
+        Err(e @ CompileError::InputTooLarge(_)) => {
+            HttpResponse::PayloadTooLarge().body(e.to_string())
+        }
+        Err(e @ CompileError::InvalidInput(_)) => HttpResponse::BadRequest().body(e.to_string()),
+        Err(e @ CompileError::RateLimited) => HttpResponse::TooManyRequests().body(e.to_string()),
+        Err(e @ CompileError::QueueFull) => HttpResponse::ServiceUnavailable().body(e.to_string()),
+        Err(CompileError::Failed(e)) => {
+            log::error!("{}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+pub async fn handle_compile_versions() -> HttpResponse {
+    match list_solc_versions().await {
+        Ok(versions) => HttpResponse::Ok().json(versions),
         Err(e) => {
             log::error!("{}", e);
             HttpResponse::InternalServerError().finish()
         }
     }
 }
+
+pub async fn handle_compile_libraries() -> HttpResponse {
+    HttpResponse::Ok().json(list_libraries().await)
+}
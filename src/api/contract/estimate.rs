@@ -0,0 +1,48 @@
+use crate::api::contract::check_network_supported;
+use crate::db::model::UserDbObj;
+use crate::db::ops::get_contract_by_id;
+use crate::deploy::{estimate_deploy, DeployError};
+use crate::{login_check_and_get, ServerData};
+use actix_session::Session;
+use actix_web::{web, HttpResponse};
+use uuid::Uuid;
+
+/// Gas and fee the deployment of the contract would currently take
+pub async fn handle_contract_estimate(
+    server_data: web::Data<Box<ServerData>>,
+    contract_id: web::Path<Uuid>,
+    session: Session,
+) -> HttpResponse {
+    let user: UserDbObj = login_check_and_get!(session);
+    let contract_id = contract_id.into_inner();
+
+    let conn = &server_data.db_connection;
+
+    let contract = match get_contract_by_id(conn, contract_id, user.uid).await {
+        Ok(Some(contract)) => contract,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if contract.address.is_none() {
+        return HttpResponse::BadRequest().body("Contract has no address assigned");
+    }
+    if let Some(resp) = check_network_supported(conn, &contract.network).await {
+        return resp;
+    }
+
+    match estimate_deploy(conn, &contract).await {
+        Ok(estimate) => HttpResponse::Ok().json(estimate),
+        Err(DeployError::InvalidInput(msg)) => HttpResponse::BadRequest().body(msg),
+        Err(DeployError::Rpc(e)) => {
+            log::error!("Estimation of contract {} failed: {}", contract_id, e);
+            HttpResponse::BadGateway().body("Network node unavailable, try again later")
+        }
+        Err(DeployError::Failed(e)) => {
+            log::error!("Estimation of contract {} failed: {}", contract_id, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
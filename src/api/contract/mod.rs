@@ -1,34 +1,107 @@
 pub mod api;
 pub mod compile;
+pub mod estimate;
+pub mod verify;
 
 use crate::db::model::{ContractCreateFromApi, ContractDbObj, DeployStatus, UserDbObj};
 use crate::db::ops::{
-    delete_contract_by_id, get_all_contracts_by_user, get_contract_address_list,
-    get_contract_by_id, insert_contract_obj, update_contract_data,
+    delete_contract_by_id, fancy_get_by_address, get_all_contracts_by_user,
+    get_contract_address_list, get_contract_by_id, get_contract_verifications, get_network,
+    get_user_contract_verifications, insert_contract_obj, update_contract_data,
 };
+use crate::solc::version::SolcVersion;
+use crate::solc::CompilerSettings;
+use crate::types::DbAddress;
 use crate::{login_check_and_get, ServerData};
 use actix_session::Session;
 use actix_web::web::Data;
 use actix_web::{web, HttpResponse};
+use sqlx::{Executor, Postgres};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Error response if contracts cannot be deployed to the network
+pub async fn check_network_supported<'c, E>(conn: E, network: &str) -> Option<HttpResponse>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    match get_network(conn, network).await {
+        Ok(Some(network)) if network.enabled => None,
+        Ok(_) => Some(HttpResponse::BadRequest().body(format!("Unsupported network: {}", network))),
+        Err(e) => {
+            log::error!("Error getting network: {}", e);
+            Some(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+/// Error response if the contract address is not a fancy address owned by the user
+pub async fn check_address_owned<'c, E>(
+    conn: E,
+    address: Option<&str>,
+    user_id: Uuid,
+) -> Option<HttpResponse>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let address = address?;
+    let db_address = match DbAddress::from_str(address) {
+        Ok(addr) => addr,
+        Err(e) => return Some(HttpResponse::BadRequest().body(format!("Invalid address: {}", e))),
+    };
+    match fancy_get_by_address(conn, db_address).await {
+        Ok(Some(fancy)) if fancy.owner_id == Some(user_id) => None,
+        Ok(_) => Some(
+            HttpResponse::Forbidden().body(format!("Address {} is not owned by user", address)),
+        ),
+        Err(e) => {
+            log::error!("Error getting fancy address: {}", e);
+            Some(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+/// Compiler version and settings stored with the contract must be usable for recompilation
+pub fn check_compiler_build(version: Option<&str>, settings: Option<&str>) -> Result<(), String> {
+    if let Some(version) = version {
+        SolcVersion::from_str(version)?;
+    }
+    if let Some(settings) = settings {
+        serde_json::from_str::<CompilerSettings>(settings)
+            .map_err(|e| format!("Invalid compiler settings: {}", e))?
+            .validate()?;
+    }
+    Ok(())
+}
 
 pub async fn get_contract_info_api(
     data: Data<Box<ServerData>>,
-    contract_id: web::Path<String>,
+    contract_id: web::Path<Uuid>,
     session: Session,
 ) -> HttpResponse {
     let user: UserDbObj = login_check_and_get!(session);
 
     let contract_id = contract_id.into_inner();
 
-    let db = data.db_connection.lock().await;
+    let db = &data.db_connection;
 
-    match get_contract_by_id(&*db, contract_id, user.uid).await {
-        Ok(contract) => HttpResponse::Ok().json(contract),
+    let mut contract = match get_contract_by_id(db, contract_id, user.uid).await {
+        Ok(contract) => contract,
         Err(e) => {
             log::error!("Error getting scan info: {}", e);
-            HttpResponse::InternalServerError().finish()
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if let Some(contract) = contract.as_mut() {
+        match get_contract_verifications(db, contract_id).await {
+            Ok(verifications) => contract.verifications = verifications,
+            Err(e) => {
+                log::error!("Error getting verifications: {}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
         }
     }
+    HttpResponse::Ok().json(contract)
 }
 
 pub async fn insert_contract_info_api(
@@ -38,11 +111,23 @@ pub async fn insert_contract_info_api(
 ) -> HttpResponse {
     let user: UserDbObj = login_check_and_get!(session);
 
-    let db = data.db_connection.lock().await;
+    let db = &data.db_connection;
 
     let contract_api = contract.into_inner();
+    if let Some(resp) = check_network_supported(db, &contract_api.network).await {
+        return resp;
+    }
+    if let Some(resp) = check_address_owned(db, contract_api.address.as_deref(), user.uid).await {
+        return resp;
+    }
+    if let Err(e) = check_compiler_build(
+        contract_api.compiler_version.as_deref(),
+        contract_api.compiler_settings.as_deref(),
+    ) {
+        return HttpResponse::BadRequest().body(e);
+    }
     let contract = ContractDbObj {
-        contract_id: uuid::Uuid::new_v4().to_string(),
+        contract_id: Uuid::new_v4(),
         user_id: user.uid,
         created: chrono::Utc::now().naive_utc(),
         address: contract_api.address,
@@ -53,9 +138,14 @@ pub async fn insert_contract_info_api(
         deploy_requested: None,
         deploy_sent: None,
         deployed: None,
+        deploy_error: None,
+        deploy_block: None,
+        compiler_version: contract_api.compiler_version,
+        compiler_settings: contract_api.compiler_settings,
+        verifications: Vec::new(),
     };
 
-    match insert_contract_obj(&db, contract).await {
+    match insert_contract_obj(db, contract).await {
         Ok(contr) => HttpResponse::Ok().json(contr),
         Err(e) => {
             log::error!("Error inserting scan info: {}", e);
@@ -71,7 +161,7 @@ pub async fn update_contract_info_api(
 ) -> HttpResponse {
     let user: UserDbObj = login_check_and_get!(session);
 
-    let db = data.db_connection.lock().await;
+    let db = &data.db_connection;
 
     let contract = contract.into_inner();
 
@@ -104,9 +194,26 @@ pub async fn update_contract_info_api(
             .body("Contract is already sent for deployment and cannot be updated");
     }
 
+    if let Some(resp) = check_network_supported(&mut *trans, &contract.network).await {
+        return resp;
+    }
+    if let Some(resp) =
+        check_address_owned(&mut *trans, contract.address.as_deref(), user.uid).await
+    {
+        return resp;
+    }
+    if let Err(e) = check_compiler_build(
+        contract.compiler_version.as_deref(),
+        contract.compiler_settings.as_deref(),
+    ) {
+        return HttpResponse::BadRequest().body(e);
+    }
+
     new_contract_version.data = contract.data;
     new_contract_version.address = contract.address;
     new_contract_version.network = contract.network;
+    new_contract_version.compiler_version = contract.compiler_version;
+    new_contract_version.compiler_settings = contract.compiler_settings;
 
     let contr = match update_contract_data(&mut *trans, new_contract_version).await {
         Ok(contr) => contr,
@@ -127,15 +234,31 @@ pub async fn update_contract_info_api(
 pub async fn get_contracts_api(data: Data<Box<ServerData>>, session: Session) -> HttpResponse {
     let user: UserDbObj = login_check_and_get!(session);
 
-    let db = data.db_connection.lock().await;
+    let db = &data.db_connection;
 
-    match get_all_contracts_by_user(&db, user.uid).await {
-        Ok(contracts) => HttpResponse::Ok().json(contracts),
+    let mut contracts = match get_all_contracts_by_user(db, user.uid).await {
+        Ok(contracts) => contracts,
         Err(e) => {
             log::error!("Error getting scan info: {}", e);
-            HttpResponse::InternalServerError().finish()
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let verifications = match get_user_contract_verifications(db, user.uid).await {
+        Ok(verifications) => verifications,
+        Err(e) => {
+            log::error!("Error getting verifications: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    for verification in verifications {
+        if let Some(contract) = contracts
+            .iter_mut()
+            .find(|c| c.contract_id == verification.contract_id)
+        {
+            contract.verifications.push(verification);
         }
     }
+    HttpResponse::Ok().json(contracts)
 }
 
 pub async fn get_all_contract_assignments(
@@ -144,9 +267,9 @@ pub async fn get_all_contract_assignments(
 ) -> HttpResponse {
     let user: UserDbObj = login_check_and_get!(session);
 
-    let db = data.db_connection.lock().await;
+    let db = &data.db_connection;
 
-    match get_contract_address_list(&*db, user.uid).await {
+    match get_contract_address_list(db, user.uid).await {
         Ok(contracts) => HttpResponse::Ok().json(contracts),
         Err(e) => {
             log::error!("Error getting scan info: {}", e);
@@ -157,14 +280,14 @@ pub async fn get_all_contract_assignments(
 
 pub async fn delete_contract_api(
     data: Data<Box<ServerData>>,
-    contract_id: web::Path<String>,
+    contract_id: web::Path<Uuid>,
     session: Session,
 ) -> HttpResponse {
     let user: UserDbObj = login_check_and_get!(session);
 
     let contract_id = contract_id.into_inner();
 
-    let db = data.db_connection.lock().await;
+    let db = &data.db_connection;
 
     let mut trans = match db.begin().await {
         Ok(t) => t,
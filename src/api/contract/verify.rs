@@ -0,0 +1,41 @@
+use crate::db::model::{DeployStatus, UserDbObj};
+use crate::db::ops::get_contract_by_id;
+use crate::verify::request_verification;
+use crate::{login_check_and_get, ServerData};
+use actix_session::Session;
+use actix_web::{web, HttpResponse};
+use uuid::Uuid;
+
+/// Queues source verification of deployed contract again, e.g. after failure
+pub async fn handle_contract_verify(
+    server_data: web::Data<Box<ServerData>>,
+    contract_id: web::Path<Uuid>,
+    session: Session,
+) -> HttpResponse {
+    let user: UserDbObj = login_check_and_get!(session);
+    let conn = &server_data.db_connection;
+
+    let contract = match get_contract_by_id(conn, contract_id.into_inner(), user.uid).await {
+        Ok(Some(contract)) => contract,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            log::error!("Error getting contract: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if contract.deploy_status != DeployStatus::Succeeded {
+        return HttpResponse::Conflict().body("Contract is not deployed");
+    }
+
+    match request_verification(conn, &contract).await {
+        Ok(queued) if queued.is_empty() => HttpResponse::BadRequest().body(format!(
+            "No explorer configured for network {}",
+            contract.network
+        )),
+        Ok(queued) => HttpResponse::Ok().json(queued),
+        Err(e) => {
+            log::error!("Error queuing verification: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
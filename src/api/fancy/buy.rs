@@ -1,8 +1,12 @@
-use crate::db::model::UserDbObj;
-use crate::db::ops::{fancy_get_by_address, fancy_update_owner, get_user, update_user_tokens};
+use crate::db::model::{LedgerAccount, TokenLedgerKind, TokenLedgerRefs, UserDbObj};
+use crate::db::ops::{
+    fancy_get_by_address, fancy_update_owner, get_public_key_base, get_user_for_update,
+    token_transfer,
+};
 use crate::{login_check_and_get, normalize_address, ServerData};
 use actix_session::Session;
 use actix_web::{web, HttpResponse};
+use serde_json::json;
 
 pub async fn handle_fancy_buy_api(
     server_data: web::Data<Box<ServerData>>,
@@ -13,7 +17,7 @@ pub async fn handle_fancy_buy_api(
 
     let address = address.into_inner();
 
-    let conn = server_data.db_connection.lock().await;
+    let conn = &server_data.db_connection;
 
     let mut trans = match conn.begin().await {
         Ok(tx) => tx,
@@ -23,7 +27,7 @@ pub async fn handle_fancy_buy_api(
         }
     };
 
-    let user_for_tx = match get_user(&mut *trans, &user.email).await {
+    let user_for_tx = match get_user_for_update(&mut *trans, &user.email).await {
         Ok(user) => user,
         Err(err) => {
             log::error!("Error getting user: {}", err);
@@ -49,6 +53,23 @@ pub async fn handle_fancy_buy_api(
         return HttpResponse::BadRequest().body("Address already owned");
     }
 
+    // addresses found for customer key can be bought only by the key owner
+    if let Some(public_key_base) = &address_db.public_key_base {
+        match get_public_key_base(&mut *trans, public_key_base).await {
+            Ok(Some(pkb)) => {
+                if pkb.user_id.is_some_and(|owner| owner != user.uid) {
+                    log::error!("Address {} reserved for public key owner", address);
+                    return HttpResponse::Forbidden().body("Address reserved for public key owner");
+                }
+            }
+            Ok(None) => {}
+            Err(err) => {
+                log::error!("Error getting public key base: {}", err);
+                return HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
     if user_for_tx.tokens < address_db.price {
         log::error!(
             "User has insufficient funds: {} < {}",
@@ -59,7 +80,11 @@ pub async fn handle_fancy_buy_api(
     }
 
     match fancy_update_owner(&mut *trans, address, user.uid).await {
-        Ok(_) => {}
+        Ok(true) => {}
+        Ok(false) => {
+            log::error!("Address already owned: {}", address);
+            return HttpResponse::BadRequest().body("Address already owned");
+        }
         Err(err) => {
             log::error!("Error updating owner: {}", err);
             return HttpResponse::InternalServerError().finish();
@@ -74,7 +99,19 @@ pub async fn handle_fancy_buy_api(
         address_db.price,
         tokens_left
     );
-    match update_user_tokens(&mut *trans, &user.email, tokens_left).await {
+    match token_transfer(
+        &mut trans,
+        LedgerAccount::User(user.uid),
+        LedgerAccount::Sales,
+        address_db.price,
+        TokenLedgerKind::Purchase,
+        TokenLedgerRefs {
+            address: Some(address),
+            ..Default::default()
+        },
+    )
+    .await
+    {
         Ok(_) => {}
         Err(err) => {
             log::error!("Error updating user tokens: {}", err);
@@ -83,7 +120,10 @@ pub async fn handle_fancy_buy_api(
     }
 
     match trans.commit().await {
-        Ok(_) => HttpResponse::Ok().finish(),
+        Ok(_) => HttpResponse::Ok().json(json!({
+            "address": address,
+            "privateKeyAdd": address_db.public_key_base.as_ref().map(|_| address_db.salt.clone()),
+        })),
         Err(err) => {
             log::error!("Error committing transaction: {}", err);
             HttpResponse::InternalServerError().finish()
@@ -1,29 +1,36 @@
+use crate::api::contract::check_network_supported;
 use crate::db::model::{DeployStatus, UserDbObj};
-use crate::db::ops::{get_contract_by_id, update_contract_data};
+use crate::db::ops::{contract_retry_deploy, get_contract_by_id, update_contract_data};
 use crate::{login_check_and_get, ServerData};
 use actix_session::Session;
 use actix_web::{web, HttpResponse};
+use uuid::Uuid;
 
 pub async fn handle_fancy_deploy_start(
     server_data: web::Data<Box<ServerData>>,
-    contract_id: web::Path<String>,
+    contract_id: web::Path<Uuid>,
     session: Session,
 ) -> HttpResponse {
     let user: UserDbObj = login_check_and_get!(session);
     let contract_id = contract_id.into_inner();
 
-    let conn = server_data.db_connection.lock().await;
+    let conn = &server_data.db_connection;
 
-    let contract = match get_contract_by_id(&*conn, contract_id, user.uid).await {
+    let contract = match get_contract_by_id(conn, contract_id, user.uid).await {
         Ok(Some(contract)) => {
             let mut contract = contract;
             match contract.deploy_status {
                 DeployStatus::None => {
+                    if let Some(resp) = check_network_supported(conn, &contract.network).await {
+                        return resp;
+                    }
                     contract.deploy_status = DeployStatus::Requested;
                     contract
                 }
                 DeployStatus::Requested => return HttpResponse::Ok().body("Already requested"),
-                DeployStatus::TxSent => return HttpResponse::Ok().body("Already sent"),
+                DeployStatus::TxSent | DeployStatus::Mined => {
+                    return HttpResponse::Ok().body("Already sent")
+                }
                 DeployStatus::Failed => return HttpResponse::Ok().body("Deployment Failed"),
                 DeployStatus::Succeeded => return HttpResponse::Ok().body("Deployment Succeeded"),
             }
@@ -37,7 +44,7 @@ pub async fn handle_fancy_deploy_start(
         }
     };
 
-    match update_contract_data(&*conn, contract).await {
+    match update_contract_data(conn, contract).await {
         Ok(contr) => HttpResponse::Ok().json(contr),
         Err(err) => {
             log::error!("Error updating contract data {}", err);
@@ -45,3 +52,34 @@ pub async fn handle_fancy_deploy_start(
         }
     }
 }
+
+/// Moves failed deployment back to requested, so it is picked up again
+pub async fn handle_fancy_deploy_retry(
+    server_data: web::Data<Box<ServerData>>,
+    contract_id: web::Path<Uuid>,
+    session: Session,
+) -> HttpResponse {
+    let user: UserDbObj = login_check_and_get!(session);
+    let contract_id = contract_id.into_inner();
+
+    let conn = &server_data.db_connection;
+
+    match contract_retry_deploy(conn, contract_id, user.uid).await {
+        Ok(Some(contract)) => HttpResponse::Ok().json(contract),
+        Ok(None) => match get_contract_by_id(conn, contract_id, user.uid).await {
+            Ok(Some(contract)) => HttpResponse::Conflict().body(format!(
+                "Only failed deployments can be retried, current status: {:?}",
+                contract.deploy_status
+            )),
+            Ok(None) => HttpResponse::NotFound().finish(),
+            Err(e) => {
+                log::error!("{}", e);
+                HttpResponse::InternalServerError().finish()
+            }
+        },
+        Err(e) => {
+            log::error!("Error retrying deployment {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::api::utils::{extract_url_date_param, extract_url_param};
-use crate::db::ops::{fancy_list, FancyOrderBy, PublicKeyFilter, ReservedStatus};
+use crate::db::ops::{fancy_count, FancyListFilter, PublicKeyFilter};
 use crate::ServerData;
 use actix_web::{web, HttpRequest, HttpResponse};
 use serde_json::json;
@@ -15,37 +15,22 @@ pub async fn handle_fancy_estimate_total_hash(
         Some(base) => PublicKeyFilter::Selected(base),
         None => PublicKeyFilter::All,
     };
-    let fancies = {
-        let conn = server_data.db_connection.lock().await;
-        match fancy_list(
-            &*conn,
-            Some("leading_zeroes".to_string()),
-            FancyOrderBy::Score,
-            ReservedStatus::All,
-            since,
-            provider_id,
-            public_key_base_filter,
-            100000000,
-        )
-        .await
-        {
-            Ok(fancies) => fancies,
-            Err(e) => {
-                log::error!("{}", e);
-                return Ok(HttpResponse::InternalServerError().finish());
-            }
-        }
+    let conn = &server_data.db_connection;
+    let filter = FancyListFilter {
+        category: Some("leading_zeroes".to_string()),
+        since,
+        provider_id,
+        public_key_base: public_key_base_filter,
+        score_above: Some(1E11),
+        ..Default::default()
     };
-
-    let mut number_of_events = 0;
-    #[allow(clippy::collapsible_if)]
-    for fancy in fancies {
-        if fancy.category == "leading_zeroes" {
-            if fancy.score > 1E11 {
-                number_of_events += 1;
-            }
+    let number_of_events = match fancy_count(conn, &filter).await {
+        Ok(count) => count,
+        Err(e) => {
+            log::error!("{}", e);
+            return Ok(HttpResponse::InternalServerError().finish());
         }
-    }
+    };
     Ok(HttpResponse::Ok().json(json!(
         {
             "eventDifficulty": 1.0E10f64,
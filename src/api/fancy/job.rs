@@ -1,21 +1,31 @@
 use crate::api::fancy::ApiMinerInfo;
-use crate::api::utils::{extract_url_date_param, extract_url_int_param, extract_url_param};
-use crate::db::model::{JobDbObj, MinerDbObj, UserDbObj};
+use crate::api::utils::{extract_page_limit, extract_url_date_param, extract_url_param, PageApi};
+use crate::config::get_min_accepted_score;
+use crate::db::model::{ContractFactoryDbObject, JobDbObj, JobWorkDbObj, MinerDbObj, UserDbObj};
 use crate::db::ops::{
     fancy_finish_job, fancy_get_job_info, fancy_get_miner_info, fancy_insert_job_info,
-    fancy_insert_miner_info, fancy_job_list, FancyJobOrderBy, FancyJobStatus,
+    fancy_insert_job_work, fancy_insert_miner_info, fancy_job_count, fancy_job_list,
+    fancy_pick_work_target, get_factory_by_address, get_open_orders, FancyJobFilter,
+    FancyJobOrderBy, FancyJobStatus, WorkTargetKind,
 };
+use crate::db::utils::get_current_utc_time;
+use crate::error::AddressologyError;
+use crate::hash::{Create3Params, SaltGuard};
 use crate::types::DbAddress;
-use crate::{get_logged_user_or_null, ServerData};
+use crate::{err_custom_create, get_logged_user_or_null, ServerData};
 use actix_session::Session;
 use actix_web::{web, HttpRequest, HttpResponse};
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 
 use serde::{Deserialize, Serialize};
 use sqlx::types::Uuid;
 use std::cmp::PartialEq;
+use std::str::FromStr;
 use web3::signing::keccak256;
 
+// number of random salt bytes assigned to a single work request
+const SALT_PREFIX_BYTES: usize = 4;
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AddNewJobData {
@@ -42,16 +52,84 @@ pub struct JobWithMinerApi {
     pub job_extra_info: Option<String>,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobWorkRequest {
+    pub job_id: Uuid,
+    /// factory, publicKeyBase or any (default)
+    pub kind: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkPatternApi {
+    pub order_id: Uuid,
+    pub kind: String,
+    pub pattern: String,
+    pub difficulty: f64,
+}
+
+/// CREATE3 parameters of the assigned factory, miners have to use them to derive addresses
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FactoryParamsApi {
+    pub kind: String,
+    pub proxy_init_code_hash: String,
+    pub salt_guard: String,
+    pub guard_sender: Option<DbAddress>,
+}
+
+impl FactoryParamsApi {
+    pub fn from_factory(factory: &ContractFactoryDbObject) -> Self {
+        FactoryParamsApi {
+            kind: factory.kind.clone(),
+            proxy_init_code_hash: factory.proxy_init_code_hash.clone(),
+            salt_guard: factory.salt_guard.clone(),
+            guard_sender: factory.guard_sender,
+        }
+    }
+
+    pub fn to_create3_params(&self) -> Result<Create3Params, AddressologyError> {
+        Ok(Create3Params {
+            proxy_init_code_hash: self.proxy_init_code_hash.clone(),
+            salt_guard: SaltGuard::from_str(&self.salt_guard)
+                .map_err(|e| err_custom_create!("{}", e))?,
+            guard_sender: self.guard_sender.map(|s| format!("{:#x}", s.addr())),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobWorkApi {
+    pub uid: Uuid,
+    pub job_id: Uuid,
+    pub factory: Option<DbAddress>,
+    /// Set together with factory
+    pub factory_params: Option<FactoryParamsApi>,
+    pub public_key_base: Option<String>,
+    pub salt_prefix: String,
+    pub min_score: f64,
+    pub patterns: Vec<WorkPatternApi>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobCursorApi {
+    pub after_updated: DateTime<Utc>,
+    pub after_uid: Uuid,
+}
+
 pub async fn handle_job_list(
     server_data: web::Data<Box<ServerData>>,
     request: HttpRequest,
     session: Session,
 ) -> Result<HttpResponse, actix_web::Error> {
     let _user = get_logged_user_or_null!(session);
-    let conn = server_data.db_connection.lock().await;
-    let limit = extract_url_int_param(&request, "limit")?;
+    let conn = &server_data.db_connection;
+    let limit = extract_page_limit(&request)?;
 
-    let order = extract_url_param(&request, "order")?.unwrap_or("score".to_string());
+    let order = extract_url_param(&request, "order")?.unwrap_or("created".to_string());
     let status = extract_url_param(&request, "status")?;
     let since = extract_url_date_param(&request, "since")?;
 
@@ -72,17 +150,35 @@ pub async fn handle_job_list(
         })?),
         None => None,
     };
+    let after = match (
+        extract_url_date_param(&request, "after_updated")?,
+        extract_url_param(&request, "after_uid")?,
+    ) {
+        (Some(updated_at), Some(uid)) => Some((
+            updated_at,
+            Uuid::parse_str(&uid)
+                .map_err(|_| actix_web::error::ErrorBadRequest("Invalid after_uid"))?,
+        )),
+        (None, None) => None,
+        _ => return Ok(HttpResponse::BadRequest().body("after_updated and after_uid go together")),
+    };
 
-    let list = match fancy_job_list(
-        &*conn,
-        order,
+    let filter = FancyJobFilter {
+        order_by: order,
         since,
         status,
         requestor_id,
-        limit.unwrap_or(100),
-    )
-    .await
-    {
+        after,
+        limit,
+    };
+    let total = match fancy_job_count(conn, &filter).await {
+        Ok(total) => total,
+        Err(e) => {
+            log::error!("{}", e);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    let list = match fancy_job_list(conn, &filter).await {
         Ok(list) => list,
         Err(e) => {
             log::error!("{}", e);
@@ -90,14 +186,19 @@ pub async fn handle_job_list(
         }
     };
 
-    Ok(HttpResponse::Ok().json(list))
+    Ok(
+        HttpResponse::Ok().json(PageApi::new(list, total, limit, |job| JobCursorApi {
+            after_updated: job.updated_at.and_utc(),
+            after_uid: job.uid,
+        })),
+    )
 }
 
 pub async fn handle_finish_job(
     server_data: web::Data<Box<ServerData>>,
     job_id: web::Path<String>,
 ) -> HttpResponse {
-    let conn = server_data.db_connection.lock().await;
+    let conn = &server_data.db_connection;
     let mut db_trans = match conn.begin().await {
         Ok(db) => db,
         Err(e) => {
@@ -180,7 +281,7 @@ pub async fn handle_new_job(
     server_data: web::Data<Box<ServerData>>,
     new_data: web::Json<AddNewJobData>,
 ) -> HttpResponse {
-    let conn = server_data.db_connection.lock().await;
+    let conn = &server_data.db_connection;
     let mut db_trans = match conn.begin().await {
         Ok(db) => db,
         Err(e) => {
@@ -316,3 +417,123 @@ pub async fn handle_new_job(
         }
     }
 }
+
+/// Assigns target and salt range to the job. Results submitted for the job
+/// are verified against all assignments issued to it.
+pub async fn handle_job_work(
+    server_data: web::Data<Box<ServerData>>,
+    work_request: web::Json<JobWorkRequest>,
+) -> HttpResponse {
+    let kind = match work_request.kind.as_deref().unwrap_or("any") {
+        "any" => WorkTargetKind::Any,
+        "factory" => WorkTargetKind::Factory,
+        "publicKeyBase" => WorkTargetKind::PublicKeyBase,
+        other => {
+            return HttpResponse::BadRequest().body(format!("Invalid work kind: {}", other));
+        }
+    };
+
+    let conn = &server_data.db_connection;
+    let mut db_trans = match conn.begin().await {
+        Ok(db) => db,
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let job = match fancy_get_job_info(&mut *db_trans, work_request.job_id).await {
+        Ok(job) => job,
+        Err(sqlx::Error::RowNotFound) => {
+            return HttpResponse::NotFound().body("Job not found");
+        }
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if job.finished_at.is_some() {
+        return HttpResponse::BadRequest().body("Job already finished");
+    }
+
+    let target = match fancy_pick_work_target(&mut *db_trans, kind).await {
+        Ok(Some(target)) => target,
+        Ok(None) => {
+            return HttpResponse::NotFound().body("No work available");
+        }
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let factory_params = match target.factory {
+        Some(factory) => match get_factory_by_address(&mut *db_trans, factory).await {
+            Ok(Some(factory)) => Some(FactoryParamsApi::from_factory(&factory)),
+            Ok(None) => {
+                log::error!("Work target factory {} not found", factory);
+                return HttpResponse::InternalServerError().finish();
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        },
+        None => None,
+    };
+
+    let orders = match get_open_orders(&mut *db_trans, 100).await {
+        Ok(orders) => orders,
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let salt_prefix = format!(
+        "0x{}",
+        hex::encode(rand::random::<[u8; SALT_PREFIX_BYTES]>())
+    );
+    let work = JobWorkDbObj {
+        uid: Uuid::new_v4(),
+        job_id: job.uid,
+        factory: target.factory,
+        public_key_base: target.public_key_base,
+        salt_prefix,
+        min_score: get_min_accepted_score(),
+        created: get_current_utc_time(),
+    };
+    let work = match fancy_insert_job_work(&mut *db_trans, work).await {
+        Ok(work) => work,
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let work_api = JobWorkApi {
+        uid: work.uid,
+        job_id: work.job_id,
+        factory: work.factory,
+        factory_params,
+        public_key_base: work.public_key_base,
+        salt_prefix: work.salt_prefix,
+        min_score: work.min_score,
+        patterns: orders
+            .into_iter()
+            .map(|o| WorkPatternApi {
+                order_id: o.uid,
+                kind: o.kind,
+                pattern: o.pattern,
+                difficulty: o.difficulty,
+            })
+            .collect(),
+    };
+    match db_trans.commit().await {
+        Ok(_) => HttpResponse::Ok().json(work_api),
+        Err(e) => {
+            log::error!("{}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
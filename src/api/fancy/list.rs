@@ -1,31 +1,99 @@
-use crate::api::utils::{extract_url_date_param, extract_url_int_param, extract_url_param};
-use crate::db::model::UserDbObj;
-use crate::db::ops::{fancy_list, FancyOrderBy, PublicKeyFilter, ReservedStatus};
+use crate::api::fancy::redact_fancy_secret;
+use crate::api::utils::{
+    extract_page_limit, extract_url_date_param, extract_url_int_param, extract_url_param, PageApi,
+};
+use crate::db::model::{FancyProviderDbObj, UserDbObj};
+use crate::db::ops::{
+    fancy_count, fancy_list, get_public_key_base, FancyCursor, FancyListFilter, FancyOrderBy,
+    PublicKeyFilter, ReservedStatus,
+};
+use crate::hash::normalize_public_key_base;
+use crate::types::DbAddress;
 use crate::{get_logged_user_or_null, ServerData};
 use actix_session::Session;
 use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::types::Uuid;
+use sqlx::PgPool;
 
-pub async fn handle_list(
-    server_data: web::Data<Box<ServerData>>,
-    request: HttpRequest,
-    session: Session,
-) -> Result<HttpResponse, actix_web::Error> {
-    let user = get_logged_user_or_null!(session);
-    let conn = server_data.db_connection.lock().await;
-    let limit = extract_url_int_param(&request, "limit")?;
-    let public_key_base = extract_url_param(&request, "public_key_base")?;
-    let mut category = extract_url_param(&request, "category")?;
+/// Passed back as after_score, after_created or after_price together with after_address
+/// to get the next page
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FancyCursorApi {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_created: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_price: Option<i64>,
+    pub after_address: DbAddress,
+}
+
+pub fn fancy_cursor(order: FancyOrderBy, fancy: &FancyProviderDbObj) -> FancyCursorApi {
+    FancyCursorApi {
+        after_score: (order == FancyOrderBy::Score).then_some(fancy.score),
+        after_created: (order == FancyOrderBy::Created).then_some(fancy.created.and_utc()),
+        after_price: (order == FancyOrderBy::Price).then_some(fancy.price),
+        after_address: fancy.address,
+    }
+}
+
+pub fn extract_fancy_cursor(
+    request: &HttpRequest,
+    order: FancyOrderBy,
+) -> Result<Option<FancyCursor>, actix_web::Error> {
+    let Some(address) = extract_url_param(request, "after_address")? else {
+        return Ok(None);
+    };
+    let address = DbAddress::from_str(&address)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid after_address"))?;
+    match order {
+        FancyOrderBy::Score => {
+            let score = extract_url_param(request, "after_score")?
+                .and_then(|score| score.parse::<f64>().ok())
+                .ok_or_else(|| {
+                    actix_web::error::ErrorBadRequest("after_score required for score order")
+                })?;
+            Ok(Some(FancyCursor::Score(score, address)))
+        }
+        FancyOrderBy::Created => {
+            let created = extract_url_date_param(request, "after_created")?.ok_or_else(|| {
+                actix_web::error::ErrorBadRequest("after_created required for created order")
+            })?;
+            Ok(Some(FancyCursor::Created(created, address)))
+        }
+        FancyOrderBy::Price => {
+            let price = extract_url_int_param(request, "after_price")?.ok_or_else(|| {
+                actix_web::error::ErrorBadRequest("after_price required for price order")
+            })?;
+            Ok(Some(FancyCursor::Price(price, address)))
+        }
+    }
+}
+
+/// Filter shared by listing and search: category, free, order, since, provider_id,
+/// public_key_base, cursor and limit parameters
+pub async fn extract_fancy_list_filter(
+    conn: &PgPool,
+    request: &HttpRequest,
+    user_id: Option<Uuid>,
+) -> Result<FancyListFilter, actix_web::Error> {
+    let limit = extract_page_limit(request)?;
+    let public_key_base = extract_url_param(request, "public_key_base")?;
+    let mut category = extract_url_param(request, "category")?;
     if category == Some("all".to_string()) {
         category = None
     }
-    let provider_id = extract_url_param(&request, "provider_id")?;
-    let free = extract_url_param(&request, "free")?;
+    let provider_id = extract_url_param(request, "provider_id")?;
+    let free = extract_url_param(request, "free")?;
     let reserved_status = match free.unwrap_or("free".to_string()).as_str() {
         "mine" => {
-            if let Some(user) = user {
-                ReservedStatus::User(user.uid)
+            if let Some(user_id) = user_id {
+                ReservedStatus::User(user_id)
             } else {
-                return Ok(HttpResponse::Unauthorized().finish());
+                return Err(actix_web::error::ErrorUnauthorized("Not logged in"));
             }
         }
         "reserved" => ReservedStatus::Reserved,
@@ -33,37 +101,77 @@ pub async fn handle_list(
         "free" => ReservedStatus::NotReserved,
         _ => ReservedStatus::NotReserved,
     };
-    let order = extract_url_param(&request, "order")?.unwrap_or("score".to_string());
-    let since = extract_url_date_param(&request, "since")?;
+    let order = extract_url_param(request, "order")?.unwrap_or("score".to_string());
+    let since = extract_url_date_param(request, "since")?;
     let order = match order.as_str() {
         "score" => FancyOrderBy::Score,
         "created" => FancyOrderBy::Created,
-        _ => return Ok(HttpResponse::BadRequest().finish()),
+        "price" => FancyOrderBy::Price,
+        _ => return Err(actix_web::error::ErrorBadRequest("Invalid order")),
     };
+    let after = extract_fancy_cursor(request, order)?;
 
     let public_key_base = match public_key_base {
         Some(base) => {
             if base == "all" {
                 PublicKeyFilter::All
             } else {
+                let base = normalize_public_key_base(&base)
+                    .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+                // addresses found for customer key are visible only to the customer
+                match get_public_key_base(conn, &base).await {
+                    Ok(Some(pkb)) => {
+                        if pkb.user_id.is_some() && pkb.user_id != user_id {
+                            return Err(actix_web::error::ErrorForbidden(
+                                "Public key base belongs to another user",
+                            ));
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        log::error!("{}", e);
+                        return Err(actix_web::error::ErrorInternalServerError(
+                            "Error getting public key base",
+                        ));
+                    }
+                }
                 PublicKeyFilter::Selected(base)
             }
         }
         None => PublicKeyFilter::OnlyNull,
     };
 
-    let list = match fancy_list(
-        &*conn,
+    Ok(FancyListFilter {
         category,
-        order,
-        reserved_status,
+        order_by: order,
+        reserved: reserved_status,
         since,
         provider_id,
         public_key_base,
-        limit.unwrap_or(100),
-    )
-    .await
-    {
+        after,
+        limit,
+        ..Default::default()
+    })
+}
+
+pub async fn handle_list(
+    server_data: web::Data<Box<ServerData>>,
+    request: HttpRequest,
+    session: Session,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user = get_logged_user_or_null!(session);
+    let user_id = user.map(|u| u.uid);
+    let conn = &server_data.db_connection;
+    let filter = extract_fancy_list_filter(conn, &request, user_id).await?;
+
+    let total = match fancy_count(conn, &filter).await {
+        Ok(total) => total,
+        Err(e) => {
+            log::error!("{}", e);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    let mut list = match fancy_list(conn, &filter).await {
         Ok(list) => list,
         Err(e) => {
             log::error!("{}", e);
@@ -71,5 +179,13 @@ pub async fn handle_list(
         }
     };
 
-    Ok(HttpResponse::Ok().json(list))
+    for fancy in list.iter_mut() {
+        redact_fancy_secret(fancy, user_id);
+    }
+
+    Ok(
+        HttpResponse::Ok().json(PageApi::new(list, total, filter.limit, |fancy| {
+            fancy_cursor(filter.order_by, fancy)
+        })),
+    )
 }
@@ -5,13 +5,17 @@ pub mod job;
 pub mod list;
 pub mod my;
 pub mod new;
+pub mod order;
+pub mod public_key;
 pub mod score;
+pub mod search;
+pub mod secret;
 pub mod tokens;
 
 use crate::api::utils::extract_url_param;
-use crate::db::model::UserDbObj;
+use crate::db::model::{FancyProviderDbObj, UserDbObj};
 use crate::db::ops::{
-    fancy_list, get_public_key_list, FancyOrderBy, PublicKeyFilter, ReservedStatus,
+    fancy_list, get_public_key_list, FancyListFilter, PublicKeyFilter, ReservedStatus,
 };
 use crate::types::DbAddress;
 use crate::{login_check_and_get, ServerData};
@@ -19,31 +23,40 @@ use actix_session::Session;
 use actix_web::{web, HttpRequest, HttpResponse};
 use rand::prelude::IndexedRandom;
 use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
 use std::cmp::PartialEq;
 
+/// Salt (or private key addition) gives control over the address, so only its owner can see it
+pub fn redact_fancy_secret(fancy: &mut FancyProviderDbObj, user_id: Option<Uuid>) {
+    if fancy.owner_id.is_none() || fancy.owner_id != user_id {
+        fancy.salt = String::new();
+    }
+}
+
 pub async fn handle_random(
     server_data: web::Data<Box<ServerData>>,
     request: HttpRequest,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let conn = server_data.db_connection.lock().await;
+    let conn = &server_data.db_connection;
 
     let mut category = extract_url_param(&request, "category")?;
     if category == Some("all".to_string()) {
         category = None
     }
     let list = fancy_list(
-        &*conn,
-        category,
-        FancyOrderBy::Score,
-        ReservedStatus::NotReserved,
-        None,
-        None,
-        PublicKeyFilter::OnlyNull,
-        1000,
+        conn,
+        &FancyListFilter {
+            category,
+            reserved: ReservedStatus::NotReserved,
+            public_key_base: PublicKeyFilter::OnlyNull,
+            limit: 1000,
+            ..Default::default()
+        },
     )
     .await
     .unwrap();
-    let random = list.choose(&mut rand::rng()).unwrap();
+    let mut random = list.choose(&mut rand::rng()).unwrap().clone();
+    redact_fancy_secret(&mut random, None);
 
     Ok(HttpResponse::Ok().json(random))
 }
@@ -60,9 +73,9 @@ pub async fn handle_public_key_list(
 ) -> HttpResponse {
     let user = login_check_and_get!(session);
 
-    let conn = server_data.db_connection.lock().await;
+    let conn = &server_data.db_connection;
 
-    let res = match get_public_key_list(&*conn, Some(user.uid)).await {
+    let res = match get_public_key_list(conn, Some(user.uid)).await {
         Ok(res) => res,
         Err(e) => {
             log::error!("{}", e);
@@ -1,8 +1,10 @@
 use crate::api::contract::api::login_check_fn;
-use crate::api::utils::extract_url_bool_param;
+use crate::api::fancy::list::{extract_fancy_cursor, FancyCursorApi};
+use crate::api::utils::{extract_page_limit, extract_url_bool_param, PageApi};
 use crate::db::model::ContractAddressDbObj;
 use crate::db::ops::{
-    fancy_list, get_contract_address_list, FancyOrderBy, PublicKeyFilter, ReservedStatus,
+    fancy_count, fancy_list, get_contract_address_list, FancyListFilter, FancyOrderBy,
+    PublicKeyFilter, ReservedStatus,
 };
 use crate::types::DbAddress;
 use crate::ServerData;
@@ -37,8 +39,10 @@ pub async fn handle_my_list(
 ) -> Result<HttpResponse, actix_web::Error> {
     let user = login_check_fn(session)?;
 
-    let conn = server_data.db_connection.lock().await;
+    let conn = &server_data.db_connection;
     let unassigned_only = extract_url_bool_param(&request, "unassigned_only")?.unwrap_or(false);
+    let limit = extract_page_limit(&request)?;
+    let after = extract_fancy_cursor(&request, FancyOrderBy::Score)?;
     let mut db_trans = conn.begin().await.map_err(|e| {
         log::error!("{}", e);
         actix_web::error::ErrorInternalServerError("Error starting transaction")
@@ -50,18 +54,22 @@ pub async fn handle_my_list(
             return Ok(HttpResponse::InternalServerError().finish());
         }
     };
-    let fancies = match fancy_list(
-        &mut *db_trans,
-        None,
-        FancyOrderBy::Score,
-        ReservedStatus::User(user.uid),
-        None,
-        None,
-        PublicKeyFilter::OnlyNull,
-        100000000,
-    )
-    .await
-    {
+    let filter = FancyListFilter {
+        reserved: ReservedStatus::User(user.uid),
+        public_key_base: PublicKeyFilter::OnlyNull,
+        unassigned_only,
+        after,
+        limit,
+        ..Default::default()
+    };
+    let total = match fancy_count(&mut *db_trans, &filter).await {
+        Ok(total) => total,
+        Err(e) => {
+            log::error!("{}", e);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    let fancies = match fancy_list(&mut *db_trans, &filter).await {
         Ok(fancies) => fancies,
         Err(e) => {
             log::error!("{}", e);
@@ -77,9 +85,6 @@ pub async fn handle_my_list(
             .filter(|x| x.address == fancy.address)
             .cloned()
             .collect();
-        if unassigned_only && !assigned_contracts.is_empty() {
-            continue;
-        }
         res.push(FancyProviderContractApi {
             address: fancy.address,
             salt: fancy.salt,
@@ -109,5 +114,12 @@ pub async fn handle_my_list(
         }
     }
 
-    Ok(HttpResponse::Ok().json(res))
+    Ok(
+        HttpResponse::Ok().json(PageApi::new(res, total, limit, |fancy| FancyCursorApi {
+            after_score: Some(fancy.score),
+            after_created: None,
+            after_price: None,
+            after_address: fancy.address,
+        })),
+    )
 }
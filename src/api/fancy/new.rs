@@ -1,15 +1,29 @@
+use crate::config::get_min_accepted_score;
+use crate::db::model::{
+    FancyDbObj, JobWorkDbObj, LedgerAccount, OrderDbObj, TokenLedgerKind, TokenLedgerRefs,
+};
 use crate::db::ops::{
-    fancy_get_job_info, fancy_update_job, get_or_insert_factory, get_or_insert_public_key,
-    insert_fancy_obj,
+    fancy_get_job_info, fancy_get_job_work, fancy_score_upsert, fancy_update_job,
+    get_all_open_orders, get_factory_by_address, get_or_insert_factory, get_or_insert_public_key,
+    insert_fancy_obj, lock_open_order, order_fulfill, token_transfer,
+};
+use crate::err_custom_create;
+use crate::error::AddressologyError;
+use crate::fancy::{
+    factory_create3_params, parse_fancy_create2, parse_fancy_create3, parse_fancy_private,
+    score_breakdown, OrderKind, OrderMatcher,
 };
-use crate::fancy::{parse_fancy, parse_fancy_private};
+use crate::hash::Create3Params;
 use crate::types::DbAddress;
 use crate::ServerData;
 use actix_web::{web, HttpResponse};
+use lazy_static::lazy_static;
 use serde::Deserialize;
 use serde_json::json;
 use sqlx::{Postgres, Transaction};
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -19,6 +33,8 @@ pub struct AddNewData {
     pub factory: String,
     pub address: Option<String>,
     pub job_id: Option<Uuid>,
+    /// When set, factory is CREATE2 deployer instead of CREATE3 factory
+    pub init_code_hash: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -27,6 +43,7 @@ pub struct AddNewDataEntry {
     pub salt: String,
     pub factory: String,
     pub address: Option<String>,
+    pub init_code_hash: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -50,14 +67,40 @@ pub enum FancyNewResult {
     Duplicate,
     Error(HttpResponse),
     ScoreTooLow,
+    NotAssigned,
+}
+
+fn work_matches(work: &JobWorkDbObj, fancy: &FancyDbObj) -> bool {
+    // factory targets are always CREATE3 factories, never CREATE2 deployers
+    let target_matches = match (&work.factory, &work.public_key_base) {
+        (Some(factory), _) => {
+            fancy.factory.as_ref() == Some(factory) && fancy.init_code_hash.is_none()
+        }
+        (None, Some(public_key_base)) => {
+            fancy.factory.is_none()
+                && fancy.public_key_base.as_ref().map(|pk| pk.to_lowercase())
+                    == Some(public_key_base.to_lowercase())
+        }
+        (None, None) => false,
+    };
+    let salt_prefix = work.salt_prefix.trim_start_matches("0x").to_lowercase();
+    target_matches
+        && fancy
+            .salt
+            .trim_start_matches("0x")
+            .to_lowercase()
+            .starts_with(&salt_prefix)
 }
 
 async fn _handle_fancy_new_with_trans(
     new_data: web::Json<AddNewData>,
     total_score: &mut f64,
+    open_orders: &mut Vec<(OrderDbObj, Arc<OrderMatcher>)>,
+    assignments: &[JobWorkDbObj],
     db_trans: &mut Transaction<'_, Postgres>,
 ) -> FancyNewResult {
-    let mut result = if new_data.factory.len() == 42 || new_data.factory.len() == 40 {
+    let (mut result, fancy_score) = if new_data.factory.len() == 42 || new_data.factory.len() == 40
+    {
         let factory = match web3::types::Address::from_str(&new_data.factory) {
             Ok(factory) => factory,
             Err(e) => {
@@ -68,21 +111,47 @@ async fn _handle_fancy_new_with_trans(
                 ));
             }
         };
-        let fancy = match parse_fancy(new_data.salt.clone(), factory) {
-            Ok(fancy) => fancy,
+        let factory_db =
+            match get_factory_by_address(&mut **db_trans, DbAddress::from_h160(factory)).await {
+                Ok(factory_db) => factory_db,
+                Err(e) => {
+                    log::error!("{}", e);
+                    return FancyNewResult::Error(HttpResponse::InternalServerError().finish());
+                }
+            };
+        // CREATE2 entries are accepted only for registered deployers, CREATE3 ones depend on
+        // the factory kind. Unknown factories are parsed as default CREATE3 ones and registered
+        // only when parsing succeeds.
+        let fancy = match &new_data.init_code_hash {
+            Some(init_code_hash) => match &factory_db {
+                Some(factory_db) if factory_db.create2_deployer => {
+                    parse_fancy_create2(new_data.salt.clone(), factory, init_code_hash.clone())
+                }
+                _ => Err(err_custom_create!(
+                    "{:#x} is not a registered CREATE2 deployer",
+                    factory
+                )),
+            },
+            None => match &factory_db {
+                Some(factory_db) => factory_create3_params(factory_db),
+                None => Ok(Create3Params::default()),
+            }
+            .and_then(|params| parse_fancy_create3(new_data.salt.clone(), factory, &params)),
+        };
+        let parsed = match fancy {
+            Ok(parsed) => parsed,
             Err(e) => {
                 log::error!("{}", e);
                 return FancyNewResult::ParseError(format!("parse fancy failed {}", e));
             }
         };
-        match get_or_insert_factory(db_trans, DbAddress::from_h160(factory)).await {
-            Ok(_) => {}
-            Err(e) => {
+        if factory_db.is_none() {
+            if let Err(e) = get_or_insert_factory(db_trans, DbAddress::from_h160(factory)).await {
                 log::error!("{}", e);
                 return FancyNewResult::Error(HttpResponse::InternalServerError().finish());
             }
         }
-        fancy
+        parsed
     } else {
         //normalize public key
         let public_key_base = new_data.factory.clone();
@@ -99,8 +168,8 @@ async fn _handle_fancy_new_with_trans(
             ));
         }
         let public_key_base = "0x".to_string() + &hex::encode(public_key_bytes);
-        let fancy = match parse_fancy_private(public_key_base, new_data.salt.clone()) {
-            Ok(fancy) => fancy,
+        let (fancy, score) = match parse_fancy_private(public_key_base, new_data.salt.clone()) {
+            Ok(parsed) => parsed,
             Err(e) => {
                 return FancyNewResult::ParseError(format!("{}", e));
             }
@@ -118,15 +187,20 @@ async fn _handle_fancy_new_with_trans(
                 return FancyNewResult::Error(HttpResponse::InternalServerError().finish());
             }
         }
-        fancy
+        (fancy, score)
     };
 
     result.job_id = new_data.job_id;
 
-    if result.score < 1E10 {
-        log::debug!("Score too low: {}", result.score);
-        return FancyNewResult::ScoreTooLow;
-    }
+    // jobs that requested work are only allowed to submit results for it
+    let min_score = if assignments.is_empty() {
+        get_min_accepted_score()
+    } else {
+        match assignments.iter().find(|work| work_matches(work, &result)) {
+            Some(work) => work.min_score,
+            None => return FancyNewResult::NotAssigned,
+        }
+    };
 
     let gen_address = format!("{:#x}", result.address.addr());
     if let Some(reference_address) = new_data.address.as_ref().map(|a| a.to_lowercase()) {
@@ -138,10 +212,76 @@ async fn _handle_fancy_new_with_trans(
             ));
         }
     }
+
+    // addresses ordered by users are accepted regardless of their score,
+    // orders taken or cancelled by other transactions meanwhile are skipped
+    let mut order = None;
+    while let Some(idx) = open_orders
+        .iter()
+        .position(|(_, matcher)| matcher.matches(result.address.addr()))
+    {
+        let (candidate, _) = open_orders.remove(idx);
+        match lock_open_order(&mut **db_trans, candidate.uid).await {
+            Ok(Some(locked)) => {
+                order = Some(locked);
+                break;
+            }
+            Ok(None) => log::debug!("Order {} is not open anymore", candidate.uid),
+            Err(e) => {
+                log::error!("{}", e);
+                return FancyNewResult::Error(HttpResponse::InternalServerError().finish());
+            }
+        }
+    }
+    if let Some(order) = &order {
+        result.owner_id = Some(order.user_id);
+        result.price = order.price;
+    } else if result.score < min_score {
+        log::debug!("Score too low: {}", result.score);
+        return FancyNewResult::ScoreTooLow;
+    }
     let score = result.score;
+    let address = result.address;
 
     match insert_fancy_obj(&mut **db_trans, result).await {
         Ok(_) => {
+            let breakdown = score_breakdown(address, &fancy_score);
+            if let Err(e) = fancy_score_upsert(&mut **db_trans, address, &breakdown).await {
+                log::error!("{}", e);
+                return FancyNewResult::Error(HttpResponse::InternalServerError().finish());
+            }
+            if let Some(order) = order {
+                match order_fulfill(&mut **db_trans, order.uid, address).await {
+                    Ok(Some(_)) => {
+                        log::info!("Order {} fulfilled with address {}", order.uid, address);
+                    }
+                    Ok(None) => {
+                        log::error!("Locked order {} is not open", order.uid);
+                        return FancyNewResult::Error(HttpResponse::InternalServerError().finish());
+                    }
+                    Err(e) => {
+                        log::error!("{}", e);
+                        return FancyNewResult::Error(HttpResponse::InternalServerError().finish());
+                    }
+                }
+                if let Err(e) = token_transfer(
+                    db_trans,
+                    LedgerAccount::Escrow,
+                    LedgerAccount::Sales,
+                    order.price,
+                    TokenLedgerKind::OrderSettle,
+                    TokenLedgerRefs {
+                        address: Some(address),
+                        order_id: Some(order.uid),
+                        ..Default::default()
+                    },
+                )
+                .await
+                {
+                    log::error!("{}", e);
+                    return FancyNewResult::Error(HttpResponse::InternalServerError().finish());
+                }
+            }
             *total_score += score;
             FancyNewResult::Ok(HttpResponse::Ok().json(json!({
                 "totalSore": score
@@ -158,12 +298,48 @@ async fn _handle_fancy_new_with_trans(
     }
 }
 
+lazy_static! {
+    /// Compiled patterns of open orders by order id, patterns of an order never change
+    static ref ORDER_MATCHERS: Mutex<HashMap<Uuid, Arc<OrderMatcher>>> = Default::default();
+}
+
+/// Pairs open orders with their matchers, compiling only orders not seen before
+fn order_matchers(orders: Vec<OrderDbObj>) -> Vec<(OrderDbObj, Arc<OrderMatcher>)> {
+    let mut cache = ORDER_MATCHERS.lock().unwrap();
+    let mut matchers = HashMap::with_capacity(orders.len());
+    let res = orders
+        .into_iter()
+        .filter_map(|order| {
+            let matcher = match cache.remove(&order.uid) {
+                Some(matcher) => matcher,
+                None => {
+                    let matcher = OrderKind::from_str(&order.kind)
+                        .map_err(|e| err_custom_create!("{}", e))
+                        .and_then(|kind| OrderMatcher::new(kind, &order.pattern));
+                    match matcher {
+                        Ok(matcher) => Arc::new(matcher),
+                        Err(e) => {
+                            log::error!("Order {}: {}", order.uid, e);
+                            return None;
+                        }
+                    }
+                }
+            };
+            matchers.insert(order.uid, matcher.clone());
+            Some((order, matcher))
+        })
+        .collect();
+    // orders no longer open are dropped from the cache
+    *cache = matchers;
+    res
+}
+
 pub async fn handle_fancy_new_many(
     server_data: web::Data<Box<ServerData>>,
     new_data: web::Json<AddNewDataMany>,
 ) -> HttpResponse {
     let mut total_score = 0.0;
-    let conn = server_data.db_connection.lock().await;
+    let conn = &server_data.db_connection;
     let mut db_trans = match conn.begin().await {
         Ok(db) => db,
         Err(e) => {
@@ -179,19 +355,41 @@ pub async fn handle_fancy_new_many(
         }
     };
 
+    let assignments = match fancy_get_job_work(&mut *db_trans, new_data.extra.job_id).await {
+        Ok(assignments) => assignments,
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let mut open_orders = match get_all_open_orders(&mut *db_trans).await {
+        Ok(orders) => order_matchers(orders),
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
     let mut entries_accepted = 0;
     let mut entries_rejected = 0;
     let mut entries_parse_error = 0;
+    let mut entries_not_assigned = 0;
     for data in new_data.data.iter() {
         let new_data = AddNewData {
             salt: data.salt.clone(),
             factory: data.factory.clone(),
             address: data.address.clone(),
             job_id: Some(new_data.extra.job_id),
+            init_code_hash: data.init_code_hash.clone(),
         };
-        let resp =
-            _handle_fancy_new_with_trans(web::Json(new_data), &mut total_score, &mut db_trans)
-                .await;
+        let resp = _handle_fancy_new_with_trans(
+            web::Json(new_data),
+            &mut total_score,
+            &mut open_orders,
+            &assignments,
+            &mut db_trans,
+        )
+        .await;
         match resp {
             FancyNewResult::Ok(_ok) => {
                 entries_accepted += 1;
@@ -212,6 +410,10 @@ pub async fn handle_fancy_new_many(
                 entries_rejected += 1;
                 log::debug!("Score too low - skipping");
             }
+            FancyNewResult::NotAssigned => {
+                entries_rejected += 1;
+                entries_not_assigned += 1;
+            }
         }
     }
 
@@ -244,5 +446,6 @@ pub async fn handle_fancy_new_many(
         "totalScore": total_score,
         "entriesRejected": entries_rejected,
         "entriesParseError": entries_parse_error,
+        "entriesNotAssigned": entries_not_assigned,
     }))
 }
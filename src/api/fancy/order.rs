@@ -0,0 +1,305 @@
+use crate::db::model::{
+    LedgerAccount, OrderDbObj, OrderStatus, TokenLedgerKind, TokenLedgerRefs, UserDbObj,
+};
+use crate::db::ops::{
+    get_open_orders, get_order_by_id, get_orders_by_user, get_user_for_update, insert_order,
+    order_cancel, token_transfer,
+};
+use crate::db::utils::get_current_utc_time;
+use crate::fancy::{order_price, OrderKind, OrderMatcher};
+use crate::{login_check_and_get, ServerData};
+use actix_session::Session;
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderRequest {
+    pub kind: OrderKind,
+    pub pattern: String,
+    pub budget: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct OrderEstimateResp {
+    kind: OrderKind,
+    pattern: String,
+    difficulty: f64,
+    price: i64,
+}
+
+/// Public view of open order, so miners know what to look for
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct OpenOrderResp {
+    uid: Uuid,
+    kind: String,
+    pattern: String,
+    difficulty: f64,
+    price: i64,
+}
+
+async fn estimate_order(request: &OrderRequest) -> Result<OrderEstimateResp, HttpResponse> {
+    let matcher = OrderMatcher::new(request.kind, &request.pattern).map_err(|e| {
+        log::warn!("Invalid order pattern {}: {}", request.pattern, e);
+        HttpResponse::BadRequest().body(e.to_string())
+    })?;
+    // regex difficulty is sampled, keep it off the async workers
+    let (matcher, difficulty) = web::block(move || {
+        let difficulty = matcher.difficulty();
+        (matcher, difficulty)
+    })
+    .await
+    .map_err(|e| {
+        log::error!("Difficulty estimation failed: {}", e);
+        HttpResponse::InternalServerError().finish()
+    })?;
+    let difficulty = difficulty.map_err(|e| {
+        log::warn!("Cannot price order pattern {}: {}", request.pattern, e);
+        HttpResponse::BadRequest().body(e.to_string())
+    })?;
+    Ok(OrderEstimateResp {
+        kind: matcher.kind,
+        pattern: matcher.pattern,
+        difficulty,
+        price: order_price(difficulty),
+    })
+}
+
+pub async fn handle_order_estimate(
+    request: web::Json<OrderRequest>,
+    session: Session,
+) -> HttpResponse {
+    let _user: UserDbObj = login_check_and_get!(session);
+    match estimate_order(&request).await {
+        Ok(estimate) => HttpResponse::Ok().json(estimate),
+        Err(resp) => resp,
+    }
+}
+
+pub async fn handle_order_new(
+    server_data: web::Data<Box<ServerData>>,
+    request: web::Json<OrderRequest>,
+    session: Session,
+) -> HttpResponse {
+    let user: UserDbObj = login_check_and_get!(session);
+
+    let estimate = match estimate_order(&request).await {
+        Ok(estimate) => estimate,
+        Err(resp) => return resp,
+    };
+    let budget = request.budget.unwrap_or(estimate.price);
+    if estimate.price > budget {
+        return HttpResponse::BadRequest().body(format!(
+            "Order price {} exceeds budget {}",
+            estimate.price, budget
+        ));
+    }
+
+    let conn = &server_data.db_connection;
+    let mut trans = match conn.begin().await {
+        Ok(tx) => tx,
+        Err(err) => {
+            log::error!("Error starting transaction: {}", err);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let user_for_tx = match get_user_for_update(&mut *trans, &user.email).await {
+        Ok(user) => user,
+        Err(err) => {
+            log::error!("Error getting user: {}", err);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if user_for_tx.tokens < estimate.price {
+        return HttpResponse::BadRequest().body("Insufficient funds");
+    }
+
+    let now = get_current_utc_time();
+    let order = OrderDbObj {
+        uid: Uuid::new_v4(),
+        user_id: user.uid,
+        kind: estimate.kind.to_string(),
+        pattern: estimate.pattern,
+        difficulty: estimate.difficulty,
+        price: estimate.price,
+        budget,
+        status: OrderStatus::Open,
+        created: now,
+        updated: now,
+        address: None,
+    };
+    let order = match insert_order(&mut *trans, &order).await {
+        Ok(order) => order,
+        Err(err) => {
+            log::error!("Error inserting order: {}", err);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    // tokens are reserved up front, returned if the order gets cancelled
+    let tokens_left = user_for_tx.tokens - order.price;
+    if let Err(err) = token_transfer(
+        &mut trans,
+        LedgerAccount::User(user.uid),
+        LedgerAccount::Escrow,
+        order.price,
+        TokenLedgerKind::OrderReserve,
+        TokenLedgerRefs {
+            order_id: Some(order.uid),
+            ..Default::default()
+        },
+    )
+    .await
+    {
+        log::error!("Error updating user tokens: {}", err);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    match trans.commit().await {
+        Ok(_) => {
+            log::info!(
+                "User {} placed {} order {} for {}, tokens left: {}",
+                user.email,
+                order.kind,
+                order.pattern,
+                order.price,
+                tokens_left
+            );
+            HttpResponse::Ok().json(order)
+        }
+        Err(err) => {
+            log::error!("Error committing transaction: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+pub async fn handle_order_list(
+    server_data: web::Data<Box<ServerData>>,
+    session: Session,
+) -> HttpResponse {
+    let user: UserDbObj = login_check_and_get!(session);
+
+    let conn = &server_data.db_connection;
+    match get_orders_by_user(conn, user.uid).await {
+        Ok(orders) => HttpResponse::Ok().json(orders),
+        Err(err) => {
+            log::error!("Error getting orders: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+pub async fn handle_order_get(
+    server_data: web::Data<Box<ServerData>>,
+    order_id: web::Path<Uuid>,
+    session: Session,
+) -> HttpResponse {
+    let user: UserDbObj = login_check_and_get!(session);
+
+    let conn = &server_data.db_connection;
+    match get_order_by_id(conn, order_id.into_inner(), user.uid).await {
+        Ok(Some(order)) => HttpResponse::Ok().json(order),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(err) => {
+            log::error!("Error getting order: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+pub async fn handle_order_open_list(server_data: web::Data<Box<ServerData>>) -> HttpResponse {
+    let conn = &server_data.db_connection;
+    match get_open_orders(conn, 1000).await {
+        Ok(orders) => HttpResponse::Ok().json(
+            orders
+                .into_iter()
+                .map(|o| OpenOrderResp {
+                    uid: o.uid,
+                    kind: o.kind,
+                    pattern: o.pattern,
+                    difficulty: o.difficulty,
+                    price: o.price,
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Err(err) => {
+            log::error!("Error getting open orders: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+pub async fn handle_order_cancel(
+    server_data: web::Data<Box<ServerData>>,
+    order_id: web::Path<Uuid>,
+    session: Session,
+) -> HttpResponse {
+    let user: UserDbObj = login_check_and_get!(session);
+
+    let conn = &server_data.db_connection;
+    let mut trans = match conn.begin().await {
+        Ok(tx) => tx,
+        Err(err) => {
+            log::error!("Error starting transaction: {}", err);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let order = match order_cancel(&mut *trans, order_id.into_inner(), user.uid).await {
+        Ok(Some(order)) => order,
+        Ok(None) => {
+            return HttpResponse::BadRequest().body("Order not found or not open");
+        }
+        Err(err) => {
+            log::error!("Error cancelling order: {}", err);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let user_for_tx = match get_user_for_update(&mut *trans, &user.email).await {
+        Ok(user) => user,
+        Err(err) => {
+            log::error!("Error getting user: {}", err);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let tokens_left = user_for_tx.tokens + order.price;
+    if let Err(err) = token_transfer(
+        &mut trans,
+        LedgerAccount::Escrow,
+        LedgerAccount::User(user.uid),
+        order.price,
+        TokenLedgerKind::Refund,
+        TokenLedgerRefs {
+            order_id: Some(order.uid),
+            ..Default::default()
+        },
+    )
+    .await
+    {
+        log::error!("Error updating user tokens: {}", err);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    match trans.commit().await {
+        Ok(_) => {
+            log::info!(
+                "User {} cancelled order {}, refunded {}, tokens left: {}",
+                user.email,
+                order.uid,
+                order.price,
+                tokens_left
+            );
+            HttpResponse::Ok().json(order)
+        }
+        Err(err) => {
+            log::error!("Error committing transaction: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::db::model::UserDbObj;
+use crate::db::ops::insert_user_public_key;
+use crate::hash::{normalize_public_key_base, verify_public_key_signature};
+use crate::{login_check_and_get, ServerData};
+use actix_session::Session;
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+/// Session key of the challenge the next registered public key has to sign
+const CHALLENGE_SESSION_KEY: &str = "public_key_challenge";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyNewRequest {
+    pub public_key_base: String,
+    /// personal_sign signature of the challenge, made with the private key base
+    pub signature: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyChallengeResp {
+    pub challenge: String,
+}
+
+/// Issues challenge that proves ownership of the private key behind registered public key
+pub async fn handle_public_key_challenge(session: Session) -> HttpResponse {
+    let user: UserDbObj = login_check_and_get!(session);
+
+    let challenge = format!(
+        "Register public key base for {}\nNonce: 0x{}",
+        user.email,
+        hex::encode(rand::random::<[u8; 32]>())
+    );
+    if let Err(e) = session.insert(CHALLENGE_SESSION_KEY, &challenge) {
+        log::error!("Error storing public key challenge: {}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    HttpResponse::Ok().json(PublicKeyChallengeResp { challenge })
+}
+
+/// Customer registers public key of a private key only they know.
+/// Miners then search for key additions and the customer combines the bought one with their key.
+pub async fn handle_public_key_new(
+    server_data: web::Data<Box<ServerData>>,
+    request: web::Json<PublicKeyNewRequest>,
+    session: Session,
+) -> HttpResponse {
+    let user: UserDbObj = login_check_and_get!(session);
+
+    let public_key_base = match normalize_public_key_base(&request.public_key_base) {
+        Ok(public_key_base) => public_key_base,
+        Err(e) => {
+            log::warn!("Invalid public key base {}: {}", request.public_key_base, e);
+            return HttpResponse::BadRequest().body(e.to_string());
+        }
+    };
+
+    // challenge is single use, failed attempts need a new one
+    let challenge = match session.remove_as::<String>(CHALLENGE_SESSION_KEY) {
+        Some(Ok(challenge)) => challenge,
+        _ => return HttpResponse::BadRequest().body("Request challenge first"),
+    };
+    if let Err(e) = verify_public_key_signature(&public_key_base, &challenge, &request.signature) {
+        log::warn!(
+            "User {} failed to prove ownership of {}: {}",
+            user.email,
+            public_key_base,
+            e
+        );
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
+
+    let conn = &server_data.db_connection;
+    match insert_user_public_key(conn, &public_key_base, user.uid).await {
+        Ok(Some(public_key)) => {
+            log::info!(
+                "User {} registered public key base {}",
+                user.email,
+                public_key_base
+            );
+            HttpResponse::Ok().json(public_key)
+        }
+        Ok(None) => HttpResponse::Conflict().body("Public key base already registered"),
+        Err(e) => {
+            log::error!("Error inserting public key base: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -1,13 +1,21 @@
-use crate::api::fancy::ApiMinerInfo;
+use crate::api::fancy::{redact_fancy_secret, ApiMinerInfo};
+use crate::api::utils::{
+    extract_page_limit, extract_url_float_param, extract_url_int_param, extract_url_param, PageApi,
+};
 use crate::config::get_base_difficulty_price;
-use crate::db::model::FancyScore;
 use crate::db::model::UserDbObj;
-use crate::db::ops::{fancy_get_by_address, fancy_get_job_info, fancy_get_miner_info};
-use crate::fancy::{list_score_categories, score_fancy};
+use crate::db::model::{FancyScore, FancyScoreDbObj};
+use crate::db::ops::{
+    fancy_count_by_category, fancy_get_by_address, fancy_get_job_info, fancy_get_miner_info,
+    fancy_get_scores, fancy_list_by_category, FancyCategoryFilter, ReservedStatus,
+};
+use crate::fancy::{list_score_categories, score_fancy, FancyScoreCategory};
+use crate::types::DbAddress;
 use crate::{get_logged_user_or_null, normalize_address, ServerData};
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
@@ -19,6 +27,9 @@ struct FancyScoreResponse {
     factory: Option<String>,
     salt: Option<String>,
     public_key_base: Option<String>,
+    init_code_hash: Option<String>,
+    /// Category scores saved for mined addresses, ordered by rank
+    stored_scores: Vec<FancyScoreDbObj>,
 }
 
 //this request can be public
@@ -30,26 +41,24 @@ pub async fn handle_score_custom(
     let user = get_logged_user_or_null!(session);
     let address = normalize_address!(address.into_inner());
 
-    if let Some(_user) = user {
+    if let Some(user) = user {
         //@todo filter out sensitive user data
-        let db = server_data.db_connection.lock().await;
+        let db = &server_data.db_connection;
 
-        let fancy = fancy_get_by_address(&*db, address)
-            .await
-            .unwrap_or_default();
+        let fancy = fancy_get_by_address(db, address).await.unwrap_or_default();
 
         let score = score_fancy(address.addr());
 
         let api_miner_info = if let Some(fancy) = &fancy {
             if let Some(job_id) = fancy.job_id {
-                let job = match fancy_get_job_info(&*db, job_id).await {
+                let job = match fancy_get_job_info(db, job_id).await {
                     Ok(job) => job,
                     Err(e) => {
                         log::error!("Error getting job info: {}", e);
                         return HttpResponse::InternalServerError().finish();
                     }
                 };
-                match fancy_get_miner_info(&*db, &job.miner).await {
+                match fancy_get_miner_info(db, &job.miner).await {
                     Ok(miner) => miner,
                     Err(e) => {
                         log::error!("Error getting miner info: {}", e);
@@ -62,6 +71,17 @@ pub async fn handle_score_custom(
         } else {
             None
         };
+        let stored_scores = if fancy.is_some() {
+            match fancy_get_scores(db, address).await {
+                Ok(scores) => scores,
+                Err(e) => {
+                    log::error!("Error getting stored scores: {}", e);
+                    return HttpResponse::InternalServerError().finish();
+                }
+            }
+        } else {
+            Vec::new()
+        };
         HttpResponse::Ok().json(FancyScoreResponse {
             score: score.clone(),
             price: (get_base_difficulty_price() as f64 * score.price_multiplier) as i64,
@@ -75,8 +95,14 @@ pub async fn handle_score_custom(
             factory: fancy
                 .as_ref()
                 .and_then(|f| f.factory.map(|f| f.to_string())),
-            salt: fancy.as_ref().map(|f| f.salt.clone()),
+            // salt and private key addition are visible only to the owner
+            salt: fancy
+                .as_ref()
+                .filter(|f| f.owner_id == Some(user.uid))
+                .map(|f| f.salt.clone()),
             public_key_base: fancy.as_ref().and_then(|f| f.public_key_base.clone()),
+            init_code_hash: fancy.as_ref().and_then(|f| f.init_code_hash.clone()),
+            stored_scores,
         })
     } else {
         let score = score_fancy(address.addr());
@@ -89,6 +115,8 @@ pub async fn handle_score_custom(
             factory: None,
             salt: None,
             public_key_base: None,
+            init_code_hash: None,
+            stored_scores: Vec::new(),
         })
     }
 }
@@ -96,3 +124,92 @@ pub async fn handle_score_custom(
 pub async fn handle_get_score_categories() -> HttpResponse {
     HttpResponse::Ok().json(list_score_categories())
 }
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FancyCategoryCursorApi {
+    pub after_difficulty: f64,
+    pub after_address: DbAddress,
+}
+
+/// Addresses ordered by their score in the category, rank=2 lists those where it came second
+pub async fn handle_fancy_best_by_category(
+    server_data: web::Data<Box<ServerData>>,
+    request: HttpRequest,
+    session: actix_session::Session,
+    category: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user = get_logged_user_or_null!(session);
+    let user_id = user.map(|u| u.uid);
+    let category = FancyScoreCategory::from_str(&category)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Unknown category"))?;
+    let limit = extract_page_limit(&request)?;
+    let rank = match extract_url_int_param(&request, "rank")? {
+        Some(rank) if rank < 1 => {
+            return Err(actix_web::error::ErrorBadRequest("rank starts at 1"));
+        }
+        rank => rank.map(|rank| rank as i32),
+    };
+    let reserved = match extract_url_param(&request, "free")?
+        .unwrap_or("free".to_string())
+        .as_str()
+    {
+        "mine" => ReservedStatus::User(
+            user_id.ok_or_else(|| actix_web::error::ErrorUnauthorized("Not logged in"))?,
+        ),
+        "reserved" => ReservedStatus::Reserved,
+        "all" => ReservedStatus::All,
+        _ => ReservedStatus::NotReserved,
+    };
+    let after = match (
+        extract_url_float_param(&request, "after_difficulty")?,
+        extract_url_param(&request, "after_address")?,
+    ) {
+        (Some(difficulty), Some(address)) => Some((
+            difficulty,
+            DbAddress::from_str(&address)
+                .map_err(|_| actix_web::error::ErrorBadRequest("Invalid after_address"))?,
+        )),
+        (None, None) => None,
+        _ => {
+            return Err(actix_web::error::ErrorBadRequest(
+                "after_difficulty and after_address go together",
+            ))
+        }
+    };
+
+    let conn = &server_data.db_connection;
+    let filter = FancyCategoryFilter {
+        category: category.to_string(),
+        rank,
+        reserved,
+        after,
+        limit,
+    };
+    let total = match fancy_count_by_category(conn, &filter).await {
+        Ok(total) => total,
+        Err(e) => {
+            log::error!("{}", e);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    let mut list = match fancy_list_by_category(conn, &filter).await {
+        Ok(list) => list,
+        Err(e) => {
+            log::error!("{}", e);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    for item in list.iter_mut() {
+        redact_fancy_secret(&mut item.fancy, user_id);
+    }
+
+    Ok(
+        HttpResponse::Ok().json(PageApi::new(list, total, limit, |item| {
+            FancyCategoryCursorApi {
+                after_difficulty: item.category_difficulty,
+                after_address: item.fancy.address,
+            }
+        })),
+    )
+}
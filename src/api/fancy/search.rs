@@ -0,0 +1,176 @@
+use crate::api::fancy::list::{extract_fancy_list_filter, fancy_cursor, FancyCursorApi};
+use crate::api::fancy::redact_fancy_secret;
+use crate::api::utils::{
+    extract_url_bool_param, extract_url_float_param, extract_url_int_param, extract_url_param,
+};
+use crate::config::get_search_max_scan;
+use crate::db::model::{FancyProviderDbObj, UserDbObj};
+use crate::db::ops::{fancy_count, fancy_list, FancyCursor, FancyOrderBy};
+use crate::fancy::address_to_mixed_case;
+use crate::types::DbAddress;
+use crate::{get_logged_user_or_null, ServerData};
+use actix_session::Session;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Serialize;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FancySearchApi {
+    pub items: Vec<FancyProviderDbObj>,
+    /// Not counted when matching EIP-55 casing
+    pub total: Option<i64>,
+    pub next: Option<FancyCursorApi>,
+}
+
+/// Hex pattern without 0x, case is kept for checksum matching
+fn extract_address_pattern(
+    request: &HttpRequest,
+    param: &str,
+) -> Result<Option<String>, actix_web::Error> {
+    let Some(pattern) = extract_url_param(request, param)? else {
+        return Ok(None);
+    };
+    let pattern = if param == "prefix" {
+        pattern
+            .strip_prefix("0x")
+            .or_else(|| pattern.strip_prefix("0X"))
+            .unwrap_or(&pattern)
+            .to_string()
+    } else {
+        pattern
+    };
+    if pattern.is_empty() {
+        return Ok(None);
+    }
+    if pattern.len() > 40 || !pattern.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "{} has to be at most 40 hex characters",
+            param
+        )));
+    }
+    Ok(Some(pattern))
+}
+
+struct MixedCasePatterns {
+    prefix: Option<String>,
+    suffix: Option<String>,
+    contains: Option<String>,
+}
+
+impl MixedCasePatterns {
+    fn matches(&self, address: DbAddress) -> bool {
+        let mixed = address_to_mixed_case(&address.addr());
+        let mixed = mixed.trim_start_matches("0x");
+        self.prefix.as_ref().is_none_or(|p| mixed.starts_with(p))
+            && self.suffix.as_ref().is_none_or(|s| mixed.ends_with(s))
+            && self.contains.as_ref().is_none_or(|c| mixed.contains(c))
+    }
+}
+
+fn db_cursor(order: FancyOrderBy, fancy: &FancyProviderDbObj) -> FancyCursor {
+    match order {
+        FancyOrderBy::Score => FancyCursor::Score(fancy.score, fancy.address),
+        FancyOrderBy::Created => FancyCursor::Created(fancy.created, fancy.address),
+        FancyOrderBy::Price => FancyCursor::Price(fancy.price, fancy.address),
+    }
+}
+
+/// Listing filters plus prefix, suffix and contains address patterns, price and score ranges
+/// and factory. With checksum=true patterns have to match the EIP-55 form exactly, otherwise
+/// case is ignored.
+pub async fn handle_fancy_search(
+    server_data: web::Data<Box<ServerData>>,
+    request: HttpRequest,
+    session: Session,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user = get_logged_user_or_null!(session);
+    let user_id = user.map(|u| u.uid);
+    let conn = &server_data.db_connection;
+    let mut filter = extract_fancy_list_filter(conn, &request, user_id).await?;
+
+    let patterns = MixedCasePatterns {
+        prefix: extract_address_pattern(&request, "prefix")?,
+        suffix: extract_address_pattern(&request, "suffix")?,
+        contains: extract_address_pattern(&request, "contains")?,
+    };
+    let checksum = extract_url_bool_param(&request, "checksum")?.unwrap_or(false);
+    filter.address_prefix = patterns.prefix.as_ref().map(|p| p.to_lowercase());
+    filter.address_suffix = patterns.suffix.as_ref().map(|s| s.to_lowercase());
+    filter.address_contains = patterns.contains.as_ref().map(|c| c.to_lowercase());
+    filter.price_min = extract_url_int_param(&request, "price_min")?;
+    filter.price_max = extract_url_int_param(&request, "price_max")?;
+    filter.score_min = extract_url_float_param(&request, "score_min")?;
+    filter.score_max = extract_url_float_param(&request, "score_max")?;
+    filter.factory = match extract_url_param(&request, "factory")? {
+        Some(factory) => Some(
+            DbAddress::from_str(&factory)
+                .map_err(|_| actix_web::error::ErrorBadRequest("Invalid factory address"))?,
+        ),
+        None => None,
+    };
+
+    let (mut items, total, next) = if checksum {
+        // lower case match narrows the candidates, casing is checked here page by page
+        let page_size = filter.limit;
+        let max_scan = get_search_max_scan();
+        let mut items = Vec::new();
+        let mut scanned = 0;
+        let next = loop {
+            let batch = match fancy_list(conn, &filter).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    log::error!("{}", e);
+                    return Ok(HttpResponse::InternalServerError().finish());
+                }
+            };
+            let exhausted = (batch.len() as i64) < filter.limit;
+            let mut last = None;
+            for fancy in batch {
+                scanned += 1;
+                last = Some(fancy_cursor(filter.order_by, &fancy));
+                filter.after = Some(db_cursor(filter.order_by, &fancy));
+                if patterns.matches(fancy.address) {
+                    items.push(fancy);
+                    if items.len() as i64 >= page_size {
+                        break;
+                    }
+                }
+            }
+            if items.len() as i64 >= page_size || scanned >= max_scan {
+                break last;
+            }
+            if exhausted {
+                break None;
+            }
+        };
+        (items, None, next)
+    } else {
+        let total = match fancy_count(conn, &filter).await {
+            Ok(total) => total,
+            Err(e) => {
+                log::error!("{}", e);
+                return Ok(HttpResponse::InternalServerError().finish());
+            }
+        };
+        let items = match fancy_list(conn, &filter).await {
+            Ok(items) => items,
+            Err(e) => {
+                log::error!("{}", e);
+                return Ok(HttpResponse::InternalServerError().finish());
+            }
+        };
+        let next = if items.len() as i64 >= filter.limit {
+            items
+                .last()
+                .map(|fancy| fancy_cursor(filter.order_by, fancy))
+        } else {
+            None
+        };
+        (items, Some(total), next)
+    };
+
+    for fancy in items.iter_mut() {
+        redact_fancy_secret(fancy, user_id);
+    }
+    Ok(HttpResponse::Ok().json(FancySearchApi { items, total, next }))
+}
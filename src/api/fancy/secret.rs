@@ -0,0 +1,82 @@
+use crate::db::model::UserDbObj;
+use crate::db::ops::{fancy_get_by_address, insert_fancy_secret_reveal};
+use crate::types::DbAddress;
+use crate::{login_check_and_get, normalize_address, ServerData};
+use actix_session::Session;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct FancySecretResponse {
+    address: DbAddress,
+    salt: String,
+    factory: Option<DbAddress>,
+    public_key_base: Option<String>,
+    init_code_hash: Option<String>,
+}
+
+/// Salt (or private key addition for split-key addresses) of an owned address.
+/// Every reveal is recorded in fancy_secret_reveal.
+pub async fn handle_fancy_secret(
+    server_data: web::Data<Box<ServerData>>,
+    address: web::Path<String>,
+    request: HttpRequest,
+    session: Session,
+) -> HttpResponse {
+    let user: UserDbObj = login_check_and_get!(session);
+    let address = normalize_address!(address.into_inner());
+
+    let conn = &server_data.db_connection;
+    let mut trans = match conn.begin().await {
+        Ok(tx) => tx,
+        Err(err) => {
+            log::error!("Error starting transaction: {}", err);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let fancy = match fancy_get_by_address(&mut *trans, address).await {
+        Ok(Some(fancy)) => fancy,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(err) => {
+            log::error!("Error getting address: {}", err);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if fancy.owner_id != Some(user.uid) {
+        log::warn!(
+            "User {} requested secret of not owned address {}",
+            user.email,
+            address
+        );
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let remote_addr = request
+        .connection_info()
+        .realip_remote_addr()
+        .map(|addr| addr.to_string());
+    if let Err(err) = insert_fancy_secret_reveal(&mut *trans, address, user.uid, remote_addr).await
+    {
+        log::error!("Error saving secret reveal: {}", err);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    match trans.commit().await {
+        Ok(_) => {
+            log::info!("User {} revealed secret of address {}", user.email, address);
+            HttpResponse::Ok().json(FancySecretResponse {
+                address: fancy.address,
+                salt: fancy.salt,
+                factory: fancy.factory,
+                public_key_base: fancy.public_key_base,
+                init_code_hash: fancy.init_code_hash,
+            })
+        }
+        Err(err) => {
+            log::error!("Error committing transaction: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -1,9 +1,11 @@
-use crate::db::model::UserDbObj;
-use crate::db::ops::get_user;
+use crate::api::contract::api::login_check_fn;
+use crate::api::utils::extract_url_int_param;
+use crate::db::model::{TokenLedgerDbObj, UserDbObj};
+use crate::db::ops::{get_user, get_user_ledger_balance, get_user_token_history};
 use crate::{login_check_and_get, ServerData};
 use actix_session::Session;
 use actix_web::web::Data;
-use actix_web::HttpResponse;
+use actix_web::{HttpRequest, HttpResponse};
 use serde::Serialize;
 use sqlx::types::Uuid;
 
@@ -14,11 +16,21 @@ struct UserTokensResp {
     tokens: i64,
 }
 
+const MAX_HISTORY_LIMIT: i64 = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UserTokensHistoryResp {
+    tokens: i64,
+    ledger_balance: i64,
+    entries: Vec<TokenLedgerDbObj>,
+}
+
 pub async fn handle_get_user_tokens(data: Data<Box<ServerData>>, session: Session) -> HttpResponse {
     let session_user: UserDbObj = login_check_and_get!(session);
 
-    let conn = data.db_connection.lock().await;
-    let user = match get_user(&*conn, &session_user.email).await {
+    let conn = &data.db_connection;
+    let user = match get_user(conn, &session_user.email).await {
         Ok(user) => user,
         Err(err) => {
             log::error!("Error getting user: {}", err);
@@ -31,3 +43,49 @@ pub async fn handle_get_user_tokens(data: Data<Box<ServerData>>, session: Sessio
         tokens: user.tokens,
     })
 }
+
+pub async fn handle_get_user_tokens_history(
+    data: Data<Box<ServerData>>,
+    request: HttpRequest,
+    session: Session,
+) -> Result<HttpResponse, actix_web::Error> {
+    let session_user = login_check_fn(session)?;
+    let limit = extract_url_int_param(&request, "limit")?
+        .unwrap_or(100)
+        .clamp(1, MAX_HISTORY_LIMIT);
+
+    let conn = &data.db_connection;
+    let user = match get_user(conn, &session_user.email).await {
+        Ok(user) => user,
+        Err(err) => {
+            log::error!("Error getting user: {}", err);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    let ledger_balance = match get_user_ledger_balance(conn, user.uid).await {
+        Ok(balance) => balance,
+        Err(err) => {
+            log::error!("Error getting ledger balance: {}", err);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    if ledger_balance != user.tokens {
+        log::error!(
+            "Token balance of user {} does not match ledger: {} != {}",
+            user.email,
+            user.tokens,
+            ledger_balance
+        );
+    }
+    match get_user_token_history(conn, user.uid, limit).await {
+        Ok(entries) => Ok(HttpResponse::Ok().json(UserTokensHistoryResp {
+            tokens: user.tokens,
+            ledger_balance,
+            entries,
+        })),
+        Err(err) => {
+            log::error!("Error getting token history: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
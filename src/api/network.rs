@@ -0,0 +1,28 @@
+use crate::db::model::NetworkWithFactories;
+use crate::db::ops::{get_network_factories, get_networks};
+use crate::ServerData;
+use actix_web::{web, HttpResponse};
+
+/// Networks contracts can be deployed to, with factories available there
+pub async fn handle_get_networks(server_data: web::Data<Box<ServerData>>) -> HttpResponse {
+    let conn = &server_data.db_connection;
+
+    let networks = match get_networks(conn, true).await {
+        Ok(networks) => networks,
+        Err(e) => {
+            log::error!("Error getting networks: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let mut res = Vec::with_capacity(networks.len());
+    for network in networks {
+        match get_network_factories(conn, &network.name).await {
+            Ok(factories) => res.push(NetworkWithFactories { network, factories }),
+            Err(e) => {
+                log::error!("Error getting network factories: {}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+    HttpResponse::Ok().json(res)
+}
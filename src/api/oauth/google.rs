@@ -10,7 +10,7 @@ use actix_web::{web, HttpRequest, HttpResponseBuilder};
 use sqlx::Error;
 
 pub async fn handle_login_via_google(data: web::Data<Box<ServerData>>) -> HttpResponse {
-    let db_conn = data.db_connection.lock().await;
+    let db_conn = &data.db_connection;
 
     let query = match create_oauth_query(db_conn.clone(), WEB_PORTAL_DOMAIN.clone()).await {
         Ok(query) => query,
@@ -36,8 +36,8 @@ pub async fn handle_google_callback(
     if let (Some(code), Some(state)) = (code, state) {
         // Exchange the code with a token.
         let obj = {
-            let conn = data.db_connection.lock().await;
-            get_and_remove_oauth_stage(&conn, &state)
+            let conn = &data.db_connection;
+            get_and_remove_oauth_stage(conn, &state)
                 .await
                 .map_err(|err: Error| {
                     log::error!("Error getting oauth stage: {:?}", err);
@@ -66,7 +66,7 @@ pub async fn handle_google_callback(
                 return Ok(HttpResponse::Unauthorized().body("This email is not allowed"));
             }
 
-            match get_user(&data.db_connection.lock().await.clone(), &email).await {
+            match get_user(&data.db_connection, &email).await {
                 Ok(usr) => {
                     if !usr.allow_google_login {
                         log::error!("User {} is not allowed to login with google", email);
@@ -1,14 +1,28 @@
-use crate::api::contract::compile::handle_compile;
+use crate::api::contract::compile::{
+    handle_compile, handle_compile_libraries, handle_compile_versions,
+};
+use crate::api::contract::estimate::handle_contract_estimate;
+use crate::api::contract::verify::handle_contract_verify;
 use crate::api::fancy::buy::handle_fancy_buy_api;
-use crate::api::fancy::deploy::handle_fancy_deploy_start;
+use crate::api::fancy::deploy::{handle_fancy_deploy_retry, handle_fancy_deploy_start};
 use crate::api::fancy::estimate::handle_fancy_estimate_total_hash;
-use crate::api::fancy::job::{handle_finish_job, handle_job_list, handle_new_job};
+use crate::api::fancy::job::{handle_finish_job, handle_job_list, handle_job_work, handle_new_job};
 use crate::api::fancy::list::handle_list;
 use crate::api::fancy::my::handle_my_list;
 use crate::api::fancy::new::handle_fancy_new_many;
-use crate::api::fancy::score::{handle_get_score_categories, handle_score_custom};
-use crate::api::fancy::tokens::handle_get_user_tokens;
+use crate::api::fancy::order::{
+    handle_order_cancel, handle_order_estimate, handle_order_get, handle_order_list,
+    handle_order_new, handle_order_open_list,
+};
+use crate::api::fancy::public_key::{handle_public_key_challenge, handle_public_key_new};
+use crate::api::fancy::score::{
+    handle_fancy_best_by_category, handle_get_score_categories, handle_score_custom,
+};
+use crate::api::fancy::search::handle_fancy_search;
+use crate::api::fancy::secret::handle_fancy_secret;
+use crate::api::fancy::tokens::{handle_get_user_tokens, handle_get_user_tokens_history};
 use crate::api::fancy::{handle_public_key_list, handle_random};
+use crate::api::network::handle_get_networks;
 use crate::api::oauth::google::{handle_google_callback, handle_login_via_google};
 use crate::api::user::handle_greet;
 use crate::api::{contract, user};
@@ -29,21 +43,38 @@ pub fn server_api_scope() -> Scope {
     .route("/set_pass",                     post().to(user::handle_password_set))
     .route("/change_pass",                  post().to(user::handle_password_change))
     .route("/user/tokens",                  get().to(handle_get_user_tokens))
+    .route("/user/tokens/history",          get().to(handle_get_user_tokens_history))
     .route("/fancy/score/{address}",        get().to(handle_score_custom))
     .route("/fancy/categories",             get().to(handle_get_score_categories))
+    .route("/fancy/best/{category}",        get().to(handle_fancy_best_by_category))
     .route("/fancy/random",                 get().to(handle_random))
     .route("/fancy/total_hash",             get().to(handle_fancy_estimate_total_hash))
     .route("/fancy/list",                   get().to(handle_list))
     .route("/fancy/mylist",                 get().to(handle_my_list))
+    .route("/fancy/search",                 get().to(handle_fancy_search))
     .route("/fancy/new_many",               post().to(handle_fancy_new_many))
     .route("/fancy/new_many2",              post().to(handle_fancy_new_many))
     .route("/fancy/buy/{address}",          post().to(handle_fancy_buy_api))
+    .route("/fancy/{address}/secret",       get().to(handle_fancy_secret))
     .route("/fancy/deploy/{contract_id}",   post().to(handle_fancy_deploy_start))
+    .route("/fancy/deploy/{contract_id}/retry", post().to(handle_fancy_deploy_retry))
+    .route("/order/estimate",               post().to(handle_order_estimate))
+    .route("/order/new",                    post().to(handle_order_new))
+    .route("/order/list",                   get().to(handle_order_list))
+    .route("/order/open",                   get().to(handle_order_open_list))
+    .route("/order/{order_id}",             get().to(handle_order_get))
+    .route("/order/{order_id}/cancel",      post().to(handle_order_cancel))
     .route("/public_key_base/list",         get().to(handle_public_key_list))
+    .route("/public_key_base/challenge",    post().to(handle_public_key_challenge))
+    .route("/public_key_base/new",          post().to(handle_public_key_new))
     .route("/job/new",                      post().to(handle_new_job))
+    .route("/job/work",                     post().to(handle_job_work))
     .route("/job/finish/{job_id}",          post().to(handle_finish_job))
     .route("/job/list",                     get().to(handle_job_list))
+    .route("/networks",                     get().to(handle_get_networks))
     .route("/contract/compile",             post().to(handle_compile))
+    .route("/contract/compile/versions",    get().to(handle_compile_versions))
+    .route("/contract/compile/libraries",   get().to(handle_compile_libraries))
     .route("/greet",                        get().to(handle_greet))
     .route("/contract/{contract_id}",       get().to(contract::get_contract_info_api))
     .route("/contract/new",                 post().to(contract::insert_contract_info_api))
@@ -51,4 +82,6 @@ pub fn server_api_scope() -> Scope {
     .route("/contracts/list",               get().to(contract::get_contracts_api))
     .route("/contracts/assignments",        get().to(contract::get_all_contract_assignments))
     .route("contract/{contract_id}/delete", post().to(contract::delete_contract_api))
+    .route("/contract/{contract_id}/estimate", get().to(handle_contract_estimate))
+    .route("/contract/{contract_id}/verify",   post().to(handle_contract_verify))
 }
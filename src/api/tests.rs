@@ -0,0 +1,385 @@
+//! Integration tests of the api scope, run against a test database
+
+use crate::api::scope::server_api_scope;
+use crate::solc::queue::{CompileLimits, CompileQueue};
+use crate::types::DbAddress;
+use crate::ServerData;
+use actix_session::storage::CookieSessionStore;
+use actix_session::SessionMiddleware;
+use actix_web::cookie::Key;
+use actix_web::{test, web, App, HttpResponse};
+use futures_util::future::join_all;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+macro_rules! test_app {
+    ($pool:expr) => {
+        test::init_service(
+            App::new()
+                .wrap(SessionMiddleware::new(
+                    CookieSessionStore::default(),
+                    Key::generate(),
+                ))
+                .app_data(web::Data::new(Box::new(ServerData {
+                    db_connection: $pool,
+                    compile_queue: Arc::new(CompileQueue::new(CompileLimits::from_env())),
+                })))
+                .route("/slow", web::get().to(handle_slow))
+                .service(server_api_scope()),
+        )
+        .await
+    };
+}
+
+const SLOW_QUERY: Duration = Duration::from_secs(5);
+const MINER: &str = "test-miner";
+const PROVIDER: &str = "0x00000000000000000000000000000000000000aa";
+
+async fn handle_slow(server_data: web::Data<Box<ServerData>>) -> HttpResponse {
+    match sqlx::query("SELECT pg_sleep($1)")
+        .bind(SLOW_QUERY.as_secs_f64())
+        .execute(&server_data.db_connection)
+        .await
+    {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Load test: requests keep being served while a long query holds one of the pool connections.
+/// Throughput is only logged, the check is that requests do not wait for the slow query.
+#[sqlx::test]
+async fn concurrent_requests_load_test(pool: sqlx::PgPool) {
+    let app = test_app!(pool);
+
+    let requests = 200;
+    let started = Instant::now();
+    let slow = async {
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/slow").to_request()).await;
+        assert!(res.status().is_success());
+        started.elapsed()
+    };
+    let fast = async {
+        let responses = join_all((0..requests).map(|i| {
+            let uri = if i % 2 == 0 {
+                "/api/networks"
+            } else {
+                "/api/fancy/list?free=all&limit=10"
+            };
+            test::call_service(&app, test::TestRequest::get().uri(uri).to_request())
+        }))
+        .await;
+        assert!(responses.iter().all(|res| res.status().is_success()));
+        started.elapsed()
+    };
+    let (slow_elapsed, fast_elapsed) = futures_util::join!(slow, fast);
+
+    log::info!(
+        "{} requests served in {:?} ({:.0} req/s) while slow query took {:?}",
+        requests,
+        fast_elapsed,
+        requests as f64 / fast_elapsed.as_secs_f64(),
+        slow_elapsed
+    );
+    assert!(slow_elapsed >= SLOW_QUERY);
+    assert!(
+        fast_elapsed < slow_elapsed,
+        "requests waited for the slow query: {:?}",
+        fast_elapsed
+    );
+}
+
+async fn insert_fancy(pool: &sqlx::PgPool, address: &str, category: &str) {
+    insert_fancy_priced(pool, address, category, 1.0, 1000).await;
+}
+
+async fn insert_fancy_priced(
+    pool: &sqlx::PgPool,
+    address: &str,
+    category: &str,
+    score: f64,
+    price: i64,
+) {
+    let job_id: Uuid = sqlx::query_scalar(
+        r"INSERT INTO job_info
+(cruncher_ver, hashes_reported, hashes_accepted, entries_accepted, entries_rejected, cost_reported, miner)
+VALUES ('test', 0, 0, 0, 0, 0, $1) RETURNING uid;",
+    )
+    .bind(MINER)
+    .fetch_one(pool)
+    .await
+    .unwrap();
+    crate::db::ops::insert_fancy_obj(
+        pool,
+        crate::db::model::FancyDbObj {
+            address: DbAddress::from_str(address).unwrap(),
+            salt: "0x00".to_string(),
+            factory: None,
+            public_key_base: None,
+            created: crate::db::utils::get_current_utc_time(),
+            score,
+            job_id: Some(job_id),
+            owner_id: None,
+            price,
+            category: category.to_string(),
+            init_code_hash: None,
+        },
+    )
+    .await
+    .unwrap();
+}
+
+fn list_uri(query: &[(&str, &str)]) -> String {
+    endpoint_uri("/api/fancy/list", query)
+}
+
+fn endpoint_uri(path: &str, query: &[(&str, &str)]) -> String {
+    let query = query
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, utf8_percent_encode(value, NON_ALPHANUMERIC)))
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{}", path, query)
+}
+
+/// Filter values reach the database as bind parameters, never as SQL
+#[sqlx::test]
+async fn fancy_list_injection_test(pool: sqlx::PgPool) {
+    sqlx::query("INSERT INTO miner_info (uid, prov_node_id, prov_name) VALUES ($1, $2, 'test');")
+        .bind(MINER)
+        .bind(PROVIDER)
+        .execute(&pool)
+        .await
+        .unwrap();
+    insert_fancy(
+        &pool,
+        "0x0000000000000000000000000000000000000001",
+        "leading_zeroes",
+    )
+    .await;
+    insert_fancy(&pool, "0x0000000000000000000000000000000000000002", "snake").await;
+    let app = test_app!(pool.clone());
+    let list_request =
+        |query: &[(&str, &str)]| test::TestRequest::get().uri(&list_uri(query)).to_request();
+
+    let page: serde_json::Value = test::call_and_read_body_json(
+        &app,
+        list_request(&[("free", "all"), ("category", "leading_zeroes")]),
+    )
+    .await;
+    assert_eq!(page["total"], 1);
+    let page: serde_json::Value = test::call_and_read_body_json(
+        &app,
+        list_request(&[("free", "all"), ("provider_id", PROVIDER)]),
+    )
+    .await;
+    assert_eq!(page["total"], 2);
+
+    let payloads = [
+        "x' OR '1'='1",
+        "leading_zeroes' OR 'a'='a",
+        "x'; DROP TABLE fancy; --",
+        "x' UNION SELECT * FROM users --",
+    ];
+    for payload in payloads {
+        for param in ["category", "provider_id"] {
+            let res =
+                test::call_service(&app, list_request(&[("free", "all"), (param, payload)])).await;
+            assert_eq!(res.status(), 200, "{}={}", param, payload);
+            let page: serde_json::Value = test::read_body_json(res).await;
+            assert_eq!(page["total"], 0, "{}={} matched rows", param, payload);
+            assert_eq!(page["items"], serde_json::json!([]));
+        }
+        let res = test::call_service(
+            &app,
+            list_request(&[("free", "all"), ("public_key_base", payload)]),
+        )
+        .await;
+        assert_eq!(res.status(), 400, "public_key_base={}", payload);
+    }
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM fancy")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 2);
+}
+
+#[sqlx::test]
+async fn fancy_list_pagination_test(pool: sqlx::PgPool) {
+    sqlx::query("INSERT INTO miner_info (uid, prov_node_id, prov_name) VALUES ($1, $2, 'test');")
+        .bind(MINER)
+        .bind(PROVIDER)
+        .execute(&pool)
+        .await
+        .unwrap();
+    for i in 1..=7 {
+        insert_fancy(&pool, &format!("0x{:040x}", i), "snake").await;
+    }
+    let app = test_app!(pool.clone());
+
+    for order in ["score", "created"] {
+        let mut seen = Vec::new();
+        let mut query = vec![
+            ("free", "all".to_string()),
+            ("order", order.to_string()),
+            ("limit", "3".to_string()),
+        ];
+        loop {
+            let params = query
+                .iter()
+                .map(|(k, v)| (*k, v.as_str()))
+                .collect::<Vec<_>>();
+            let page: serde_json::Value = test::call_and_read_body_json(
+                &app,
+                test::TestRequest::get()
+                    .uri(&list_uri(&params))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(page["total"], 7);
+            let items = page["items"].as_array().unwrap();
+            assert!(items.len() <= 3);
+            seen.extend(items.iter().map(|item| item["address"].clone()));
+            let next = &page["next"];
+            if next.is_null() {
+                break;
+            }
+            query.truncate(3);
+            for (param, key) in [
+                ("after_score", "afterScore"),
+                ("after_created", "afterCreated"),
+                ("after_address", "afterAddress"),
+            ] {
+                match &next[key] {
+                    serde_json::Value::Null => {}
+                    serde_json::Value::String(value) => query.push((param, value.clone())),
+                    value => query.push((param, value.to_string())),
+                }
+            }
+        }
+        let mut unique = seen.clone();
+        unique.sort_by_key(|address| address.to_string());
+        unique.dedup();
+        assert_eq!(seen.len(), 7, "order {}", order);
+        assert_eq!(unique.len(), 7, "order {}", order);
+    }
+
+    // cursor for the other ordering is rejected
+    let res = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri(&list_uri(&[
+                ("order", "created"),
+                ("after_score", "1"),
+                ("after_address", PROVIDER),
+            ]))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(res.status(), 400);
+}
+
+#[sqlx::test]
+async fn fancy_search_test(pool: sqlx::PgPool) {
+    sqlx::query("INSERT INTO miner_info (uid, prov_node_id, prov_name) VALUES ($1, $2, 'test');")
+        .bind(MINER)
+        .bind(PROVIDER)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let dead = "0xdeadbeef00000000000000000000000000000001";
+    let beef = "0x1000000000000000000000000000000000c0beef";
+    let cafe = "0x2000000000000000cafe00000000000000000002";
+    insert_fancy_priced(&pool, dead, "snake", 5.0, 3000).await;
+    insert_fancy_priced(&pool, beef, "snake", 3.0, 1000).await;
+    insert_fancy_priced(&pool, cafe, "snake", 1.0, 2000).await;
+    let app = test_app!(pool.clone());
+
+    let search = |query: &[(&str, &str)]| {
+        let mut query = query.to_vec();
+        query.push(("free", "all"));
+        test::TestRequest::get()
+            .uri(&endpoint_uri("/api/fancy/search", &query))
+            .to_request()
+    };
+    let addresses = |page: &serde_json::Value| {
+        page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["address"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    for (query, expected) in [
+        (vec![("prefix", "dead")], vec![dead]),
+        (vec![("prefix", "0xDEADBEEF")], vec![dead]),
+        (vec![("suffix", "beef")], vec![beef]),
+        (vec![("contains", "beef")], vec![dead, beef]),
+        (vec![("contains", "CAFE")], vec![cafe]),
+        (vec![("score_min", "2"), ("score_max", "4")], vec![beef]),
+        (
+            vec![("price_max", "2000"), ("order", "price")],
+            vec![beef, cafe],
+        ),
+        (vec![("order", "price")], vec![beef, cafe, dead]),
+    ] {
+        let page: serde_json::Value = test::call_and_read_body_json(&app, search(&query)).await;
+        assert_eq!(addresses(&page), expected, "{:?}", query);
+        assert_eq!(page["total"], expected.len(), "{:?}", query);
+    }
+
+    // price ordering pages through cursor
+    let page: serde_json::Value =
+        test::call_and_read_body_json(&app, search(&[("order", "price"), ("limit", "2")])).await;
+    assert_eq!(page["next"]["afterPrice"], 2000);
+    let page: serde_json::Value = test::call_and_read_body_json(
+        &app,
+        search(&[
+            ("order", "price"),
+            ("after_price", "2000"),
+            ("after_address", cafe),
+        ]),
+    )
+    .await;
+    assert_eq!(addresses(&page), vec![dead]);
+
+    // checksum matching follows EIP-55 casing of the address
+    let mixed = crate::fancy::address_to_mixed_case(&DbAddress::from_str(dead).unwrap().addr());
+    let prefix = mixed[2..10].to_string();
+    let flipped = prefix
+        .chars()
+        .map(|c| {
+            if c.is_ascii_uppercase() {
+                c.to_ascii_lowercase()
+            } else {
+                c.to_ascii_uppercase()
+            }
+        })
+        .collect::<String>();
+    let page: serde_json::Value =
+        test::call_and_read_body_json(&app, search(&[("prefix", &prefix), ("checksum", "true")]))
+            .await;
+    assert_eq!(addresses(&page), vec![dead]);
+    assert!(page["total"].is_null());
+    let page: serde_json::Value =
+        test::call_and_read_body_json(&app, search(&[("prefix", &flipped), ("checksum", "true")]))
+            .await;
+    assert!(addresses(&page).is_empty());
+    let page: serde_json::Value =
+        test::call_and_read_body_json(&app, search(&[("prefix", &flipped)])).await;
+    assert_eq!(addresses(&page), vec![dead]);
+
+    for query in [
+        vec![("prefix", "xyz")],
+        vec![("contains", "%")],
+        vec![("suffix", &"a".repeat(41))],
+    ] {
+        let res = test::call_service(&app, search(&query)).await;
+        assert_eq!(res.status(), 400, "{:?}", query);
+    }
+}
@@ -35,14 +35,14 @@ pub async fn handle_password_change(
     let random_duration = rng.random_range(300..=600);
     tokio::time::sleep(Duration::from_millis(random_duration)).await;
 
-    let db_conn = data.db_connection.lock().await;
+    let db_conn = &data.db_connection;
 
     // Hash the old password
     let old_password_hash = pass_to_hash(change_pass.old_password.as_bytes());
 
     // Fetch the user from the database using the provided email
     log::info!("Fetching user: {}", email);
-    let usr = match get_user(&*db_conn, &email).await {
+    let usr = match get_user(db_conn, &email).await {
         Ok(usr) => usr,
         Err(err) => {
             log::error!("Error getting user: {}", err);
@@ -64,5 +64,5 @@ pub async fn handle_password_change(
     // Hash the new password
     let new_password_hash = pass_to_hash(change_pass.new_password.as_bytes());
 
-    set_password_to_response(session, &db_conn, &email, &new_password_hash).await
+    set_password_to_response(session, db_conn, &email, &new_password_hash).await
 }
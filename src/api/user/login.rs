@@ -40,12 +40,12 @@ pub async fn handle_login(
         return HttpResponse::Unauthorized().body("This email is not allowed");
     }
 
-    let db_conn = data.db_connection.lock().await;
+    let db_conn = &data.db_connection;
 
     let key = pass_to_hash(login.password.as_bytes());
 
     //log::info!("Getting user: {}", email);
-    let usr = match get_user(&*db_conn, &email).await {
+    let usr = match get_user(db_conn, &email).await {
         Ok(usr) => usr,
         Err(err) => {
             log::error!("Error getting user: {}", err);
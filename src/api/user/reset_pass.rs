@@ -26,9 +26,9 @@ pub async fn handle_password_reset(
         return HttpResponse::Unauthorized().body("This email is not allowed");
     }
 
-    let db_conn = data.db_connection.lock().await;
+    let db_conn = &data.db_connection;
 
-    let user = match get_user(&*db_conn, &email).await {
+    let user = match get_user(db_conn, &email).await {
         Ok(user) => user,
         Err(_err) => {
             if !*ALLOW_CREATING_NEW_ACCOUNTS {
@@ -49,7 +49,7 @@ pub async fn handle_password_reset(
                 set_pass_token_date: None,
                 tokens: 0,
             };
-            match insert_user(&*db_conn, &user_to_insert).await {
+            match insert_user(db_conn, &user_to_insert).await {
                 Ok(user) => user,
                 Err(err) => {
                     log::error!("Error inserting user: {}", err);
@@ -76,7 +76,7 @@ pub async fn handle_password_reset(
         str.push_str(&num.to_string());
     }
 
-    match save_reset_token(&db_conn, &email, &str).await {
+    match save_reset_token(db_conn, &email, &str).await {
         Ok(()) => {}
         Err(err) => {
             return HttpResponse::InternalServerError()
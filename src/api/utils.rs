@@ -1,6 +1,8 @@
+use crate::config::{get_list_default_page_size, get_list_max_page_size};
 use actix_web::HttpRequest;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use percent_encoding::percent_decode_str;
+use serde::Serialize;
 
 pub fn extract_url_param(
     request: &HttpRequest,
@@ -56,6 +58,23 @@ pub fn extract_url_bool_param(
     }
 }
 
+pub fn extract_url_float_param(
+    request: &HttpRequest,
+    param: &str,
+) -> Result<Option<f64>, actix_web::Error> {
+    if let Some(str) = extract_url_param(request, param)? {
+        match str.parse::<f64>() {
+            Ok(val) if val.is_finite() => Ok(Some(val)),
+            _ => Err(actix_web::error::ErrorBadRequest(format!(
+                "Failed to parse {} as number",
+                param
+            ))),
+        }
+    } else {
+        Ok(None)
+    }
+}
+
 pub fn extract_url_date_param(
     request: &HttpRequest,
     param: &str,
@@ -72,3 +91,35 @@ pub fn extract_url_date_param(
         Ok(None)
     }
 }
+
+/// Page size from limit parameter, clamped to the server maximum
+pub fn extract_page_limit(request: &HttpRequest) -> Result<i64, actix_web::Error> {
+    let limit = extract_url_int_param(request, "limit")?.unwrap_or(get_list_default_page_size());
+    if limit <= 0 {
+        return Err(actix_web::error::ErrorBadRequest(
+            "limit has to be a positive number",
+        ));
+    }
+    Ok(limit.min(get_list_max_page_size()))
+}
+
+/// One page of a listing, next is the cursor to pass for the following page
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageApi<T, C> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub next: Option<C>,
+}
+
+impl<T, C> PageApi<T, C> {
+    /// Next cursor is taken from the last item only when the page is full
+    pub fn new(items: Vec<T>, total: i64, limit: i64, cursor: impl Fn(&T) -> C) -> Self {
+        let next = if items.len() as i64 >= limit {
+            items.last().map(cursor)
+        } else {
+            None
+        };
+        PageApi { items, total, next }
+    }
+}
@@ -20,3 +20,158 @@ pub fn get_base_difficulty() -> f64 {
 pub fn get_base_difficulty_price() -> i64 {
     get_env_int("BASE_DIFFICULTY_PRICE", 1000)
 }
+
+/// Addresses with lower total score are rejected on ingestion, unless they match an order
+pub fn get_min_accepted_score() -> f64 {
+    get_env_float("MIN_ACCEPTED_SCORE", 1E10)
+}
+
+pub fn get_score_rules_file() -> Option<String> {
+    env::var("SCORE_RULES_FILE").ok()
+}
+
+/// Key used to sign deployment transactions, hex encoded.
+/// Networks reference the variable by name, DEPLOYER_PRIVATE_KEY by default.
+pub fn get_deployer_private_key(key_env: &str) -> Option<String> {
+    env::var(key_env).ok()
+}
+
+/// JSON-RPC endpoint overriding urls registered for the network, read from RPC_URL_<NETWORK> (e.g. RPC_URL_HOLESKY)
+pub fn get_rpc_url(network: &str) -> Option<String> {
+    env::var(format!(
+        "RPC_URL_{}",
+        network.to_uppercase().replace(['-', ' '], "_")
+    ))
+    .ok()
+}
+
+/// How long to wait for deployment transaction receipt, in seconds
+pub fn get_deploy_receipt_timeout() -> i64 {
+    get_env_int("DEPLOY_RECEIPT_TIMEOUT", 300)
+}
+
+/// Blocks on top of the deployment block before contract is considered deployed
+pub fn get_deploy_confirmations() -> i64 {
+    get_env_int("DEPLOY_CONFIRMATIONS", 2)
+}
+
+/// How long a deploy worker keeps claimed contract before other workers can take it over, in seconds
+pub fn get_deploy_claim_timeout() -> i64 {
+    get_env_int("DEPLOY_CLAIM_TIMEOUT", 3600)
+}
+
+/// Tokens charged per unit of native currency when estimating deployment cost
+pub fn get_deploy_tokens_per_native() -> Option<f64> {
+    env::var("DEPLOY_TOKENS_PER_NATIVE")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+}
+
+/// Directory with cached solc binaries, named solc-<version>
+pub fn get_solc_dir() -> String {
+    env::var("SOLC_DIR").unwrap_or("/addressology/bin".to_string())
+}
+
+/// Directory with vendored solidity packages, unpacked to <name>@<version>
+pub fn get_solc_lib_dir() -> String {
+    env::var("SOLC_LIB_DIR").unwrap_or("/addressology/lib".to_string())
+}
+
+/// Base url of the official solc-bin list
+pub fn get_solc_bin_url() -> String {
+    env::var("SOLC_BIN_URL").unwrap_or("https://binaries.soliditylang.org".to_string())
+}
+
+/// Use pre-seeded solc binaries only, never download
+pub fn get_solc_offline() -> bool {
+    env::var("SOLC_OFFLINE")
+        .map(|v| v == "1" || v.to_lowercase() == "true")
+        .unwrap_or(false)
+}
+
+/// Solc process is killed after this many seconds
+pub fn get_compile_timeout() -> u64 {
+    get_env_int("COMPILE_TIMEOUT", 60) as u64
+}
+
+/// Address space limit of solc process in MB, 0 disables the limit
+pub fn get_compile_memory_limit_mb() -> u64 {
+    get_env_int("COMPILE_MEMORY_LIMIT_MB", 2048) as u64
+}
+
+/// Solc processes running at once
+pub fn get_compile_workers() -> usize {
+    get_env_int("COMPILE_WORKERS", 2) as usize
+}
+
+/// Compilations waiting for a free worker, further requests are rejected
+pub fn get_compile_queue_size() -> usize {
+    get_env_int("COMPILE_QUEUE_SIZE", 16) as usize
+}
+
+/// Total size of sources accepted for compilation, in bytes
+pub fn get_compile_max_input_bytes() -> usize {
+    get_env_int("COMPILE_MAX_INPUT_BYTES", 1024 * 1024) as usize
+}
+
+pub fn get_compile_max_sources() -> usize {
+    get_env_int("COMPILE_MAX_SOURCES", 64) as usize
+}
+
+/// Compilations allowed per user (or ip for anonymous requests) within a minute
+pub fn get_compile_rate_limit() -> usize {
+    get_env_int("COMPILE_RATE_LIMIT", 10) as usize
+}
+
+/// Compilation results kept in memory
+pub fn get_compile_cache_size() -> usize {
+    get_env_int("COMPILE_CACHE_SIZE", 256) as usize
+}
+
+/// Comma separated ips of reverse proxies, X-Forwarded-For is trusted only from them
+pub fn get_trusted_proxies() -> Vec<std::net::IpAddr> {
+    env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|ip| ip.trim().parse().ok())
+        .collect()
+}
+
+/// Api key of Etherscan compatible explorer, networks reference the variable by name
+pub fn get_explorer_api_key(key_env: &str) -> Option<String> {
+    env::var(key_env).ok().filter(|key| !key.is_empty())
+}
+
+/// Sourcify server, verification there is skipped when set to empty string
+pub fn get_sourcify_url() -> Option<String> {
+    env::var("SOURCIFY_URL")
+        .unwrap_or("https://sourcify.dev/server".to_string())
+        .split_whitespace()
+        .next()
+        .map(|url| url.trim_end_matches('/').to_string())
+}
+
+/// Delay between verification attempts and status checks, in seconds
+pub fn get_verify_retry_interval() -> i64 {
+    get_env_int("VERIFY_RETRY_INTERVAL", 30)
+}
+
+/// Verification is marked failed after this many unsuccessful attempts
+pub fn get_verify_max_attempts() -> i64 {
+    get_env_int("VERIFY_MAX_ATTEMPTS", 20)
+}
+
+/// Rows returned by list endpoints when no limit is given
+pub fn get_list_default_page_size() -> i64 {
+    get_env_int("LIST_DEFAULT_PAGE_SIZE", 100)
+}
+
+/// Larger limits requested by clients are lowered to this value
+pub fn get_list_max_page_size() -> i64 {
+    get_env_int("LIST_MAX_PAGE_SIZE", 1000)
+}
+
+/// Rows checked against EIP-55 casing in one search request, the page may come back short
+pub fn get_search_max_scan() -> i64 {
+    get_env_int("SEARCH_MAX_SCAN", 10000)
+}
@@ -1,3 +1,4 @@
+use crate::db::model::ContractVerificationDbObj;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::encode::IsNull;
@@ -12,10 +13,31 @@ pub enum DeployStatus {
     None,
     Requested,
     TxSent,
+    /// Receipt received, waiting for confirmations
+    Mined,
     Failed,
     Succeeded,
 }
 
+impl DeployStatus {
+    /// Allowed deployment state transitions, Failed goes back to Requested only on user retry
+    pub fn can_transition_to(&self, next: &DeployStatus) -> bool {
+        matches!(
+            (self, next),
+            (DeployStatus::None, DeployStatus::Requested)
+                | (DeployStatus::Requested, DeployStatus::TxSent)
+                | (DeployStatus::Requested, DeployStatus::Failed)
+                // contract found already deployed at its address
+                | (DeployStatus::Requested, DeployStatus::Succeeded)
+                | (DeployStatus::TxSent, DeployStatus::Mined)
+                | (DeployStatus::TxSent, DeployStatus::Failed)
+                | (DeployStatus::Mined, DeployStatus::Succeeded)
+                | (DeployStatus::Mined, DeployStatus::Failed)
+                | (DeployStatus::Failed, DeployStatus::Requested)
+        )
+    }
+}
+
 impl FromStr for DeployStatus {
     type Err = String;
 
@@ -23,6 +45,7 @@ impl FromStr for DeployStatus {
         match s {
             "requested" => Ok(DeployStatus::Requested),
             "tx_sent" => Ok(DeployStatus::TxSent),
+            "mined" => Ok(DeployStatus::Mined),
             "failed" => Ok(DeployStatus::Failed),
             "succeeded" => Ok(DeployStatus::Succeeded),
             "" => Ok(DeployStatus::None),
@@ -37,6 +60,7 @@ impl Display for DeployStatus {
             DeployStatus::None => write!(f, ""),
             DeployStatus::Requested => write!(f, "requested"),
             DeployStatus::TxSent => write!(f, "tx_sent"),
+            DeployStatus::Mined => write!(f, "mined"),
             DeployStatus::Failed => write!(f, "failed"),
             DeployStatus::Succeeded => write!(f, "succeeded"),
         }
@@ -74,7 +98,7 @@ where
 #[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ContractDbObj {
-    pub contract_id: String,
+    pub contract_id: Uuid,
     pub user_id: Uuid,
     pub created: NaiveDateTime,
     pub address: Option<String>,
@@ -85,13 +109,23 @@ pub struct ContractDbObj {
     pub deploy_requested: Option<NaiveDateTime>,
     pub deploy_sent: Option<NaiveDateTime>,
     pub deployed: Option<NaiveDateTime>,
+    pub deploy_error: Option<String>,
+    /// Block the deployment transaction was mined in
+    pub deploy_block: Option<i64>,
+    pub compiler_version: Option<String>,
+    /// Compiler settings as json, needed to reproduce the build for verification
+    pub compiler_settings: Option<String>,
+    /// Source verification on block explorers, filled by api handlers
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub verifications: Vec<ContractVerificationDbObj>,
 }
 
 #[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ContractAddressDbObj {
-    pub contract_id: String,
-    pub user_id: String,
+    pub contract_id: Uuid,
+    pub user_id: Uuid,
     pub created: NaiveDateTime,
     pub address: String,
     pub network: String,
@@ -101,3 +135,27 @@ pub struct ContractAddressDbObj {
     pub deploy_sent: Option<NaiveDateTime>,
     pub deployed: Option<NaiveDateTime>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deploy_status_transitions() {
+        use DeployStatus::*;
+        let happy_path = [None, Requested, TxSent, Mined, Succeeded];
+        for pair in happy_path.windows(2) {
+            assert!(pair[0].can_transition_to(&pair[1]), "{:?}", pair);
+        }
+        for status in [Requested, TxSent, Mined] {
+            assert!(status.can_transition_to(&Failed));
+        }
+        assert!(Failed.can_transition_to(&Requested));
+        assert!(Requested.can_transition_to(&Succeeded));
+        assert!(!Succeeded.can_transition_to(&Requested));
+        assert!(!Succeeded.can_transition_to(&Failed));
+        assert!(!TxSent.can_transition_to(&Requested));
+        assert!(!Failed.can_transition_to(&TxSent));
+        assert_eq!(DeployStatus::from_str("mined"), Ok(Mined));
+    }
+}
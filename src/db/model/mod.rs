@@ -1,7 +1,15 @@
 mod contract;
+mod network;
+mod order;
+mod token;
+mod verification;
 
 pub use contract::*;
+pub use network::*;
+pub use order::*;
 use std::collections::BTreeMap;
+pub use token::*;
+pub use verification::*;
 
 use crate::types::DbAddress;
 use chrono::NaiveDateTime;
@@ -74,6 +82,12 @@ pub struct ContractFactoryDbObject {
     pub address: DbAddress,
     pub added: NaiveDateTime,
     pub user_id: Option<Uuid>,
+    pub kind: String,
+    pub proxy_init_code_hash: String,
+    pub salt_guard: String,
+    pub guard_sender: Option<DbAddress>,
+    /// Plain CREATE2 deployer registered by submitted addresses, not a CREATE3 factory
+    pub create2_deployer: bool,
 }
 
 #[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
@@ -89,6 +103,8 @@ pub struct FancyDbObj {
     pub owner_id: Option<Uuid>,
     pub price: i64,
     pub category: String,
+    /// Set for CREATE2 addresses, factory is then the deployer
+    pub init_code_hash: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
@@ -103,18 +119,47 @@ pub struct FancyProviderDbObj {
     pub owner_id: Option<Uuid>,
     pub price: i64,
     pub category: String,
+    pub init_code_hash: Option<String>,
     pub job_id: Option<Uuid>,
     pub prov_name: String,
     pub prov_node_id: Option<DbAddress>,
     pub prov_reward_addr: Option<DbAddress>,
 }
 
+/// Score of the address in a single category
+#[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FancyScoreDbObj {
+    pub address: DbAddress,
+    pub category: String,
+    pub score: f64,
+    pub difficulty: f64,
+    /// 1 for the winning category
+    pub category_rank: i32,
+}
+
+/// Address listed by its score in a selected category
+#[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FancyCategoryScoreDbObj {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub fancy: FancyProviderDbObj,
+    pub category_score: f64,
+    pub category_difficulty: f64,
+    pub category_rank: i32,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ContractCreateFromApi {
     pub address: Option<String>,
     pub network: String,
     pub data: String,
+    #[serde(default)]
+    pub compiler_version: Option<String>,
+    #[serde(default)]
+    pub compiler_settings: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
@@ -148,6 +193,7 @@ pub struct JobDbObj {
 #[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct JobMinerDbReadObj {
+    pub uid: Uuid,
     pub cruncher_ver: String,
     pub started_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
@@ -164,3 +210,34 @@ pub struct JobMinerDbReadObj {
     pub prov_name: Option<String>,
     pub prov_extra_info: Option<String>,
 }
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobWorkDbObj {
+    pub uid: Uuid,
+    pub job_id: Uuid,
+    pub factory: Option<DbAddress>,
+    pub public_key_base: Option<String>,
+    pub salt_prefix: String,
+    pub min_score: f64,
+    pub created: NaiveDateTime,
+}
+
+/// Audit entry written every time owner reads salt or key addition of the address
+#[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FancySecretRevealDbObj {
+    pub uid: Uuid,
+    pub address: DbAddress,
+    pub user_id: Uuid,
+    pub remote_addr: Option<String>,
+    pub revealed: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkTargetDbObj {
+    pub factory: Option<DbAddress>,
+    pub public_key_base: Option<String>,
+    pub unsold: i64,
+}
@@ -0,0 +1,98 @@
+use crate::types::DbAddress;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GasStrategy {
+    /// Gas price suggested by the node
+    #[default]
+    Legacy,
+    /// Type 2 transaction, max fee is twice the base fee plus tip
+    Eip1559,
+}
+
+impl FromStr for GasStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "legacy" => Ok(GasStrategy::Legacy),
+            "eip1559" => Ok(GasStrategy::Eip1559),
+            _ => Err(format!("Invalid gas strategy: {}", s)),
+        }
+    }
+}
+
+impl Display for GasStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GasStrategy::Legacy => write!(f, "legacy"),
+            GasStrategy::Eip1559 => write!(f, "eip1559"),
+        }
+    }
+}
+
+/// Network contracts can be deployed to, rpc urls and key reference are never sent to clients
+#[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkDbObj {
+    pub name: String,
+    pub chain_id: i64,
+    #[serde(skip)]
+    pub rpc_urls: Vec<String>,
+    pub explorer_url: Option<String>,
+    pub gas_strategy: String,
+    pub gas_multiplier: f64,
+    /// Name of environment variable holding the deployer key
+    #[serde(skip)]
+    pub deployer_key_env: String,
+    /// Etherscan compatible api used for source verification
+    pub explorer_api_url: Option<String>,
+    /// Name of environment variable holding the explorer api key
+    #[serde(skip)]
+    pub explorer_key_env: String,
+    pub enabled: bool,
+    pub added: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkWithFactories {
+    #[serde(flatten)]
+    pub network: NetworkDbObj,
+    pub factories: Vec<DbAddress>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_serialize() {
+        let network = NetworkDbObj {
+            name: "holesky".to_string(),
+            chain_id: 17000,
+            rpc_urls: vec!["https://rpc.example/secret-key".to_string()],
+            explorer_url: Some("https://holesky.etherscan.io".to_string()),
+            gas_strategy: GasStrategy::Eip1559.to_string(),
+            gas_multiplier: 1.2,
+            deployer_key_env: "HOLESKY_DEPLOYER_KEY".to_string(),
+            explorer_api_url: Some("https://api-holesky.etherscan.io/api".to_string()),
+            explorer_key_env: "HOLESKY_ETHERSCAN_KEY".to_string(),
+            enabled: true,
+            added: chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc(),
+        };
+        let json = serde_json::to_string(&NetworkWithFactories {
+            network,
+            factories: Vec::new(),
+        })
+        .unwrap();
+        assert!(json.contains("\"chainId\":17000"));
+        assert!(!json.contains("secret-key"));
+        assert!(!json.contains("HOLESKY_DEPLOYER_KEY"));
+        assert!(!json.contains("HOLESKY_ETHERSCAN_KEY"));
+        assert_eq!(GasStrategy::from_str("EIP1559"), Ok(GasStrategy::Eip1559));
+    }
+}
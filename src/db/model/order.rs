@@ -0,0 +1,83 @@
+use crate::types::DbAddress;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::types::Uuid;
+use sqlx::{Database, Decode, Encode, Postgres};
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum OrderStatus {
+    Open,
+    Fulfilled,
+    Cancelled,
+}
+
+impl FromStr for OrderStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(OrderStatus::Open),
+            "fulfilled" => Ok(OrderStatus::Fulfilled),
+            "cancelled" => Ok(OrderStatus::Cancelled),
+            _ => Err(format!("Invalid order status: {}", s)),
+        }
+    }
+}
+
+impl Display for OrderStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderStatus::Open => write!(f, "open"),
+            OrderStatus::Fulfilled => write!(f, "fulfilled"),
+            OrderStatus::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl sqlx::Type<sqlx::Postgres> for OrderStatus {
+    fn type_info() -> <Postgres as sqlx::Database>::TypeInfo {
+        <String as sqlx::Type<Postgres>>::type_info()
+    }
+    fn compatible(ty: &<Postgres as sqlx::Database>::TypeInfo) -> bool {
+        <String as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for OrderStatus
+where
+    &'r str: Decode<'r, DB>,
+{
+    fn decode(value: <DB as Database>::ValueRef<'r>) -> sqlx::Result<Self, BoxDynError> {
+        let value: &str = Decode::decode(value)?;
+        OrderStatus::from_str(value).map_err(Into::into)
+    }
+}
+
+impl<'q, DB: Database> Encode<'q, DB> for OrderStatus
+where
+    String: sqlx::Encode<'q, DB>,
+{
+    fn encode_by_ref(&self, buf: &mut DB::ArgumentBuffer<'q>) -> sqlx::Result<IsNull, BoxDynError> {
+        Encode::<DB>::encode(self.to_string(), buf)
+    }
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderDbObj {
+    pub uid: Uuid,
+    pub user_id: Uuid,
+    pub kind: String,
+    pub pattern: String,
+    pub difficulty: f64,
+    pub price: i64,
+    pub budget: i64,
+    pub status: OrderStatus,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+    pub address: Option<DbAddress>,
+}
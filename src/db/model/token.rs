@@ -0,0 +1,146 @@
+use crate::types::DbAddress;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::types::Uuid;
+use sqlx::{Database, Decode, Encode, Postgres};
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum TokenLedgerKind {
+    /// Balance from before the ledger was introduced
+    Opening,
+    Grant,
+    Purchase,
+    /// Tokens moved to escrow when custom order is placed
+    OrderReserve,
+    /// Escrowed tokens moved to sales when order is fulfilled
+    OrderSettle,
+    Refund,
+    Adjustment,
+}
+
+impl FromStr for TokenLedgerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "opening" => Ok(TokenLedgerKind::Opening),
+            "grant" => Ok(TokenLedgerKind::Grant),
+            "purchase" => Ok(TokenLedgerKind::Purchase),
+            "orderReserve" => Ok(TokenLedgerKind::OrderReserve),
+            "orderSettle" => Ok(TokenLedgerKind::OrderSettle),
+            "refund" => Ok(TokenLedgerKind::Refund),
+            "adjustment" => Ok(TokenLedgerKind::Adjustment),
+            _ => Err(format!("Invalid token ledger kind: {}", s)),
+        }
+    }
+}
+
+impl Display for TokenLedgerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenLedgerKind::Opening => write!(f, "opening"),
+            TokenLedgerKind::Grant => write!(f, "grant"),
+            TokenLedgerKind::Purchase => write!(f, "purchase"),
+            TokenLedgerKind::OrderReserve => write!(f, "orderReserve"),
+            TokenLedgerKind::OrderSettle => write!(f, "orderSettle"),
+            TokenLedgerKind::Refund => write!(f, "refund"),
+            TokenLedgerKind::Adjustment => write!(f, "adjustment"),
+        }
+    }
+}
+
+impl sqlx::Type<sqlx::Postgres> for TokenLedgerKind {
+    fn type_info() -> <Postgres as sqlx::Database>::TypeInfo {
+        <String as sqlx::Type<Postgres>>::type_info()
+    }
+    fn compatible(ty: &<Postgres as sqlx::Database>::TypeInfo) -> bool {
+        <String as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for TokenLedgerKind
+where
+    &'r str: Decode<'r, DB>,
+{
+    fn decode(value: <DB as Database>::ValueRef<'r>) -> sqlx::Result<Self, BoxDynError> {
+        let value: &str = Decode::decode(value)?;
+        TokenLedgerKind::from_str(value).map_err(Into::into)
+    }
+}
+
+impl<'q, DB: Database> Encode<'q, DB> for TokenLedgerKind
+where
+    String: sqlx::Encode<'q, DB>,
+{
+    fn encode_by_ref(&self, buf: &mut DB::ArgumentBuffer<'q>) -> sqlx::Result<IsNull, BoxDynError> {
+        Encode::<DB>::encode(self.to_string(), buf)
+    }
+}
+
+/// Side of a ledger transaction
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum LedgerAccount {
+    User(Uuid),
+    /// Source of granted tokens
+    Treasury,
+    /// Tokens paid for addresses
+    Sales,
+    /// Tokens reserved by open orders
+    Escrow,
+}
+
+impl LedgerAccount {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LedgerAccount::User(_) => "user",
+            LedgerAccount::Treasury => "treasury",
+            LedgerAccount::Sales => "sales",
+            LedgerAccount::Escrow => "escrow",
+        }
+    }
+
+    pub fn user_id(&self) -> Option<Uuid> {
+        match self {
+            LedgerAccount::User(user_id) => Some(*user_id),
+            _ => None,
+        }
+    }
+}
+
+/// What the transaction was about
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct TokenLedgerRefs {
+    pub address: Option<DbAddress>,
+    pub order_id: Option<Uuid>,
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenLedgerDbObj {
+    pub uid: Uuid,
+    pub tx_id: Uuid,
+    pub account: String,
+    pub user_id: Option<Uuid>,
+    pub amount: i64,
+    pub kind: TokenLedgerKind,
+    pub address: Option<DbAddress>,
+    pub order_id: Option<Uuid>,
+    pub note: Option<String>,
+    pub created: NaiveDateTime,
+}
+
+/// User whose cached balance differs from the ledger
+#[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenBalanceMismatchDbObj {
+    pub uid: Uuid,
+    pub email: String,
+    pub tokens: i64,
+    pub ledger_balance: i64,
+}
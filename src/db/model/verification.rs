@@ -0,0 +1,115 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::types::Uuid;
+use sqlx::{Database, Decode, Encode, Postgres};
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Explorer {
+    /// Etherscan compatible api registered for the network, Blockscout works too
+    Etherscan,
+    Sourcify,
+}
+
+impl FromStr for Explorer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "etherscan" => Ok(Explorer::Etherscan),
+            "sourcify" => Ok(Explorer::Sourcify),
+            _ => Err(format!("Invalid explorer: {}", s)),
+        }
+    }
+}
+
+impl Display for Explorer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Explorer::Etherscan => write!(f, "etherscan"),
+            Explorer::Sourcify => write!(f, "sourcify"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub enum VerifyStatus {
+    /// Waiting for submission
+    Pending,
+    /// Accepted by explorer, waiting for the result
+    Submitted,
+    Verified,
+    Failed,
+}
+
+impl FromStr for VerifyStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(VerifyStatus::Pending),
+            "submitted" => Ok(VerifyStatus::Submitted),
+            "verified" => Ok(VerifyStatus::Verified),
+            "failed" => Ok(VerifyStatus::Failed),
+            _ => Err(format!("Invalid verify status: {}", s)),
+        }
+    }
+}
+
+impl Display for VerifyStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyStatus::Pending => write!(f, "pending"),
+            VerifyStatus::Submitted => write!(f, "submitted"),
+            VerifyStatus::Verified => write!(f, "verified"),
+            VerifyStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl sqlx::Type<sqlx::Postgres> for VerifyStatus {
+    fn type_info() -> <Postgres as sqlx::Database>::TypeInfo {
+        <String as sqlx::Type<Postgres>>::type_info()
+    }
+    fn compatible(ty: &<Postgres as sqlx::Database>::TypeInfo) -> bool {
+        <String as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for VerifyStatus
+where
+    &'r str: Decode<'r, DB>,
+{
+    fn decode(value: <DB as Database>::ValueRef<'r>) -> sqlx::Result<Self, BoxDynError> {
+        let value: &str = Decode::decode(value)?;
+        VerifyStatus::from_str(value).map_err(Into::into)
+    }
+}
+
+impl<'q, DB: Database> Encode<'q, DB> for VerifyStatus
+where
+    String: sqlx::Encode<'q, DB>,
+{
+    fn encode_by_ref(&self, buf: &mut DB::ArgumentBuffer<'q>) -> sqlx::Result<IsNull, BoxDynError> {
+        Encode::<DB>::encode(self.to_string(), buf)
+    }
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractVerificationDbObj {
+    pub contract_id: Uuid,
+    pub explorer: String,
+    pub status: VerifyStatus,
+    /// Etherscan submission id, polled until the result is known
+    pub guid: Option<String>,
+    pub attempts: i32,
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub next_check: NaiveDateTime,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
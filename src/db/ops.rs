@@ -1,10 +1,18 @@
 mod contract;
 mod fancy;
+mod network;
+mod order;
+mod token;
 mod user;
+mod verification;
 
 pub use contract::*;
 pub use fancy::*;
+pub use network::*;
+pub use order::*;
+pub use token::*;
 pub use user::*;
+pub use verification::*;
 
 use std::future::Future;
 use std::time::Duration;
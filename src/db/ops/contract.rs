@@ -1,4 +1,5 @@
 use crate::db::model::{ContractAddressDbObj, ContractDbObj, DeployStatus};
+use chrono::NaiveDateTime;
 use sqlx::types::Uuid;
 use sqlx::{Executor, PgPool, Postgres};
 
@@ -8,8 +9,8 @@ pub async fn insert_contract_obj(
 ) -> Result<ContractDbObj, sqlx::Error> {
     let res = sqlx::query_as::<_, ContractDbObj>(
         r"INSERT INTO contract
-        (contract_id, user_id, created, network, data, tx, deploy_status, deploy_requested, deploy_sent, deployed)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING *;
+        (contract_id, user_id, created, network, data, tx, deploy_status, deploy_requested, deploy_sent, deployed, compiler_version, compiler_settings)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING *;
         ",
     )
     .bind(contract_data.contract_id)
@@ -22,6 +23,8 @@ pub async fn insert_contract_obj(
     .bind(contract_data.deploy_requested)
     .bind(contract_data.deploy_sent)
     .bind(contract_data.deployed)
+    .bind(&contract_data.compiler_version)
+    .bind(&contract_data.compiler_settings)
     .fetch_one(conn)
     .await?;
     Ok(res)
@@ -29,7 +32,7 @@ pub async fn insert_contract_obj(
 
 pub async fn get_contract_by_id<'c, E>(
     conn: E,
-    contract_id: String,
+    contract_id: Uuid,
     user_id: Uuid,
 ) -> Result<Option<ContractDbObj>, sqlx::Error>
 where
@@ -45,6 +48,24 @@ where
     Ok(res)
 }
 
+/// Successfully deployed contract of any user, used by background verification
+pub async fn get_deployed_contract<'c, E>(
+    conn: E,
+    contract_id: Uuid,
+) -> Result<Option<ContractDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, ContractDbObj>(
+        r"SELECT * FROM contract WHERE contract_id = $1 AND deploy_status = $2;",
+    )
+    .bind(contract_id)
+    .bind(DeployStatus::Succeeded)
+    .fetch_optional(conn)
+    .await?;
+    Ok(res)
+}
+
 pub async fn get_contract_address_list<'c, E>(
     conn: E,
     user_id: Uuid,
@@ -85,29 +106,80 @@ pub async fn get_all_contracts_by_user(
     Ok(res)
 }
 
-pub async fn get_all_contracts_by_deploy_status_and_network(
-    conn: &PgPool,
-    deploy_status: DeployStatus,
-    network: String,
-) -> Result<Vec<ContractDbObj>, sqlx::Error> {
+/// Networks with deployments waiting for a worker
+pub async fn get_contract_deploy_networks<'c, E>(conn: E) -> Result<Vec<String>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_scalar::<_, String>(
+        r"SELECT DISTINCT network FROM contract WHERE deploy_status = ANY($1) ORDER BY network;",
+    )
+    .bind(deploy_worker_statuses())
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+fn deploy_worker_statuses() -> Vec<String> {
+    [
+        DeployStatus::Requested,
+        DeployStatus::TxSent,
+        DeployStatus::Mined,
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+/// Claims next contract to deploy on network until `claimed_until`.
+/// Contracts with transaction already sent go first, rows locked by other workers are skipped.
+pub async fn contract_claim_deploy<'c, E>(
+    conn: E,
+    network: &str,
+    now: NaiveDateTime,
+    claimed_until: NaiveDateTime,
+) -> Result<Option<ContractDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
     let res = sqlx::query_as::<_, ContractDbObj>(
-        r"
-    SELECT * FROM contract
-    WHERE deploy_status=$1
-        AND network=$2
-    ORDER BY deploy_requested ASC;
-        ",
+        r"UPDATE contract SET deploy_claimed_until = $4
+    WHERE contract_id = (
+        SELECT contract_id FROM contract
+        WHERE network = $1
+            AND deploy_status = ANY($2)
+            AND (deploy_claimed_until IS NULL OR deploy_claimed_until < $3)
+        ORDER BY deploy_status = 'requested', deploy_requested ASC
+        LIMIT 1
+        FOR UPDATE SKIP LOCKED
+    ) RETURNING *;",
     )
-    .bind(deploy_status)
     .bind(network)
-    .fetch_all(conn)
+    .bind(deploy_worker_statuses())
+    .bind(now)
+    .bind(claimed_until)
+    .fetch_optional(conn)
     .await?;
     Ok(res)
 }
 
+pub async fn contract_release_deploy_claim<'c, E>(
+    conn: E,
+    contract_id: Uuid,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query(r"UPDATE contract SET deploy_claimed_until = NULL WHERE contract_id = $1")
+        .bind(contract_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
 pub async fn delete_contract_by_id<'c, E>(
     conn: E,
-    contract_id: String,
+    contract_id: Uuid,
     user_id: Uuid,
 ) -> Result<(), sqlx::Error>
 where
@@ -138,7 +210,11 @@ where
     deploy_requested = $7,
     deploy_sent = $8,
     deployed = $9,
-    address = $10
+    address = $10,
+    deploy_error = $11,
+    deploy_block = $12,
+    compiler_version = $13,
+    compiler_settings = $14
     WHERE contract_id = $3 AND user_id = $4 RETURNING *;",
     )
     .bind(contract.data)
@@ -151,7 +227,187 @@ where
     .bind(contract.deploy_sent)
     .bind(contract.deployed)
     .bind(contract.address)
+    .bind(contract.deploy_error)
+    .bind(contract.deploy_block)
+    .bind(contract.compiler_version)
+    .bind(contract.compiler_settings)
     .fetch_one(conn)
     .await?;
     Ok(obj)
 }
+
+/// Moves failed deployment back to requested, returns None if contract is not in failed state
+pub async fn contract_retry_deploy<'c, E>(
+    conn: E,
+    contract_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<ContractDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, ContractDbObj>(
+        r"UPDATE contract SET
+    deploy_status = $1,
+    deploy_requested = $2,
+    deploy_sent = NULL,
+    deploy_error = NULL,
+    deploy_block = NULL,
+    tx = NULL
+    WHERE contract_id = $3 AND user_id = $4 AND deploy_status = $5 RETURNING *;",
+    )
+    .bind(DeployStatus::Requested)
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(contract_id)
+    .bind(user_id)
+    .bind(DeployStatus::Failed)
+    .fetch_optional(conn)
+    .await?;
+    Ok(res)
+}
+
+#[sqlx::test]
+async fn contract_retry_deploy_test(pool: PgPool) -> sqlx::Result<()> {
+    use crate::db::ops::insert_test_user;
+    use crate::db::utils::get_current_utc_time;
+
+    let now = get_current_utc_time();
+    let user = insert_test_user(&pool, "deploy@mail.domain", 0).await?;
+
+    let contract = insert_contract_obj(
+        &pool,
+        ContractDbObj {
+            contract_id: Uuid::new_v4(),
+            user_id: user.uid,
+            created: now,
+            address: None,
+            network: "holesky".to_string(),
+            data: "{}".to_string(),
+            tx: None,
+            deploy_status: DeployStatus::Requested,
+            deploy_requested: Some(now),
+            deploy_sent: None,
+            deployed: None,
+            deploy_error: None,
+            deploy_block: None,
+            compiler_version: None,
+            compiler_settings: None,
+            verifications: Vec::new(),
+        },
+    )
+    .await?;
+
+    // only failed deployments can be retried
+    assert!(contract_retry_deploy(&pool, contract.contract_id, user.uid)
+        .await?
+        .is_none());
+
+    let failed = update_contract_data(
+        &pool,
+        ContractDbObj {
+            tx: Some("0x01".to_string()),
+            deploy_status: DeployStatus::Failed,
+            deploy_sent: Some(now),
+            deploy_error: Some("execution reverted".to_string()),
+            deploy_block: Some(12),
+            compiler_version: None,
+            compiler_settings: None,
+            verifications: Vec::new(),
+            ..contract.clone()
+        },
+    )
+    .await?;
+    assert_eq!(failed.deploy_error.as_deref(), Some("execution reverted"));
+
+    assert!(
+        contract_retry_deploy(&pool, contract.contract_id, Uuid::new_v4())
+            .await?
+            .is_none()
+    );
+    let retried = contract_retry_deploy(&pool, contract.contract_id, user.uid)
+        .await?
+        .unwrap();
+    assert_eq!(retried.deploy_status, DeployStatus::Requested);
+    assert_eq!(retried.tx, None);
+    assert_eq!(retried.deploy_error, None);
+    assert_eq!(retried.deploy_block, None);
+    assert_eq!(retried.deploy_sent, None);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn contract_claim_deploy_test(pool: PgPool) -> sqlx::Result<()> {
+    use crate::db::ops::insert_test_user;
+    use crate::db::utils::get_current_utc_time;
+
+    let now = get_current_utc_time();
+    let user = insert_test_user(&pool, "worker@mail.domain", 0).await?;
+    let mut contracts = Vec::new();
+    for (idx, status) in [
+        DeployStatus::Requested,
+        DeployStatus::TxSent,
+        DeployStatus::Succeeded,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        contracts.push(
+            insert_contract_obj(
+                &pool,
+                ContractDbObj {
+                    contract_id: Uuid::new_v4(),
+                    user_id: user.uid,
+                    created: now,
+                    address: None,
+                    network: "holesky".to_string(),
+                    data: "{}".to_string(),
+                    tx: None,
+                    deploy_status: status,
+                    deploy_requested: Some(now + chrono::Duration::seconds(idx as i64)),
+                    deploy_sent: None,
+                    deployed: None,
+                    deploy_error: None,
+                    deploy_block: None,
+                    compiler_version: None,
+                    compiler_settings: None,
+                    verifications: Vec::new(),
+                },
+            )
+            .await?,
+        );
+    }
+    assert_eq!(get_contract_deploy_networks(&pool).await?, vec!["holesky"]);
+    assert!(contract_claim_deploy(&pool, "sepolia", now, now)
+        .await?
+        .is_none());
+
+    let until = now + chrono::Duration::hours(1);
+    // sent transaction is followed before new deployments are started
+    let first = contract_claim_deploy(&pool, "holesky", now, until)
+        .await?
+        .unwrap();
+    assert_eq!(first.contract_id, contracts[1].contract_id);
+    let second = contract_claim_deploy(&pool, "holesky", now, until)
+        .await?
+        .unwrap();
+    assert_eq!(second.contract_id, contracts[0].contract_id);
+    assert!(contract_claim_deploy(&pool, "holesky", now, until)
+        .await?
+        .is_none());
+
+    // released or expired claims can be taken again
+    contract_release_deploy_claim(&pool, first.contract_id).await?;
+    let again = contract_claim_deploy(&pool, "holesky", now, until)
+        .await?
+        .unwrap();
+    assert_eq!(again.contract_id, first.contract_id);
+    sqlx::query("UPDATE contract SET deploy_claimed_until = $1 WHERE contract_id = $2")
+        .bind(now - chrono::Duration::hours(1))
+        .bind(second.contract_id)
+        .execute(&pool)
+        .await?;
+    let expired = contract_claim_deploy(&pool, "holesky", now, until)
+        .await?
+        .unwrap();
+    assert_eq!(expired.contract_id, second.contract_id);
+    Ok(())
+}
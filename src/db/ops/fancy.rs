@@ -1,12 +1,13 @@
 use crate::db::model::{
-    ContractFactoryDbObject, FancyDbObj, FancyProviderDbObj, JobDbObj, JobMinerDbReadObj,
-    MinerDbObj, PublicKeyBaseDbObject,
+    ContractFactoryDbObject, FancyCategoryScoreDbObj, FancyDbObj, FancyProviderDbObj,
+    FancyScoreDbObj, FancySecretRevealDbObj, JobDbObj, JobMinerDbReadObj, JobWorkDbObj, MinerDbObj,
+    PublicKeyBaseDbObject, WorkTargetDbObj,
 };
 use crate::db::utils::get_min_time;
 use crate::types::DbAddress;
 use chrono::{NaiveDateTime, Utc};
 use sqlx::types::Uuid;
-use sqlx::{Executor, PgPool, Postgres, Transaction};
+use sqlx::{Executor, PgPool, Postgres, QueryBuilder, Transaction};
 
 pub async fn insert_fancy_obj<'c, E>(
     conn: E,
@@ -17,8 +18,8 @@ where
 {
     let res = sqlx::query_as::<_, FancyDbObj>(
         r"INSERT INTO fancy
-(address, salt, factory, created, score, job_id, owner_id, price, category, public_key_base)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING *;
+(address, salt, factory, created, score, job_id, owner_id, price, category, public_key_base, init_code_hash)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING *;
 ",
     )
     .bind(fancy_data.address)
@@ -31,6 +32,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING *;
     .bind(fancy_data.price)
     .bind(&fancy_data.category)
     .bind(&fancy_data.public_key_base)
+    .bind(&fancy_data.init_code_hash)
     .fetch_one(conn)
     .await?;
     Ok(res)
@@ -51,6 +53,8 @@ pub async fn fancy_list_all(
 pub enum FancyOrderBy {
     Score,
     Created,
+    /// Cheapest first
+    Price,
 }
 
 pub enum ReservedStatus {
@@ -72,16 +76,17 @@ pub async fn get_public_key_list<'c, E>(
 where
     E: Executor<'c, Database = Postgres>,
 {
-    let where_clause = match user_id {
-        Some(uid) => format!("WHERE user_id = '{}' OR user_id is NULL", uid),
-        None => "".to_string(),
-    };
-
-    let res = sqlx::query_as::<_, PublicKeyBaseDbObject>(&format!(
-        r"SELECT * FROM public_key_base {where_clause};"
-    ))
-    .fetch_all(conn)
-    .await?;
+    let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM public_key_base");
+    if let Some(uid) = user_id {
+        query
+            .push(" WHERE user_id = ")
+            .push_bind(uid)
+            .push(" OR user_id is NULL");
+    }
+    let res = query
+        .build_query_as::<PublicKeyBaseDbObject>()
+        .fetch_all(conn)
+        .await?;
     Ok(res)
 }
 
@@ -112,6 +117,44 @@ pub async fn get_or_insert_public_key(
     }
 }
 
+pub async fn get_public_key_base<'c, E>(
+    conn: E,
+    public_key_base: &str,
+) -> Result<Option<PublicKeyBaseDbObject>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, PublicKeyBaseDbObject>(
+        r"SELECT * FROM public_key_base WHERE hex = $1;",
+    )
+    .bind(public_key_base)
+    .fetch_optional(conn)
+    .await?;
+    Ok(res)
+}
+
+/// Registers customer owned public key base, returns None if the key is already known
+pub async fn insert_user_public_key<'c, E>(
+    conn: E,
+    public_key_base: &str,
+    user_id: Uuid,
+) -> Result<Option<PublicKeyBaseDbObject>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, PublicKeyBaseDbObject>(
+        r"INSERT INTO public_key_base (uid, hex, added, user_id) VALUES ($1, $2, $3, $4)
+ON CONFLICT (hex) DO NOTHING RETURNING *;",
+    )
+    .bind(Uuid::new_v4())
+    .bind(public_key_base)
+    .bind(Utc::now().naive_utc())
+    .bind(user_id)
+    .fetch_optional(conn)
+    .await?;
+    Ok(res)
+}
+
 pub async fn get_or_insert_factory(
     conn: &mut Transaction<'_, Postgres>,
     factory_address: DbAddress,
@@ -139,85 +182,278 @@ pub async fn get_or_insert_factory(
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-pub async fn fancy_list<'c, E>(
+pub async fn get_factory_by_address<'c, E>(
     conn: E,
-    category: Option<String>,
-    order_by: FancyOrderBy,
-    reserved: ReservedStatus,
-    since: Option<NaiveDateTime>,
-    provider_id: Option<String>,
-    public_key_base: PublicKeyFilter,
-    limit: i64,
-) -> Result<Vec<FancyProviderDbObj>, sqlx::Error>
+    factory_address: DbAddress,
+) -> Result<Option<ContractFactoryDbObject>, sqlx::Error>
 where
     E: Executor<'c, Database = Postgres>,
 {
-    let order_by = match order_by {
-        FancyOrderBy::Score => "score",
-        FancyOrderBy::Created => "created",
-    };
+    let res = sqlx::query_as::<_, ContractFactoryDbObject>(
+        r"SELECT * FROM contract_factory WHERE address = $1;",
+    )
+    .bind(factory_address)
+    .fetch_optional(conn)
+    .await?;
+    Ok(res)
+}
 
-    let owner_condition = match reserved {
-        ReservedStatus::All => "".to_string(),
-        ReservedStatus::Reserved => "f.owner_id is NOT NULL".to_string(),
-        ReservedStatus::NotReserved => "f.owner_id is NULL".to_string(),
-        ReservedStatus::User(user) => format!("f.owner_id = '{}'", user).to_string(),
-    };
+/// Registers factory or updates how addresses of existing one are computed
+pub async fn upsert_factory<'c, E>(
+    conn: E,
+    factory: &ContractFactoryDbObject,
+) -> Result<ContractFactoryDbObject, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, ContractFactoryDbObject>(
+        r"INSERT INTO contract_factory
+(uid, address, added, user_id, kind, proxy_init_code_hash, salt_guard, guard_sender, create2_deployer)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+ON CONFLICT (address) DO UPDATE SET
+    kind = EXCLUDED.kind,
+    proxy_init_code_hash = EXCLUDED.proxy_init_code_hash,
+    salt_guard = EXCLUDED.salt_guard,
+    guard_sender = EXCLUDED.guard_sender,
+    create2_deployer = EXCLUDED.create2_deployer
+RETURNING *;",
+    )
+    .bind(factory.uid)
+    .bind(factory.address)
+    .bind(factory.added)
+    .bind(factory.user_id)
+    .bind(&factory.kind)
+    .bind(&factory.proxy_init_code_hash)
+    .bind(&factory.salt_guard)
+    .bind(factory.guard_sender)
+    .bind(factory.create2_deployer)
+    .fetch_one(conn)
+    .await?;
+    Ok(res)
+}
 
-    let public_key_base_condition = match public_key_base {
-        PublicKeyFilter::All => "".to_string(),
-        PublicKeyFilter::Selected(pk) => format!("f.public_key_base = '{}'", pk),
-        PublicKeyFilter::OnlyNull => "f.public_key_base is NULL".to_string(),
-    };
+/// Position after the last row of the previous page, has to match order of the listing
+#[derive(Debug, Clone, PartialEq)]
+pub enum FancyCursor {
+    Score(f64, DbAddress),
+    Created(NaiveDateTime, DbAddress),
+    Price(i64, DbAddress),
+}
 
-    let created_condition = match since {
-        Some(since) => format!("f.created > '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-        None => "".to_string(),
-    };
+pub struct FancyListFilter {
+    pub category: Option<String>,
+    pub order_by: FancyOrderBy,
+    pub reserved: ReservedStatus,
+    pub since: Option<NaiveDateTime>,
+    pub provider_id: Option<String>,
+    pub public_key_base: PublicKeyFilter,
+    pub score_above: Option<f64>,
+    pub score_min: Option<f64>,
+    pub score_max: Option<f64>,
+    pub price_min: Option<i64>,
+    pub price_max: Option<i64>,
+    pub factory: Option<DbAddress>,
+    /// Lower case hex without 0x, matched right after the 0x
+    pub address_prefix: Option<String>,
+    /// Lower case hex, matched at the end of the address
+    pub address_suffix: Option<String>,
+    /// Lower case hex, matched anywhere in the address
+    pub address_contains: Option<String>,
+    /// Skips addresses assigned to contracts of their owner
+    pub unassigned_only: bool,
+    pub after: Option<FancyCursor>,
+    /// Zero means no limit
+    pub limit: i64,
+}
+
+impl Default for FancyListFilter {
+    fn default() -> Self {
+        FancyListFilter {
+            category: None,
+            order_by: FancyOrderBy::Score,
+            reserved: ReservedStatus::All,
+            since: None,
+            provider_id: None,
+            public_key_base: PublicKeyFilter::All,
+            score_above: None,
+            score_min: None,
+            score_max: None,
+            price_min: None,
+            price_max: None,
+            factory: None,
+            address_prefix: None,
+            address_suffix: None,
+            address_contains: None,
+            unassigned_only: false,
+            after: None,
+            limit: 0,
+        }
+    }
+}
+
+/// Starts WHERE clause with the first condition, joins the following ones with AND
+fn push_condition<'a>(query: &mut QueryBuilder<'a, Postgres>, first: &mut bool, condition: &str) {
+    query.push(if *first { " WHERE " } else { " AND " });
+    query.push(condition);
+    *first = false;
+}
 
-    let category_condition = match category {
-        Some(cat) => format!("f.category = '{}'", cat),
-        None => "".to_string(),
+/// Conditions shared by the listing and its total count, cursor and limit are not included
+fn push_fancy_conditions(
+    query: &mut QueryBuilder<'_, Postgres>,
+    first: &mut bool,
+    filter: &FancyListFilter,
+) {
+    match &filter.reserved {
+        ReservedStatus::All => {}
+        ReservedStatus::Reserved => push_condition(query, first, "f.owner_id is NOT NULL"),
+        ReservedStatus::NotReserved => push_condition(query, first, "f.owner_id is NULL"),
+        ReservedStatus::User(user) => {
+            push_condition(query, first, "f.owner_id = ");
+            query.push_bind(*user);
+        }
     };
 
-    let provider_condition = match provider_id {
-        Some(pid) => format!("mi.prov_node_id = '{}'", pid),
-        None => "".to_string(),
+    match &filter.public_key_base {
+        PublicKeyFilter::All => {}
+        PublicKeyFilter::Selected(pk) => {
+            push_condition(query, first, "f.public_key_base = ");
+            query.push_bind(pk.clone());
+        }
+        PublicKeyFilter::OnlyNull => push_condition(query, first, "f.public_key_base is NULL"),
     };
 
-    let where_clause = [
-        owner_condition,
-        public_key_base_condition,
-        category_condition,
-        created_condition,
-        provider_condition,
-    ]
-    .into_iter()
-    .filter(|x| !x.is_empty())
-    .collect::<Vec<_>>()
-    .join(" AND ");
-
-    let limit_clause = if limit > 0 {
-        format!("LIMIT {}", limit)
-    } else {
-        "".to_string()
+    if let Some(category) = &filter.category {
+        push_condition(query, first, "f.category = ");
+        query.push_bind(category.clone());
+    }
+    if let Some(since) = filter.since {
+        push_condition(query, first, "f.created > ");
+        query.push_bind(since);
+    }
+    if let Some(provider_id) = &filter.provider_id {
+        push_condition(query, first, "mi.prov_node_id = ");
+        query.push_bind(provider_id.clone());
+    }
+    if let Some(score) = filter.score_above {
+        push_condition(query, first, "f.score > ");
+        query.push_bind(score);
+    }
+    if let Some(score) = filter.score_min {
+        push_condition(query, first, "f.score >= ");
+        query.push_bind(score);
+    }
+    if let Some(score) = filter.score_max {
+        push_condition(query, first, "f.score <= ");
+        query.push_bind(score);
+    }
+    if let Some(price) = filter.price_min {
+        push_condition(query, first, "f.price >= ");
+        query.push_bind(price);
+    }
+    if let Some(price) = filter.price_max {
+        push_condition(query, first, "f.price <= ");
+        query.push_bind(price);
+    }
+    if let Some(factory) = filter.factory {
+        push_condition(query, first, "f.factory = ");
+        query.push_bind(factory);
+    }
+    // patterns are validated hex, so they never contain LIKE wildcards
+    if let Some(prefix) = &filter.address_prefix {
+        push_condition(query, first, "f.address LIKE ");
+        query.push_bind(format!("0x{}%", prefix));
+    }
+    if let Some(suffix) = &filter.address_suffix {
+        // matches fancy_address_reverse_idx
+        push_condition(query, first, "reverse(f.address) LIKE ");
+        query.push_bind(format!("{}%", suffix.chars().rev().collect::<String>()));
+    }
+    if let Some(contains) = &filter.address_contains {
+        // matches fancy_address_trgm_idx
+        push_condition(query, first, "f.address LIKE ");
+        query.push_bind(format!("%{}%", contains));
+    }
+    if filter.unassigned_only {
+        push_condition(
+            query,
+            first,
+            "NOT EXISTS (SELECT 1 FROM contract c WHERE c.address = f.address AND c.user_id = f.owner_id)",
+        );
+    }
+}
+
+/// Ordered by score, creation date or price, address breaks ties so pages never overlap
+pub async fn fancy_list<'c, E>(
+    conn: E,
+    filter: &FancyListFilter,
+) -> Result<Vec<FancyProviderDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let mut query = QueryBuilder::<Postgres>::new(
+        r"SELECT f.*, mi.prov_name, mi.prov_node_id, mi.prov_reward_addr
+            FROM fancy as f LEFT JOIN job_info as ji ON f.job_id=ji.uid LEFT JOIN miner_info as mi ON mi.uid=ji.miner",
+    );
+    let mut first = true;
+    push_fancy_conditions(&mut query, &mut first, filter);
+
+    match &filter.after {
+        None => {}
+        Some(FancyCursor::Score(score, address)) => {
+            push_condition(&mut query, &mut first, "(f.score, f.address) < (");
+            query
+                .push_bind(*score)
+                .push(", ")
+                .push_bind(*address)
+                .push(")");
+        }
+        Some(FancyCursor::Created(created, address)) => {
+            push_condition(&mut query, &mut first, "(f.created, f.address) < (");
+            query
+                .push_bind(*created)
+                .push(", ")
+                .push_bind(*address)
+                .push(")");
+        }
+        Some(FancyCursor::Price(price, address)) => {
+            push_condition(&mut query, &mut first, "(f.price, f.address) > (");
+            query
+                .push_bind(*price)
+                .push(", ")
+                .push_bind(*address)
+                .push(")");
+        }
     };
 
-    let order_by_clause = format!("{} DESC", order_by);
+    query.push(match filter.order_by {
+        FancyOrderBy::Score => " ORDER BY f.score DESC, f.address DESC",
+        FancyOrderBy::Created => " ORDER BY f.created DESC, f.address DESC",
+        FancyOrderBy::Price => " ORDER BY f.price ASC, f.address ASC",
+    });
+    if filter.limit > 0 {
+        query.push(" LIMIT ").push_bind(filter.limit);
+    }
 
-    let res = sqlx::query_as::<_, FancyProviderDbObj>(
-        format!(
-            r"SELECT f.*, mi.prov_name, mi.prov_node_id, mi.prov_reward_addr
-            FROM fancy as f LEFT JOIN job_info as ji ON f.job_id=ji.uid LEFT JOIN miner_info as mi ON mi.uid=ji.miner
-            WHERE {where_clause}
-            ORDER BY {order_by_clause}
-            {limit_clause}"
-        )
-        .as_str(),
-    )
-    .fetch_all(conn)
-    .await?;
+    let res = query
+        .build_query_as::<FancyProviderDbObj>()
+        .fetch_all(conn)
+        .await?;
+    Ok(res)
+}
+
+/// Number of addresses matching the filter, regardless of cursor and limit
+pub async fn fancy_count<'c, E>(conn: E, filter: &FancyListFilter) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let mut query = QueryBuilder::<Postgres>::new(
+        r"SELECT COUNT(*)
+            FROM fancy as f LEFT JOIN job_info as ji ON f.job_id=ji.uid LEFT JOIN miner_info as mi ON mi.uid=ji.miner",
+    );
+    let mut first = true;
+    push_fancy_conditions(&mut query, &mut first, filter);
+    let res = query.build_query_scalar::<i64>().fetch_one(conn).await?;
     Ok(res)
 }
 
@@ -231,59 +467,47 @@ pub enum FancyJobStatus {
     Finished,
 }
 
+pub struct FancyJobFilter {
+    pub order_by: FancyJobOrderBy,
+    pub since: Option<NaiveDateTime>,
+    pub status: FancyJobStatus,
+    pub requestor_id: Option<DbAddress>,
+    /// Update time and uid of the last job of the previous page
+    pub after: Option<(NaiveDateTime, Uuid)>,
+    /// Zero means no limit
+    pub limit: i64,
+}
+
+fn push_job_conditions(
+    query: &mut QueryBuilder<'_, Postgres>,
+    first: &mut bool,
+    filter: &FancyJobFilter,
+) {
+    if let Some(requestor_id) = filter.requestor_id {
+        push_condition(query, first, "requestor_id = ");
+        query.push_bind(requestor_id);
+    }
+    if let Some(since) = filter.since {
+        push_condition(query, first, "updated_at > ");
+        query.push_bind(since);
+    }
+    match filter.status {
+        FancyJobStatus::All => {}
+        FancyJobStatus::Active => push_condition(query, first, "finished_at is NULL"),
+        FancyJobStatus::Finished => push_condition(query, first, "finished_at is NOT NULL"),
+    };
+}
+
 pub async fn fancy_job_list<'c, E>(
     conn: E,
-    order_by: FancyJobOrderBy,
-    since: Option<NaiveDateTime>,
-    status: FancyJobStatus,
-    requestor_id: Option<DbAddress>,
-    limit: i64,
+    filter: &FancyJobFilter,
 ) -> Result<Vec<JobMinerDbReadObj>, sqlx::Error>
 where
     E: Executor<'c, Database = Postgres>,
 {
-    let order_by = match order_by {
-        FancyJobOrderBy::Date => "updated_at",
-    };
-
-    let requestor_id_condition = match requestor_id {
-        Some(id) => format!("requestor_id = '{}'", id),
-        None => "".to_string(),
-    };
-
-    let created_condition = match since {
-        Some(since) => format!("updated_at > '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-        None => "".to_string(),
-    };
-
-    let status_condition = match status {
-        FancyJobStatus::All => "".to_string(),
-        FancyJobStatus::Active => "finished_at is NULL".to_string(),
-        FancyJobStatus::Finished => "finished_at is NOT NULL".to_string(),
-    };
-
-    let where_clause = [
-        "1=1".to_string(),
-        requestor_id_condition,
-        created_condition,
-        status_condition,
-    ]
-    .into_iter()
-    .filter(|x| !x.is_empty())
-    .collect::<Vec<_>>()
-    .join(" AND ");
-
-    let limit_clause = if limit > 0 {
-        format!("LIMIT {}", limit)
-    } else {
-        "".to_string()
-    };
-
-    let order_by_clause = format!("{} DESC", order_by);
-
-    let res = sqlx::query_as::<_, JobMinerDbReadObj>(
-        format!(
-            r"SELECT
+    let mut query = QueryBuilder::<Postgres>::new(
+        r"SELECT
+                ji.uid,
                 cruncher_ver,
                 started_at,
                 updated_at,
@@ -299,15 +523,43 @@ where
                 prov_node_id,
                 prov_reward_addr,
                 prov_extra_info
-            FROM job_info as ji JOIN miner_info as mi on ji.miner=mi.uid
-            WHERE {where_clause}
-            ORDER BY {order_by_clause}
-            {limit_clause}"
-        )
-        .as_str(),
-    )
-    .fetch_all(conn)
-    .await?;
+            FROM job_info as ji JOIN miner_info as mi on ji.miner=mi.uid",
+    );
+    let mut first = true;
+    push_job_conditions(&mut query, &mut first, filter);
+    if let Some((updated_at, uid)) = filter.after {
+        push_condition(&mut query, &mut first, "(updated_at, ji.uid) < (");
+        query
+            .push_bind(updated_at)
+            .push(", ")
+            .push_bind(uid)
+            .push(")");
+    }
+
+    query.push(match filter.order_by {
+        FancyJobOrderBy::Date => " ORDER BY updated_at DESC, ji.uid DESC",
+    });
+    if filter.limit > 0 {
+        query.push(" LIMIT ").push_bind(filter.limit);
+    }
+
+    let res = query
+        .build_query_as::<JobMinerDbReadObj>()
+        .fetch_all(conn)
+        .await?;
+    Ok(res)
+}
+
+pub async fn fancy_job_count<'c, E>(conn: E, filter: &FancyJobFilter) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let mut query = QueryBuilder::<Postgres>::new(
+        r"SELECT COUNT(*) FROM job_info as ji JOIN miner_info as mi on ji.miner=mi.uid",
+    );
+    let mut first = true;
+    push_job_conditions(&mut query, &mut first, filter);
+    let res = query.build_query_scalar::<i64>().fetch_one(conn).await?;
     Ok(res)
 }
 
@@ -325,20 +577,45 @@ where
     Ok(res)
 }
 
+/// Sets owner of unowned address, returns false when somebody else got it first
 pub async fn fancy_update_owner<'c, E>(
     conn: E,
     address: DbAddress,
     owner_id: Uuid,
-) -> Result<(), sqlx::Error>
+) -> Result<bool, sqlx::Error>
 where
     E: Executor<'c, Database = Postgres>,
 {
-    let _res = sqlx::query(r"UPDATE fancy SET owner_id = $1 WHERE address = $2;")
-        .bind(owner_id)
-        .bind(address)
-        .execute(conn)
-        .await?;
-    Ok(())
+    let res =
+        sqlx::query(r"UPDATE fancy SET owner_id = $1 WHERE address = $2 AND owner_id IS NULL;")
+            .bind(owner_id)
+            .bind(address)
+            .execute(conn)
+            .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+pub async fn insert_fancy_secret_reveal<'c, E>(
+    conn: E,
+    address: DbAddress,
+    user_id: Uuid,
+    remote_addr: Option<String>,
+) -> Result<FancySecretRevealDbObj, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, FancySecretRevealDbObj>(
+        r"INSERT INTO fancy_secret_reveal (uid, address, user_id, remote_addr, revealed)
+VALUES ($1, $2, $3, $4, $5) RETURNING *;",
+    )
+    .bind(Uuid::new_v4())
+    .bind(address)
+    .bind(user_id)
+    .bind(remote_addr)
+    .bind(Utc::now().naive_utc())
+    .fetch_one(conn)
+    .await?;
+    Ok(res)
 }
 
 pub async fn fancy_update_score<'c, E>(
@@ -362,6 +639,146 @@ where
     Ok(())
 }
 
+/// Replaces stored category scores of the address
+pub async fn fancy_score_upsert<'c, E>(
+    conn: E,
+    address: DbAddress,
+    scores: &[FancyScoreDbObj],
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let categories = scores
+        .iter()
+        .map(|s| s.category.clone())
+        .collect::<Vec<_>>();
+    let values = scores.iter().map(|s| s.score).collect::<Vec<_>>();
+    let difficulties = scores.iter().map(|s| s.difficulty).collect::<Vec<_>>();
+    let ranks = scores.iter().map(|s| s.category_rank).collect::<Vec<_>>();
+    let _res = sqlx::query(
+        r"WITH removed AS (
+            DELETE FROM fancy_score WHERE address = $1 AND NOT (category = ANY($2))
+        )
+        INSERT INTO fancy_score (address, category, score, difficulty, category_rank)
+        SELECT $1, * FROM UNNEST($2::text[], $3::float8[], $4::float8[], $5::int[])
+        ON CONFLICT (address, category) DO UPDATE SET
+            score = EXCLUDED.score,
+            difficulty = EXCLUDED.difficulty,
+            category_rank = EXCLUDED.category_rank;",
+    )
+    .bind(address)
+    .bind(&categories)
+    .bind(&values)
+    .bind(&difficulties)
+    .bind(&ranks)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+pub async fn fancy_get_scores<'c, E>(
+    conn: E,
+    address: DbAddress,
+) -> Result<Vec<FancyScoreDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, FancyScoreDbObj>(
+        r"SELECT * FROM fancy_score WHERE address = $1 ORDER BY category_rank;",
+    )
+    .bind(address)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+pub struct FancyCategoryFilter {
+    pub category: String,
+    /// Only addresses where the category ended at this place, 1 is the winning category
+    pub rank: Option<i32>,
+    pub reserved: ReservedStatus,
+    /// Difficulty and address of the last row of the previous page
+    pub after: Option<(f64, DbAddress)>,
+    /// Zero means no limit
+    pub limit: i64,
+}
+
+fn push_category_conditions(
+    query: &mut QueryBuilder<'_, Postgres>,
+    first: &mut bool,
+    filter: &FancyCategoryFilter,
+) {
+    // addresses of customer keys are not listed publicly
+    push_condition(query, first, "f.public_key_base is NULL");
+    push_condition(query, first, "s.category = ");
+    query.push_bind(filter.category.clone());
+    if let Some(rank) = filter.rank {
+        push_condition(query, first, "s.category_rank = ");
+        query.push_bind(rank);
+    }
+    match &filter.reserved {
+        ReservedStatus::All => {}
+        ReservedStatus::Reserved => push_condition(query, first, "f.owner_id is NOT NULL"),
+        ReservedStatus::NotReserved => push_condition(query, first, "f.owner_id is NULL"),
+        ReservedStatus::User(user) => {
+            push_condition(query, first, "f.owner_id = ");
+            query.push_bind(*user);
+        }
+    };
+}
+
+/// Best addresses in the category regardless of their winning category
+pub async fn fancy_list_by_category<'c, E>(
+    conn: E,
+    filter: &FancyCategoryFilter,
+) -> Result<Vec<FancyCategoryScoreDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let mut query = QueryBuilder::<Postgres>::new(
+        r"SELECT f.*, mi.prov_name, mi.prov_node_id, mi.prov_reward_addr,
+                s.score AS category_score, s.difficulty AS category_difficulty, s.category_rank
+            FROM fancy_score as s JOIN fancy as f ON f.address=s.address
+            LEFT JOIN job_info as ji ON f.job_id=ji.uid LEFT JOIN miner_info as mi ON mi.uid=ji.miner",
+    );
+    let mut first = true;
+    push_category_conditions(&mut query, &mut first, filter);
+    if let Some((difficulty, address)) = filter.after {
+        push_condition(&mut query, &mut first, "(s.difficulty, s.address) < (");
+        query
+            .push_bind(difficulty)
+            .push(", ")
+            .push_bind(address)
+            .push(")");
+    }
+    query.push(" ORDER BY s.difficulty DESC, s.address DESC");
+    if filter.limit > 0 {
+        query.push(" LIMIT ").push_bind(filter.limit);
+    }
+
+    let res = query
+        .build_query_as::<FancyCategoryScoreDbObj>()
+        .fetch_all(conn)
+        .await?;
+    Ok(res)
+}
+
+pub async fn fancy_count_by_category<'c, E>(
+    conn: E,
+    filter: &FancyCategoryFilter,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let mut query = QueryBuilder::<Postgres>::new(
+        r"SELECT COUNT(*) FROM fancy_score as s JOIN fancy as f ON f.address=s.address",
+    );
+    let mut first = true;
+    push_category_conditions(&mut query, &mut first, filter);
+    let res = query.build_query_scalar::<i64>().fetch_one(conn).await?;
+    Ok(res)
+}
+
 pub async fn fancy_get_miner_info<'c, E>(
     conn: E,
     miner_info_uid: &str,
@@ -482,3 +899,386 @@ where
         .await?;
     Ok(())
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkTargetKind {
+    Any,
+    Factory,
+    PublicKeyBase,
+}
+
+/// Picks factory or public key base with the smallest stock of unsold addresses
+pub async fn fancy_pick_work_target<'c, E>(
+    conn: E,
+    kind: WorkTargetKind,
+) -> Result<Option<WorkTargetDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, WorkTargetDbObj>(
+        r"SELECT * FROM (
+    SELECT cf.address AS factory, NULL AS public_key_base,
+        (SELECT COUNT(*) FROM fancy f WHERE f.factory = cf.address AND f.owner_id IS NULL) AS unsold
+    FROM contract_factory cf
+    WHERE $1 AND NOT cf.create2_deployer
+        AND EXISTS (SELECT 1 FROM network_factory nf WHERE nf.factory = cf.address)
+    UNION ALL
+    SELECT NULL AS factory, pkb.hex AS public_key_base,
+        (SELECT COUNT(*) FROM fancy f WHERE f.public_key_base = pkb.hex AND f.owner_id IS NULL) AS unsold
+    FROM public_key_base pkb
+    WHERE $2
+) t ORDER BY unsold ASC, random() LIMIT 1;",
+    )
+    .bind(kind != WorkTargetKind::PublicKeyBase)
+    .bind(kind != WorkTargetKind::Factory)
+    .fetch_optional(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn fancy_insert_job_work<'c, E>(
+    conn: E,
+    work: JobWorkDbObj,
+) -> Result<JobWorkDbObj, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, JobWorkDbObj>(
+        r"INSERT INTO job_work (uid, job_id, factory, public_key_base, salt_prefix, min_score, created)
+VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *;",
+    )
+    .bind(work.uid)
+    .bind(work.job_id)
+    .bind(work.factory)
+    .bind(&work.public_key_base)
+    .bind(&work.salt_prefix)
+    .bind(work.min_score)
+    .bind(work.created)
+    .fetch_one(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn fancy_get_job_work<'c, E>(
+    conn: E,
+    job_id: Uuid,
+) -> Result<Vec<JobWorkDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, JobWorkDbObj>(
+        r"SELECT * FROM job_work WHERE job_id = $1 ORDER BY created DESC;",
+    )
+    .bind(job_id)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+#[sqlx::test]
+async fn job_work_target_test(pool: PgPool) -> sqlx::Result<()> {
+    use crate::db::model::NetworkDbObj;
+    use crate::db::ops::{set_network_factories, upsert_network};
+    use crate::db::utils::get_current_utc_time;
+
+    assert!(fancy_pick_work_target(&pool, WorkTargetKind::Any)
+        .await?
+        .is_none());
+
+    let mut trans = pool.begin().await?;
+    let factory = DbAddress::from_str("0x9E3F8eCE2b74Ff4F9b4e1d1F3A7dD6F6bFcF3b0A").unwrap();
+    get_or_insert_factory(&mut trans, factory).await?;
+    // CREATE2 deployers are never handed out as work targets
+    let deployer = DbAddress::from_str("0x4e59b44847b379578588920cA78FbF26c0B4956C").unwrap();
+    let deployer_db = upsert_factory(
+        &mut *trans,
+        &ContractFactoryDbObject {
+            uid: Uuid::new_v4(),
+            address: deployer,
+            added: get_current_utc_time(),
+            user_id: None,
+            kind: "solady".to_string(),
+            proxy_init_code_hash: "".to_string(),
+            salt_guard: "none".to_string(),
+            guard_sender: None,
+            create2_deployer: true,
+        },
+    )
+    .await?;
+    assert!(deployer_db.create2_deployer);
+    // factories without a network are user supplied and never handed out either
+    let unlisted = DbAddress::from_str("0x1111111111111111111111111111111111111111").unwrap();
+    get_or_insert_factory(&mut trans, unlisted).await?;
+    upsert_network(
+        &mut *trans,
+        &NetworkDbObj {
+            name: "holesky".to_string(),
+            chain_id: 17000,
+            rpc_urls: Vec::new(),
+            explorer_url: None,
+            gas_strategy: "legacy".to_string(),
+            gas_multiplier: 1.2,
+            deployer_key_env: "DEPLOYER_PRIVATE_KEY".to_string(),
+            explorer_api_url: None,
+            explorer_key_env: "ETHERSCAN_API_KEY".to_string(),
+            enabled: true,
+            added: get_current_utc_time(),
+        },
+    )
+    .await?;
+    set_network_factories(&mut trans, "holesky", &[factory, deployer]).await?;
+    let public_key_base = "0x".to_string() + &"ab".repeat(64);
+    get_or_insert_public_key(&mut trans, &public_key_base).await?;
+    trans.commit().await?;
+
+    // factory has one unsold address, so public key base is picked first
+    insert_fancy_obj(
+        &pool,
+        FancyDbObj {
+            address: DbAddress::from_str("0x0000000000000000000000000000000000000001").unwrap(),
+            salt: "0x00".to_string(),
+            factory: Some(factory),
+            public_key_base: None,
+            created: get_current_utc_time(),
+            score: 1.0,
+            job_id: None,
+            owner_id: None,
+            price: 1,
+            category: "random".to_string(),
+            init_code_hash: None,
+        },
+    )
+    .await?;
+    let target = fancy_pick_work_target(&pool, WorkTargetKind::Any)
+        .await?
+        .unwrap();
+    assert_eq!(target.public_key_base, Some(public_key_base));
+    let target = fancy_pick_work_target(&pool, WorkTargetKind::Factory)
+        .await?
+        .unwrap();
+    assert_eq!(target.factory, Some(factory));
+    assert_eq!(target.unsold, 1);
+    for _ in 0..10 {
+        let target = fancy_pick_work_target(&pool, WorkTargetKind::Any)
+            .await?
+            .unwrap();
+        assert_ne!(target.factory, Some(deployer));
+        assert_ne!(target.factory, Some(unlisted));
+    }
+
+    let now = get_current_utc_time();
+    let job = fancy_insert_job_info(
+        &pool,
+        JobDbObj {
+            uid: Uuid::new_v4(),
+            cruncher_ver: "test".to_string(),
+            started_at: now,
+            updated_at: now,
+            finished_at: None,
+            requestor_id: None,
+            hashes_reported: 0.0,
+            hashes_accepted: 0.0,
+            entries_accepted: 0,
+            entries_rejected: 0,
+            cost_reported: 0.0,
+            miner: fancy_insert_miner_info(
+                &pool,
+                MinerDbObj {
+                    uid: "miner".to_string(),
+                    prov_node_id: None,
+                    prov_reward_addr: None,
+                    prov_name: Some("test".to_string()),
+                    prov_extra_info: None,
+                },
+            )
+            .await?
+            .uid,
+            job_extra_info: None,
+        },
+    )
+    .await?;
+    let work = fancy_insert_job_work(
+        &pool,
+        JobWorkDbObj {
+            uid: Uuid::new_v4(),
+            job_id: job.uid,
+            factory: target.factory,
+            public_key_base: None,
+            salt_prefix: "0x01020304".to_string(),
+            min_score: 1E10,
+            created: now,
+        },
+    )
+    .await?;
+    assert_eq!(fancy_get_job_work(&pool, job.uid).await?, vec![work]);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn user_public_key_test(pool: sqlx::PgPool) -> sqlx::Result<()> {
+    use crate::db::ops::insert_test_user;
+
+    let user = insert_test_user(&pool, "pkb@mail.domain", 0).await?;
+
+    let public_key_base = "0x".to_string() + &"cd".repeat(64);
+    let pkb = insert_user_public_key(&pool, &public_key_base, user.uid)
+        .await?
+        .unwrap();
+    assert_eq!(pkb.user_id, Some(user.uid));
+    // key cannot be claimed twice
+    assert!(
+        insert_user_public_key(&pool, &public_key_base, Uuid::new_v4())
+            .await?
+            .is_none()
+    );
+    assert_eq!(
+        get_public_key_base(&pool, &public_key_base).await?,
+        Some(pkb)
+    );
+
+    // customer keys are offered to miners as well
+    let target = fancy_pick_work_target(&pool, WorkTargetKind::PublicKeyBase)
+        .await?
+        .unwrap();
+    assert_eq!(target.public_key_base, Some(public_key_base));
+    Ok(())
+}
+
+#[sqlx::test]
+async fn fancy_secret_reveal_test(pool: sqlx::PgPool) -> sqlx::Result<()> {
+    use crate::db::ops::insert_test_user;
+    use crate::db::utils::get_current_utc_time;
+
+    let now = get_current_utc_time();
+    let user = insert_test_user(&pool, "reveal@mail.domain", 0).await?;
+    let address = DbAddress::from_str("0x31585b5cd5557777376822555552bb555ee18882").unwrap();
+    insert_fancy_obj(
+        &pool,
+        FancyDbObj {
+            address,
+            salt: "0x9a07547b2ac4220006e585000000000000000000000000000000000000000000".to_string(),
+            factory: None,
+            public_key_base: None,
+            created: now,
+            score: 1.0,
+            job_id: None,
+            owner_id: Some(user.uid),
+            price: 1,
+            category: "".to_string(),
+            init_code_hash: None,
+        },
+    )
+    .await?;
+
+    let reveal =
+        insert_fancy_secret_reveal(&pool, address, user.uid, Some("127.0.0.1".to_string())).await?;
+    assert_eq!(reveal.address, address);
+    let count: i64 =
+        sqlx::query_scalar(r"SELECT COUNT(*) FROM fancy_secret_reveal WHERE address = $1;")
+            .bind(address)
+            .fetch_one(&pool)
+            .await?;
+    assert_eq!(count, 1);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn fancy_score_breakdown_test(pool: PgPool) -> sqlx::Result<()> {
+    use crate::db::utils::get_current_utc_time;
+    use crate::fancy::{score_breakdown, score_fancy};
+
+    let now = get_current_utc_time();
+    let miner = fancy_insert_miner_info(
+        &pool,
+        MinerDbObj {
+            uid: "miner".to_string(),
+            prov_node_id: None,
+            prov_reward_addr: None,
+            prov_name: Some("test".to_string()),
+            prov_extra_info: None,
+        },
+    )
+    .await?;
+    let job = fancy_insert_job_info(
+        &pool,
+        JobDbObj {
+            uid: Uuid::new_v4(),
+            cruncher_ver: "test".to_string(),
+            started_at: now,
+            updated_at: now,
+            finished_at: None,
+            requestor_id: None,
+            hashes_reported: 0.0,
+            hashes_accepted: 0.0,
+            entries_accepted: 0,
+            entries_rejected: 0,
+            cost_reported: 0.0,
+            miner: miner.uid,
+            job_extra_info: None,
+        },
+    )
+    .await?;
+
+    let zeroes = DbAddress::from_str("0x0000000000555555550000000000000000000001").unwrap();
+    let snake = DbAddress::from_str("0x1555555555555555550000000000000000000001").unwrap();
+    for address in [zeroes, snake] {
+        let score = score_fancy(address.addr());
+        insert_fancy_obj(
+            &pool,
+            FancyDbObj {
+                address,
+                salt: "0x00".to_string(),
+                factory: None,
+                public_key_base: None,
+                created: now,
+                score: score.total_score,
+                job_id: Some(job.uid),
+                owner_id: None,
+                price: 1,
+                category: score.category.clone(),
+                init_code_hash: None,
+            },
+        )
+        .await?;
+        fancy_score_upsert(&pool, address, &score_breakdown(address, &score)).await?;
+    }
+
+    let scores = fancy_get_scores(&pool, zeroes).await?;
+    let expected = score_fancy(zeroes.addr());
+    assert_eq!(scores[0].category, expected.category);
+    assert_eq!(scores.len(), expected.scores.len());
+
+    let mut filter = FancyCategoryFilter {
+        category: "snake_score_no_case".to_string(),
+        rank: None,
+        reserved: ReservedStatus::All,
+        after: None,
+        limit: 10,
+    };
+    let best = fancy_list_by_category(&pool, &filter).await?;
+    assert_eq!(best.len(), 2);
+    assert!(best[0].category_difficulty >= best[1].category_difficulty);
+    assert_eq!(fancy_count_by_category(&pool, &filter).await?, 2);
+    let snake_scores = fancy_get_scores(&pool, snake).await?;
+    let snake_row = best.iter().find(|b| b.fancy.address == snake).unwrap();
+    assert_eq!(
+        Some(snake_row.category_rank),
+        snake_scores
+            .iter()
+            .find(|s| s.category == filter.category)
+            .map(|s| s.category_rank)
+    );
+
+    filter.rank = Some(1);
+    let winners = fancy_list_by_category(&pool, &filter).await?;
+    assert!(winners.iter().all(|w| w.fancy.category == filter.category));
+
+    filter.rank = None;
+    filter.after = Some((best[0].category_difficulty, best[0].fancy.address));
+    assert_eq!(fancy_list_by_category(&pool, &filter).await?, best[1..]);
+
+    // removed categories are dropped on the next upsert
+    fancy_score_upsert(&pool, zeroes, &scores[..2]).await?;
+    assert_eq!(fancy_get_scores(&pool, zeroes).await?, scores[..2].to_vec());
+    Ok(())
+}
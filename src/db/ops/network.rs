@@ -0,0 +1,202 @@
+use crate::db::model::NetworkDbObj;
+use crate::types::DbAddress;
+use sqlx::{Executor, Postgres, Transaction};
+
+pub async fn get_networks<'c, E>(
+    conn: E,
+    enabled_only: bool,
+) -> Result<Vec<NetworkDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, NetworkDbObj>(
+        r"SELECT * FROM network WHERE enabled OR NOT $1 ORDER BY name;",
+    )
+    .bind(enabled_only)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn get_network<'c, E>(conn: E, name: &str) -> Result<Option<NetworkDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, NetworkDbObj>(r"SELECT * FROM network WHERE name = $1;")
+        .bind(name)
+        .fetch_optional(conn)
+        .await?;
+    Ok(res)
+}
+
+pub async fn get_network_factories<'c, E>(
+    conn: E,
+    network: &str,
+) -> Result<Vec<DbAddress>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_scalar::<_, DbAddress>(
+        r"SELECT factory FROM network_factory WHERE network = $1 ORDER BY factory;",
+    )
+    .bind(network)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn upsert_network<'c, E>(
+    conn: E,
+    network: &NetworkDbObj,
+) -> Result<NetworkDbObj, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, NetworkDbObj>(
+        r"INSERT INTO network
+(name, chain_id, rpc_urls, explorer_url, gas_strategy, gas_multiplier, deployer_key_env, enabled, added,
+explorer_api_url, explorer_key_env)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+ON CONFLICT (name) DO UPDATE SET
+    chain_id = EXCLUDED.chain_id,
+    rpc_urls = EXCLUDED.rpc_urls,
+    explorer_url = EXCLUDED.explorer_url,
+    gas_strategy = EXCLUDED.gas_strategy,
+    gas_multiplier = EXCLUDED.gas_multiplier,
+    deployer_key_env = EXCLUDED.deployer_key_env,
+    enabled = EXCLUDED.enabled,
+    explorer_api_url = EXCLUDED.explorer_api_url,
+    explorer_key_env = EXCLUDED.explorer_key_env
+RETURNING *;",
+    )
+    .bind(&network.name)
+    .bind(network.chain_id)
+    .bind(&network.rpc_urls)
+    .bind(&network.explorer_url)
+    .bind(&network.gas_strategy)
+    .bind(network.gas_multiplier)
+    .bind(&network.deployer_key_env)
+    .bind(network.enabled)
+    .bind(network.added)
+    .bind(&network.explorer_api_url)
+    .bind(&network.explorer_key_env)
+    .fetch_one(conn)
+    .await?;
+    Ok(res)
+}
+
+/// Replaces the list of factories deployed on network
+pub async fn set_network_factories(
+    conn: &mut Transaction<'_, Postgres>,
+    network: &str,
+    factories: &[DbAddress],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(r"DELETE FROM network_factory WHERE network = $1;")
+        .bind(network)
+        .execute(&mut **conn)
+        .await?;
+    for factory in factories {
+        sqlx::query(r"INSERT INTO network_factory (network, factory) VALUES ($1, $2);")
+            .bind(network)
+            .bind(factory)
+            .execute(&mut **conn)
+            .await?;
+    }
+    Ok(())
+}
+
+#[sqlx::test]
+async fn network_registry_test(pool: sqlx::PgPool) -> sqlx::Result<()> {
+    use crate::db::model::ContractFactoryDbObject;
+    use crate::db::ops::upsert_factory;
+    use crate::db::utils::get_current_utc_time;
+    use sqlx::types::Uuid;
+
+    let now = get_current_utc_time();
+    let factory = upsert_factory(
+        &pool,
+        &ContractFactoryDbObject {
+            uid: Uuid::new_v4(),
+            address: DbAddress::from_str("0x9E3F8eCE2b74Ff4F9b4e1d1F3A7dD6F6bFcF3b0A").unwrap(),
+            added: now,
+            user_id: None,
+            kind: "solady".to_string(),
+            proxy_init_code_hash: "".to_string(),
+            salt_guard: "none".to_string(),
+            guard_sender: None,
+            create2_deployer: false,
+        },
+    )
+    .await?;
+
+    let holesky = NetworkDbObj {
+        name: "holesky".to_string(),
+        chain_id: 17000,
+        rpc_urls: vec!["https://holesky.example".to_string()],
+        explorer_url: None,
+        gas_strategy: "legacy".to_string(),
+        gas_multiplier: 1.2,
+        deployer_key_env: "DEPLOYER_PRIVATE_KEY".to_string(),
+        explorer_api_url: None,
+        explorer_key_env: "ETHERSCAN_API_KEY".to_string(),
+        enabled: true,
+        added: now,
+    };
+    upsert_network(&pool, &holesky).await?;
+    let sepolia = upsert_network(
+        &pool,
+        &NetworkDbObj {
+            name: "sepolia".to_string(),
+            chain_id: 11155111,
+            enabled: false,
+            ..holesky.clone()
+        },
+    )
+    .await?;
+    assert!(!sepolia.enabled);
+
+    let enabled = get_networks(&pool, true).await?;
+    assert_eq!(enabled.len(), 1);
+    assert_eq!(enabled[0].name, "holesky");
+    assert_eq!(get_networks(&pool, false).await?.len(), 2);
+
+    // chain ids are unique across networks
+    assert!(upsert_network(
+        &pool,
+        &NetworkDbObj {
+            name: "holesky2".to_string(),
+            ..holesky.clone()
+        },
+    )
+    .await
+    .is_err());
+
+    let updated = upsert_network(
+        &pool,
+        &NetworkDbObj {
+            rpc_urls: vec![
+                "https://a.example".to_string(),
+                "https://b.example".to_string(),
+            ],
+            gas_strategy: "eip1559".to_string(),
+            ..holesky.clone()
+        },
+    )
+    .await?;
+    assert_eq!(updated.rpc_urls.len(), 2);
+    assert_eq!(get_network(&pool, "holesky").await?, Some(updated));
+    assert_eq!(get_network(&pool, "mainnet").await?, None);
+
+    let mut trans = pool.begin().await?;
+    set_network_factories(&mut trans, "holesky", &[factory.address]).await?;
+    trans.commit().await?;
+    assert_eq!(
+        get_network_factories(&pool, "holesky").await?,
+        vec![factory.address]
+    );
+    let mut trans = pool.begin().await?;
+    set_network_factories(&mut trans, "holesky", &[]).await?;
+    trans.commit().await?;
+    assert!(get_network_factories(&pool, "holesky").await?.is_empty());
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use crate::db::model::{OrderDbObj, OrderStatus};
+use crate::types::DbAddress;
+use chrono::Utc;
+use sqlx::types::Uuid;
+use sqlx::{Executor, Postgres};
+
+pub async fn insert_order<'c, E>(conn: E, order: &OrderDbObj) -> Result<OrderDbObj, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, OrderDbObj>(
+        r"INSERT INTO orders
+(uid, user_id, kind, pattern, difficulty, price, budget, status, created, updated, address)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING *;
+",
+    )
+    .bind(order.uid)
+    .bind(order.user_id)
+    .bind(&order.kind)
+    .bind(&order.pattern)
+    .bind(order.difficulty)
+    .bind(order.price)
+    .bind(order.budget)
+    .bind(order.status)
+    .bind(order.created)
+    .bind(order.updated)
+    .bind(order.address)
+    .fetch_one(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn get_order_by_id<'c, E>(
+    conn: E,
+    uid: Uuid,
+    user_id: Uuid,
+) -> Result<Option<OrderDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res =
+        sqlx::query_as::<_, OrderDbObj>(r"SELECT * FROM orders WHERE uid = $1 AND user_id = $2;")
+            .bind(uid)
+            .bind(user_id)
+            .fetch_optional(conn)
+            .await?;
+    Ok(res)
+}
+
+pub async fn get_orders_by_user<'c, E>(
+    conn: E,
+    user_id: Uuid,
+) -> Result<Vec<OrderDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, OrderDbObj>(
+        r"SELECT * FROM orders WHERE user_id = $1 ORDER BY created DESC;",
+    )
+    .bind(user_id)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn get_open_orders<'c, E>(conn: E, limit: i64) -> Result<Vec<OrderDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, OrderDbObj>(
+        r"SELECT * FROM orders WHERE status = $1 ORDER BY created ASC LIMIT $2;",
+    )
+    .bind(OrderStatus::Open)
+    .bind(limit)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+/// All open orders, oldest first. Rows are not locked, lock the matched one with `lock_open_order`
+pub async fn get_all_open_orders<'c, E>(conn: E) -> Result<Vec<OrderDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, OrderDbObj>(
+        r"SELECT * FROM orders WHERE status = $1 ORDER BY created ASC;",
+    )
+    .bind(OrderStatus::Open)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+/// Locks the order until the end of transaction, so it cannot be fulfilled or cancelled
+/// concurrently. Returns None if it is not open anymore or already locked by other transaction.
+pub async fn lock_open_order<'c, E>(conn: E, uid: Uuid) -> Result<Option<OrderDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, OrderDbObj>(
+        r"SELECT * FROM orders WHERE uid = $1 AND status = $2 FOR UPDATE SKIP LOCKED;",
+    )
+    .bind(uid)
+    .bind(OrderStatus::Open)
+    .fetch_optional(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn order_fulfill<'c, E>(
+    conn: E,
+    uid: Uuid,
+    address: DbAddress,
+) -> Result<Option<OrderDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, OrderDbObj>(
+        r"UPDATE orders SET status = $1, address = $2, updated = $3
+WHERE uid = $4 AND status = $5 RETURNING *;",
+    )
+    .bind(OrderStatus::Fulfilled)
+    .bind(address)
+    .bind(Utc::now().naive_utc())
+    .bind(uid)
+    .bind(OrderStatus::Open)
+    .fetch_optional(conn)
+    .await?;
+    Ok(res)
+}
+
+/// Returns None if the order does not exist, belongs to other user or is not open anymore
+pub async fn order_cancel<'c, E>(
+    conn: E,
+    uid: Uuid,
+    user_id: Uuid,
+) -> Result<Option<OrderDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, OrderDbObj>(
+        r"UPDATE orders SET status = $1, updated = $2
+WHERE uid = $3 AND user_id = $4 AND status = $5 RETURNING *;",
+    )
+    .bind(OrderStatus::Cancelled)
+    .bind(Utc::now().naive_utc())
+    .bind(uid)
+    .bind(user_id)
+    .bind(OrderStatus::Open)
+    .fetch_optional(conn)
+    .await?;
+    Ok(res)
+}
+
+#[sqlx::test]
+async fn order_cancel_fulfill_test(pool: sqlx::PgPool) -> sqlx::Result<()> {
+    use crate::db::ops::insert_test_user;
+    use crate::db::utils::get_current_utc_time;
+
+    let now = get_current_utc_time();
+    let user = insert_test_user(&pool, "order@mail.domain", 1000).await?;
+
+    let order = insert_order(
+        &pool,
+        &OrderDbObj {
+            uid: Uuid::new_v4(),
+            user_id: user.uid,
+            kind: "prefix".to_string(),
+            pattern: "dead".to_string(),
+            difficulty: 65536.0,
+            price: 1,
+            budget: 10,
+            status: OrderStatus::Open,
+            created: now,
+            updated: now,
+            address: None,
+        },
+    )
+    .await?;
+
+    assert_eq!(get_all_open_orders(&pool).await?.len(), 1);
+    assert_eq!(get_open_orders(&pool, 10).await?.len(), 1);
+    // locked order is skipped by other transactions
+    let mut trans = pool.begin().await?;
+    assert!(lock_open_order(&mut *trans, order.uid).await?.is_some());
+    assert!(lock_open_order(&pool, order.uid).await?.is_none());
+    trans.rollback().await?;
+    // other user cannot cancel
+    assert!(order_cancel(&pool, order.uid, Uuid::new_v4())
+        .await?
+        .is_none());
+    let cancelled = order_cancel(&pool, order.uid, user.uid).await?.unwrap();
+    assert_eq!(cancelled.status, OrderStatus::Cancelled);
+    // cancelled order cannot be cancelled again (no double refund) nor fulfilled
+    assert!(order_cancel(&pool, order.uid, user.uid).await?.is_none());
+    assert!(get_all_open_orders(&pool).await?.is_empty());
+    assert!(lock_open_order(&pool, order.uid).await?.is_none());
+    assert_eq!(get_orders_by_user(&pool, user.uid).await?.len(), 1);
+    Ok(())
+}
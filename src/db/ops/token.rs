@@ -0,0 +1,155 @@
+use crate::db::model::{
+    LedgerAccount, TokenBalanceMismatchDbObj, TokenLedgerDbObj, TokenLedgerKind, TokenLedgerRefs,
+};
+use chrono::Utc;
+use sqlx::types::Uuid;
+use sqlx::{Executor, Postgres, Transaction};
+
+/// Moves tokens between two accounts as single ledger transaction.
+/// Cached balance in users.tokens is updated in the same database transaction.
+pub async fn token_transfer(
+    conn: &mut Transaction<'_, Postgres>,
+    from: LedgerAccount,
+    to: LedgerAccount,
+    amount: i64,
+    kind: TokenLedgerKind,
+    refs: TokenLedgerRefs,
+) -> Result<Uuid, sqlx::Error> {
+    let tx_id = Uuid::new_v4();
+    let now = Utc::now().naive_utc();
+    for (account, amount) in [(from, -amount), (to, amount)] {
+        sqlx::query(
+            r"INSERT INTO token_ledger (uid, tx_id, account, user_id, amount, kind, address, order_id, note, created)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10);",
+        )
+        .bind(Uuid::new_v4())
+        .bind(tx_id)
+        .bind(account.name())
+        .bind(account.user_id())
+        .bind(amount)
+        .bind(kind)
+        .bind(refs.address)
+        .bind(refs.order_id)
+        .bind(&refs.note)
+        .bind(now)
+        .execute(&mut **conn)
+        .await?;
+
+        if let Some(user_id) = account.user_id() {
+            let res = sqlx::query(r"UPDATE users SET tokens = tokens + $1 WHERE uid = $2;")
+                .bind(amount)
+                .bind(user_id)
+                .execute(&mut **conn)
+                .await?;
+            if res.rows_affected() != 1 {
+                return Err(sqlx::Error::RowNotFound);
+            }
+        }
+    }
+    Ok(tx_id)
+}
+
+pub async fn get_user_token_history<'c, E>(
+    conn: E,
+    user_id: Uuid,
+    limit: i64,
+) -> Result<Vec<TokenLedgerDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, TokenLedgerDbObj>(
+        r"SELECT * FROM token_ledger WHERE user_id = $1 ORDER BY created DESC, uid LIMIT $2;",
+    )
+    .bind(user_id)
+    .bind(limit)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn get_user_ledger_balance<'c, E>(conn: E, user_id: Uuid) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_scalar::<_, i64>(
+        r"SELECT COALESCE(SUM(amount), 0)::BIGINT FROM token_ledger WHERE user_id = $1;",
+    )
+    .bind(user_id)
+    .fetch_one(conn)
+    .await?;
+    Ok(res)
+}
+
+/// Users whose users.tokens does not match sum of their ledger entries
+pub async fn get_token_balance_mismatches<'c, E>(
+    conn: E,
+) -> Result<Vec<TokenBalanceMismatchDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, TokenBalanceMismatchDbObj>(
+        r"SELECT u.uid, u.email, u.tokens, COALESCE(SUM(tl.amount), 0)::BIGINT AS ledger_balance
+FROM users u LEFT JOIN token_ledger tl ON tl.user_id = u.uid
+GROUP BY u.uid, u.email, u.tokens
+HAVING u.tokens <> COALESCE(SUM(tl.amount), 0);",
+    )
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+#[sqlx::test]
+async fn token_ledger_test(pool: sqlx::PgPool) -> sqlx::Result<()> {
+    use crate::db::ops::{get_user, insert_test_user};
+
+    let user = insert_test_user(&pool, "ledger@mail.domain", 0).await?;
+
+    let mut trans = pool.begin().await?;
+    token_transfer(
+        &mut trans,
+        LedgerAccount::Treasury,
+        LedgerAccount::User(user.uid),
+        1000,
+        TokenLedgerKind::Grant,
+        TokenLedgerRefs::default(),
+    )
+    .await?;
+    token_transfer(
+        &mut trans,
+        LedgerAccount::User(user.uid),
+        LedgerAccount::Sales,
+        300,
+        TokenLedgerKind::Purchase,
+        TokenLedgerRefs {
+            note: Some("test purchase".to_string()),
+            ..Default::default()
+        },
+    )
+    .await?;
+    trans.commit().await?;
+
+    assert_eq!(get_user(&pool, &user.email).await?.tokens, 700);
+    assert_eq!(get_user_ledger_balance(&pool, user.uid).await?, 700);
+    let history = get_user_token_history(&pool, user.uid, 10).await?;
+    assert_eq!(history.len(), 2);
+    assert!(history
+        .iter()
+        .any(|e| e.kind == TokenLedgerKind::Purchase && e.amount == -300));
+    // every transaction is balanced
+    let total: i64 =
+        sqlx::query_scalar(r"SELECT COALESCE(SUM(amount), 0)::BIGINT FROM token_ledger;")
+            .fetch_one(&pool)
+            .await?;
+    assert_eq!(total, 0);
+    assert!(get_token_balance_mismatches(&pool).await?.is_empty());
+
+    // direct update of cached balance is detected
+    sqlx::query(r"UPDATE users SET tokens = 5 WHERE uid = $1;")
+        .bind(user.uid)
+        .execute(&pool)
+        .await?;
+    let mismatches = get_token_balance_mismatches(&pool).await?;
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].ledger_balance, 700);
+    Ok(())
+}
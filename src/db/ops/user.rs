@@ -62,6 +62,35 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *;
         .await
 }
 
+/// Google login user with given token balance
+#[cfg(test)]
+pub async fn insert_test_user<'c, E>(
+    conn: E,
+    email: &str,
+    tokens: i64,
+) -> Result<UserDbObj, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let now = crate::db::utils::get_current_utc_time();
+    insert_user(
+        conn,
+        &UserDbObj {
+            uid: sqlx::types::Uuid::new_v4(),
+            email: email.to_string(),
+            pass_hash: "".to_string(),
+            created_date: now,
+            last_pass_change: now,
+            set_pass_token: None,
+            set_pass_token_date: None,
+            allow_pass_login: false,
+            allow_google_login: true,
+            tokens,
+        },
+    )
+    .await
+}
+
 pub async fn save_reset_token(
     conn: &PgPool,
     email: &str,
@@ -141,16 +170,16 @@ where
     Ok(res)
 }
 
-pub async fn update_user_tokens<'c, E>(conn: E, email: &str, tokens: i64) -> Result<(), sqlx::Error>
+/// Locks the user row until the end of transaction, so concurrent spending sees the current balance
+pub async fn get_user_for_update<'c, E>(conn: E, email: &str) -> Result<UserDbObj, sqlx::Error>
 where
     E: Executor<'c, Database = Postgres>,
 {
-    let _res = sqlx::query(r"UPDATE users SET tokens = $1 WHERE email = $2")
-        .bind(tokens)
+    let res = sqlx::query_as::<_, UserDbObj>(r"SELECT * FROM users WHERE email = $1 FOR UPDATE")
         .bind(email)
-        .execute(conn)
+        .fetch_one(conn)
         .await?;
-    Ok(())
+    Ok(res)
 }
 
 #[sqlx::test]
@@ -0,0 +1,202 @@
+use crate::db::model::ContractVerificationDbObj;
+use chrono::NaiveDateTime;
+use sqlx::types::Uuid;
+use sqlx::{Executor, Postgres};
+
+/// Queues verification on given explorers, previous results are reset
+pub async fn insert_contract_verifications<'c, E>(
+    conn: E,
+    contract_id: Uuid,
+    explorers: &[String],
+    now: NaiveDateTime,
+) -> Result<Vec<ContractVerificationDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, ContractVerificationDbObj>(
+        r"INSERT INTO contract_verification
+(contract_id, explorer, status, guid, attempts, error, next_check, created, updated)
+SELECT $1, explorer, 'pending', NULL, 0, NULL, $3, $3, $3 FROM UNNEST($2::text[]) AS explorer
+ON CONFLICT (contract_id, explorer) DO UPDATE SET
+    status = 'pending',
+    guid = NULL,
+    attempts = 0,
+    error = NULL,
+    next_check = EXCLUDED.next_check,
+    updated = EXCLUDED.updated
+RETURNING *;",
+    )
+    .bind(contract_id)
+    .bind(explorers)
+    .bind(now)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn get_contract_verifications<'c, E>(
+    conn: E,
+    contract_id: Uuid,
+) -> Result<Vec<ContractVerificationDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, ContractVerificationDbObj>(
+        r"SELECT * FROM contract_verification WHERE contract_id = $1 ORDER BY explorer;",
+    )
+    .bind(contract_id)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn get_user_contract_verifications<'c, E>(
+    conn: E,
+    user_id: Uuid,
+) -> Result<Vec<ContractVerificationDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, ContractVerificationDbObj>(
+        r"SELECT cv.* FROM contract_verification cv
+        JOIN contract c ON c.contract_id = cv.contract_id
+        WHERE c.user_id = $1
+        ORDER BY cv.contract_id, cv.explorer;",
+    )
+    .bind(user_id)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+/// Takes verifications due for submission or status check, they are not due again until claimed_until.
+/// Empty networks means all of them.
+pub async fn claim_due_verifications<'c, E>(
+    conn: E,
+    networks: &[String],
+    now: NaiveDateTime,
+    claimed_until: NaiveDateTime,
+    limit: i64,
+) -> Result<Vec<ContractVerificationDbObj>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, ContractVerificationDbObj>(
+        r"UPDATE contract_verification SET next_check = $3
+        WHERE (contract_id, explorer) IN (
+            SELECT cv.contract_id, cv.explorer FROM contract_verification cv
+            JOIN contract c ON c.contract_id = cv.contract_id
+            WHERE cv.status IN ('pending', 'submitted')
+                AND cv.next_check <= $2
+                AND (cardinality($1::text[]) = 0 OR c.network = ANY($1))
+            ORDER BY cv.next_check
+            LIMIT $4
+            FOR UPDATE OF cv SKIP LOCKED
+        )
+        RETURNING *;",
+    )
+    .bind(networks)
+    .bind(now)
+    .bind(claimed_until)
+    .bind(limit)
+    .fetch_all(conn)
+    .await?;
+    Ok(res)
+}
+
+pub async fn update_contract_verification<'c, E>(
+    conn: E,
+    verification: &ContractVerificationDbObj,
+) -> Result<ContractVerificationDbObj, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let res = sqlx::query_as::<_, ContractVerificationDbObj>(
+        r"UPDATE contract_verification SET
+    status = $3,
+    guid = $4,
+    attempts = $5,
+    error = $6,
+    next_check = $7,
+    updated = $8
+    WHERE contract_id = $1 AND explorer = $2 RETURNING *;",
+    )
+    .bind(verification.contract_id)
+    .bind(&verification.explorer)
+    .bind(&verification.status)
+    .bind(&verification.guid)
+    .bind(verification.attempts)
+    .bind(&verification.error)
+    .bind(verification.next_check)
+    .bind(verification.updated)
+    .fetch_one(conn)
+    .await?;
+    Ok(res)
+}
+
+#[sqlx::test]
+async fn contract_verification_test(pool: sqlx::PgPool) -> sqlx::Result<()> {
+    use crate::db::model::{ContractDbObj, DeployStatus, VerifyStatus};
+    use crate::db::ops::{insert_contract_obj, insert_test_user};
+    use crate::db::utils::get_current_utc_time;
+
+    let now = get_current_utc_time();
+    let user = insert_test_user(&pool, "verify@mail.domain", 0).await?;
+    let contract = insert_contract_obj(
+        &pool,
+        ContractDbObj {
+            contract_id: Uuid::new_v4(),
+            user_id: user.uid,
+            created: now,
+            address: None,
+            network: "holesky".to_string(),
+            data: "{}".to_string(),
+            tx: None,
+            deploy_status: DeployStatus::Succeeded,
+            deploy_requested: None,
+            deploy_sent: None,
+            deployed: Some(now),
+            deploy_error: None,
+            deploy_block: None,
+            compiler_version: None,
+            compiler_settings: None,
+            verifications: Vec::new(),
+        },
+    )
+    .await?;
+
+    let explorers = ["etherscan".to_string(), "sourcify".to_string()];
+    let queued =
+        insert_contract_verifications(&pool, contract.contract_id, &explorers, now).await?;
+    assert_eq!(queued.len(), 2);
+    assert!(queued.iter().all(|v| v.status == VerifyStatus::Pending));
+
+    let later = now + chrono::Duration::seconds(60);
+    let other_network =
+        claim_due_verifications(&pool, &["mainnet".to_string()], now, later, 10).await?;
+    assert!(other_network.is_empty());
+    let claimed = claim_due_verifications(&pool, &[], now, later, 10).await?;
+    assert_eq!(claimed.len(), 2);
+    assert!(claim_due_verifications(&pool, &[], now, later, 10)
+        .await?
+        .is_empty());
+
+    let mut etherscan = claimed
+        .into_iter()
+        .find(|v| v.explorer == "etherscan")
+        .unwrap();
+    etherscan.status = VerifyStatus::Verified;
+    etherscan.guid = Some("guid".to_string());
+    update_contract_verification(&pool, &etherscan).await?;
+    assert_eq!(
+        get_user_contract_verifications(&pool, user.uid).await?,
+        get_contract_verifications(&pool, contract.contract_id).await?
+    );
+
+    // requesting again resets the results
+    let queued =
+        insert_contract_verifications(&pool, contract.contract_id, &explorers[..1], later).await?;
+    assert_eq!(queued[0].status, VerifyStatus::Pending);
+    assert_eq!(queued[0].guid, None);
+    Ok(())
+}
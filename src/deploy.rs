@@ -1,127 +1,1014 @@
-use crate::db::model::ContractDbObj;
-use crate::db::ops::{fancy_get_by_address, update_contract_data};
+use crate::config::{
+    get_deploy_confirmations, get_deploy_receipt_timeout, get_deploy_tokens_per_native,
+    get_deployer_private_key, get_rpc_url,
+};
+use crate::db::model::{ContractDbObj, DeployStatus, GasStrategy, NetworkDbObj};
+use crate::db::ops::{
+    fancy_get_by_address, get_factory_by_address, get_network, get_network_factories,
+    update_contract_data,
+};
 use crate::error::AddressologyError;
+use crate::fancy::factory_create3_params;
+use crate::hash::{compute_create3, Create3FactoryKind, Create3Params, SaltGuard};
 use crate::types::DbAddress;
+use crate::verify::request_verification;
 use crate::{err_custom_create, DeployData};
+use lazy_static::lazy_static;
+use serde::Serialize;
 use sqlx::PgPool;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use web3::ethabi::{ParamType, Token};
+use web3::signing::{keccak256, Key, SecretKey, SecretKeyRef};
+use web3::transports::Http;
+use web3::types::{
+    Address, BlockId, BlockNumber, Bytes, CallRequest, SignedTransaction, TransactionId,
+    TransactionParameters, TransactionReceipt, H256, U256, U64,
+};
+use web3::Web3;
 
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Calldata deploying init code under salt.
+/// Without factory kind the target is CREATE2 deterministic deployment proxy,
+/// which takes raw salt followed by init code.
+pub fn build_deploy_call(
+    kind: Option<Create3FactoryKind>,
+    salt: [u8; 32],
+    init_code: &[u8],
+) -> Vec<u8> {
+    let signature = match kind {
+        None => return [salt.as_slice(), init_code].concat(),
+        Some(Create3FactoryKind::CreateX) => "deployCreate3(bytes32,bytes)",
+        Some(_) => "deploy(bytes32,bytes)",
+    };
+    let args = web3::ethabi::encode(&[
+        Token::FixedBytes(salt.to_vec()),
+        Token::Bytes(init_code.to_vec()),
+    ]);
+    [&keccak256(signature.as_bytes())[..4], args.as_slice()].concat()
+}
+
+pub fn parse_deployer_key(private_key: &str) -> Result<SecretKey, AddressologyError> {
+    let bytes = hex::decode(private_key.trim().trim_start_matches("0x"))
+        .map_err(|e| err_custom_create!("Failed to decode deployer key: {}", e))?;
+    SecretKey::from_slice(&bytes).map_err(|e| err_custom_create!("Invalid deployer key: {}", e))
+}
+
+/// Gas limit estimated by the node, raised by network gas multiplier
+pub async fn estimate_deploy_gas(
+    web3: &Web3<Http>,
+    network: &NetworkDbObj,
+    from: Address,
+    to: Address,
+    data: &[u8],
+) -> Result<U256, AddressologyError> {
+    let gas = web3
+        .eth()
+        .estimate_gas(
+            CallRequest {
+                from: Some(from),
+                to: Some(to),
+                data: Some(Bytes(data.to_vec())),
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .map_err(|e| err_custom_create!("Failed to estimate gas: {}", e))?;
+    Ok(U256::from(
+        (gas.as_u128() as f64 * network.gas_multiplier.max(1.0)).ceil() as u128,
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeParams {
+    /// Highest price paid per gas unit, gas price of legacy transactions
+    pub max_fee_per_gas: U256,
+    /// Set for EIP-1559 transactions
+    pub max_priority_fee_per_gas: Option<U256>,
+}
+
+pub async fn fetch_fee_params(
+    web3: &Web3<Http>,
+    network: &NetworkDbObj,
+) -> Result<FeeParams, AddressologyError> {
+    let strategy =
+        GasStrategy::from_str(&network.gas_strategy).map_err(|e| err_custom_create!("{}", e))?;
+    let gas_price = web3
+        .eth()
+        .gas_price()
+        .await
+        .map_err(|e| err_custom_create!("Failed to get gas price: {}", e))?;
+    match strategy {
+        GasStrategy::Legacy => Ok(FeeParams {
+            max_fee_per_gas: gas_price,
+            max_priority_fee_per_gas: None,
+        }),
+        GasStrategy::Eip1559 => {
+            let base_fee = web3
+                .eth()
+                .block(BlockId::Number(BlockNumber::Latest))
+                .await
+                .map_err(|e| err_custom_create!("Failed to get latest block: {}", e))?
+                .and_then(|block| block.base_fee_per_gas)
+                .ok_or_else(|| err_custom_create!("Network {} has no base fee", network.name))?;
+            let tip = gas_price.saturating_sub(base_fee);
+            Ok(FeeParams {
+                max_fee_per_gas: base_fee * 2 + tip,
+                max_priority_fee_per_gas: Some(tip),
+            })
+        }
+    }
+}
+
+lazy_static! {
+    static ref DEPLOYER_LOCKS: std::sync::Mutex<HashMap<Address, Arc<tokio::sync::Mutex<()>>>> =
+        Default::default();
+}
+
+/// Serializes sending from one deployer account, so concurrent deployments never share a nonce.
+/// Only covers this process, workers in other processes need their own deployer keys.
+async fn lock_deployer(deployer: Address) -> tokio::sync::OwnedMutexGuard<()> {
+    let lock = DEPLOYER_LOCKS
+        .lock()
+        .unwrap()
+        .entry(deployer)
+        .or_default()
+        .clone();
+    lock.lock_owned().await
+}
+
+/// Signs deployment with the next nonce of the deployer, hold `lock_deployer` until broadcast
+pub async fn sign_deploy_tx(
+    web3: &Web3<Http>,
+    network: &NetworkDbObj,
+    key: &SecretKey,
+    to: Address,
+    data: Vec<u8>,
+) -> Result<SignedTransaction, AddressologyError> {
+    let from = SecretKeyRef::new(key).address();
+    let gas = estimate_deploy_gas(web3, network, from, to, &data).await?;
+    let fees = fetch_fee_params(web3, network).await?;
+    // pending count skips transactions still in the mempool, e.g. timed out deployments
+    let nonce = web3
+        .eth()
+        .transaction_count(from, Some(BlockNumber::Pending))
+        .await
+        .map_err(|e| err_custom_create!("Failed to get nonce of {:#x}: {}", from, e))?;
+
+    let mut params = TransactionParameters {
+        nonce: Some(nonce),
+        to: Some(to),
+        gas,
+        data: Bytes(data),
+        chain_id: Some(network.chain_id as u64),
+        ..Default::default()
+    };
+    match fees.max_priority_fee_per_gas {
+        Some(tip) => {
+            params.transaction_type = Some(2.into());
+            params.max_fee_per_gas = Some(fees.max_fee_per_gas);
+            params.max_priority_fee_per_gas = Some(tip);
+        }
+        None => params.gas_price = Some(fees.max_fee_per_gas),
+    }
+
+    web3.accounts()
+        .sign_transaction(params, SecretKeyRef::new(key))
+        .await
+        .map_err(|e| err_custom_create!("Failed to sign transaction: {}", e))
+}
+
+/// Native currency amount, for display only
+pub fn wei_to_native(wei: U256) -> f64 {
+    wei.as_u128() as f64 / 1e18
+}
+
+/// Human readable reason from revert data, supports `Error(string)` and `Panic(uint256)`
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    if data.len() < 4 {
+        return None;
+    }
+    let (selector, args) = data.split_at(4);
+    if selector == &keccak256(b"Error(string)")[..4] {
+        match web3::ethabi::decode(&[ParamType::String], args)
+            .ok()?
+            .pop()?
+        {
+            Token::String(reason) => Some(reason),
+            _ => None,
+        }
+    } else if selector == &keccak256(b"Panic(uint256)")[..4] {
+        match web3::ethabi::decode(&[ParamType::Uint(256)], args)
+            .ok()?
+            .pop()?
+        {
+            Token::Uint(code) => Some(format!("Panic(0x{:02x})", code)),
+            _ => None,
+        }
+    } else {
+        Some(format!("Custom error 0x{}", hex::encode(selector)))
+    }
+}
+
+/// Replays reverted transaction as a call in its block to get the revert reason
+pub async fn fetch_revert_reason(web3: &Web3<Http>, tx_hash: H256, block: U64) -> Option<String> {
+    let tx = match web3.eth().transaction(TransactionId::Hash(tx_hash)).await {
+        Ok(Some(tx)) => tx,
+        Ok(None) => return None,
+        Err(e) => {
+            log::warn!("Failed to get transaction {:#x}: {}", tx_hash, e);
+            return None;
+        }
+    };
+    let call = CallRequest {
+        from: tx.from,
+        to: tx.to,
+        gas: Some(tx.gas),
+        value: Some(tx.value),
+        data: Some(tx.input),
+        ..Default::default()
+    };
+    match web3
+        .eth()
+        .call(call, Some(BlockId::Number(BlockNumber::Number(block))))
+        .await
+    {
+        Ok(_) => None,
+        Err(web3::Error::Rpc(err)) => err
+            .data
+            .as_ref()
+            .and_then(|data| data.as_str())
+            .and_then(|data| hex::decode(data.trim_start_matches("0x")).ok())
+            .and_then(|data| decode_revert_reason(&data))
+            .or(Some(err.message)),
+        Err(e) => {
+            log::warn!("Failed to replay transaction {:#x}: {}", tx_hash, e);
+            None
+        }
+    }
+}
+
+pub async fn broadcast(
+    web3: &Web3<Http>,
+    signed: &SignedTransaction,
+) -> Result<H256, AddressologyError> {
+    let tx_hash = web3
+        .eth()
+        .send_raw_transaction(signed.raw_transaction.clone())
+        .await
+        .map_err(|e| err_custom_create!("Failed to send transaction: {}", e))?;
+    if tx_hash != signed.transaction_hash {
+        log::warn!(
+            "Node returned tx hash {:#x}, expected {:#x}",
+            tx_hash,
+            signed.transaction_hash
+        );
+    }
+    Ok(tx_hash)
+}
+
+/// Polls for the receipt, reverted transactions are reported with their revert reason.
+/// Returns None when the transaction is not mined within timeout.
+pub async fn wait_for_receipt(
+    web3: &Web3<Http>,
+    tx_hash: H256,
+    timeout: Duration,
+) -> Result<Option<TransactionReceipt>, AddressologyError> {
+    let started = std::time::Instant::now();
+    let receipt = loop {
+        match web3.eth().transaction_receipt(tx_hash).await {
+            Ok(Some(receipt)) if receipt.block_number.is_some() => break receipt,
+            Ok(_) => {}
+            Err(e) => log::warn!("Failed to get receipt of {:#x}: {}", tx_hash, e),
+        }
+        if started.elapsed() > timeout {
+            return Ok(None);
+        }
+        tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+    };
+    if receipt.status != Some(1.into()) {
+        let block = receipt.block_number.unwrap_or_default();
+        return Err(match fetch_revert_reason(web3, tx_hash, block).await {
+            Some(reason) => err_custom_create!(
+                "Transaction {:#x} reverted in block {}: {}",
+                tx_hash,
+                block,
+                reason
+            ),
+            None => err_custom_create!("Transaction {:#x} reverted in block {}", tx_hash, block),
+        });
+    }
+    Ok(Some(receipt))
+}
+
+/// Waits until there are enough blocks on top of the transaction block.
+/// Receipt is fetched again on every poll, so a reorg moving the transaction is followed.
+/// Returns the block the transaction ended up in, None when not confirmed within timeout.
+pub async fn wait_for_confirmations(
+    web3: &Web3<Http>,
+    tx_hash: H256,
+    confirmations: u64,
+    timeout: Duration,
+) -> Result<Option<U64>, AddressologyError> {
+    let started = std::time::Instant::now();
+    loop {
+        let receipt = web3
+            .eth()
+            .transaction_receipt(tx_hash)
+            .await
+            .map_err(|e| err_custom_create!("Failed to get receipt of {:#x}: {}", tx_hash, e))?
+            .ok_or_else(|| {
+                err_custom_create!("Transaction {:#x} dropped from the chain", tx_hash)
+            })?;
+        if receipt.status != Some(1.into()) {
+            return Err(err_custom_create!(
+                "Transaction {:#x} reverted after reorg",
+                tx_hash
+            ));
+        }
+        let block = receipt
+            .block_number
+            .ok_or_else(|| err_custom_create!("Transaction {:#x} is pending again", tx_hash))?;
+        let head = web3
+            .eth()
+            .block_number()
+            .await
+            .map_err(|e| err_custom_create!("Failed to get block number: {}", e))?;
+        if head >= block + confirmations {
+            return Ok(Some(block));
+        }
+        if started.elapsed() > timeout {
+            return Ok(None);
+        }
+        tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+    }
+}
+
+pub async fn verify_deployed_code(
+    web3: &Web3<Http>,
+    address: Address,
+) -> Result<(), AddressologyError> {
+    let code = web3
+        .eth()
+        .code(address, None)
+        .await
+        .map_err(|e| err_custom_create!("Failed to get code: {}", e))?;
+    if code.0.is_empty() {
+        return Err(err_custom_create!("There is no code at {:#x}", address));
+    }
+    Ok(())
+}
+
+/// Moves contract to the next deploy state and persists it
+async fn save_deploy_status(
+    conn: &PgPool,
+    contract: &mut ContractDbObj,
+    status: DeployStatus,
+) -> Result<(), AddressologyError> {
+    if !contract.deploy_status.can_transition_to(&status) {
+        return Err(err_custom_create!(
+            "Invalid deploy status transition {:?} -> {:?}",
+            contract.deploy_status,
+            status
+        ));
+    }
+    contract.deploy_status = status;
+    *contract = update_contract_data(conn, contract.clone())
+        .await
+        .map_err(|e| err_custom_create!("Failed to update contract: {}", e))?;
+    Ok(())
+}
+
+async fn record_deploy_failure(
+    conn: &PgPool,
+    mut contract: ContractDbObj,
+    err: &AddressologyError,
+) {
+    log::error!(
+        "Deployment of contract {} failed: {}",
+        contract.contract_id,
+        err
+    );
+    contract.deploy_error = Some(err.to_string());
+    if let Err(e) = save_deploy_status(conn, &mut contract, DeployStatus::Failed).await {
+        log::error!(
+            "Failed to record failure of contract {}: {}",
+            contract.contract_id,
+            e
+        );
+    }
+}
+
+/// Looks up network in the registry and connects to the first RPC url serving its chain.
+/// RPC_URL_<NETWORK> environment variable takes precedence over registered urls.
+pub async fn connect_network(
+    conn: &PgPool,
+    name: &str,
+) -> Result<(Web3<Http>, NetworkDbObj), AddressologyError> {
+    let network = get_network(conn, name)
+        .await
+        .map_err(|e| err_custom_create!("Failed to get network: {}", e))?
+        .ok_or_else(|| err_custom_create!("Network {} is not supported", name))?;
+    if !network.enabled {
+        return Err(err_custom_create!("Network {} is disabled", name));
+    }
+
+    let rpc_urls = get_rpc_url(name)
+        .into_iter()
+        .chain(network.rpc_urls.iter().cloned());
+    let mut last_err = err_custom_create!("No RPC url configured for {}", name);
+    for rpc_url in rpc_urls {
+        let web3 = match Http::new(&rpc_url) {
+            Ok(transport) => Web3::new(transport),
+            Err(e) => {
+                last_err = err_custom_create!("Invalid RPC url of {}: {}", name, e);
+                continue;
+            }
+        };
+        match web3.eth().chain_id().await {
+            Ok(chain_id) if chain_id == U256::from(network.chain_id as u64) => {
+                return Ok((web3, network))
+            }
+            Ok(chain_id) => {
+                last_err = err_custom_create!(
+                    "RPC of {} serves chain {}, expected {}",
+                    name,
+                    chain_id,
+                    network.chain_id
+                );
+            }
+            Err(e) => {
+                last_err = err_custom_create!("RPC of {} unavailable: {}", name, e);
+            }
+        }
+        log::warn!("{}", last_err);
+    }
+    Err(last_err)
+}
+
+/// Result of a deployment round that did not fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeployProgress {
+    Succeeded,
+    /// Transaction is not mined or confirmed yet, the contract keeps its state for the next round
+    Pending,
+}
+
+/// Follows sent transaction through Mined to Succeeded
+async fn track_deploy_tx(
+    conn: &PgPool,
+    web3: &Web3<Http>,
+    contract: &mut ContractDbObj,
+    tx_hash: H256,
+    expected_address: Address,
+) -> Result<DeployProgress, AddressologyError> {
+    let timeout = Duration::from_secs(get_deploy_receipt_timeout().max(0) as u64);
+    if contract.deploy_status == DeployStatus::TxSent {
+        let Some(receipt) = wait_for_receipt(web3, tx_hash, timeout).await? else {
+            log::warn!(
+                "Transaction {:#x} of contract {} not mined within {} seconds",
+                tx_hash,
+                contract.contract_id,
+                timeout.as_secs()
+            );
+            return Ok(DeployProgress::Pending);
+        };
+        contract.deploy_block = receipt.block_number.map(|b| b.as_u64() as i64);
+        save_deploy_status(conn, contract, DeployStatus::Mined).await?;
+    }
+
+    let Some(block) = wait_for_confirmations(
+        web3,
+        tx_hash,
+        get_deploy_confirmations().max(0) as u64,
+        timeout,
+    )
+    .await?
+    else {
+        log::warn!(
+            "Transaction {:#x} of contract {} not confirmed within {} seconds",
+            tx_hash,
+            contract.contract_id,
+            timeout.as_secs()
+        );
+        return Ok(DeployProgress::Pending);
+    };
+    verify_deployed_code(web3, expected_address).await?;
+
+    contract.deploy_block = Some(block.as_u64() as i64);
+    finish_deploy(conn, contract).await?;
+    Ok(DeployProgress::Succeeded)
+}
+
+/// Marks contract as deployed and queues its source verification
+async fn finish_deploy(
+    conn: &PgPool,
+    contract: &mut ContractDbObj,
+) -> Result<(), AddressologyError> {
+    contract.deployed = Some(chrono::Utc::now().naive_utc());
+    save_deploy_status(conn, contract, DeployStatus::Succeeded).await?;
+
+    // verification failures never fail the deployment
+    match request_verification(conn, contract).await {
+        Ok(queued) => log::info!(
+            "Queued verification of {} on {} explorers",
+            contract.contract_id,
+            queued.len()
+        ),
+        Err(e) => log::error!(
+            "Failed to queue verification of {}: {}",
+            contract.contract_id,
+            e
+        ),
+    }
+    Ok(())
+}
+
+/// Deploys requested contract, any failure is recorded on the contract
 pub async fn handle_fancy_deploy(
     conn: &PgPool,
     contract: ContractDbObj,
-) -> Result<(), AddressologyError> {
+) -> Result<DeployProgress, AddressologyError> {
+    let mut contract = contract;
+    let res = deploy_contract(conn, &mut contract).await;
+    if let Err(e) = &res {
+        record_deploy_failure(conn, contract, e).await;
+    }
+    res
+}
+
+/// Continues tracking of contracts left in TxSent or Mined, e.g. after restart
+pub async fn handle_fancy_deploy_resume(
+    conn: &PgPool,
+    contract: ContractDbObj,
+) -> Result<DeployProgress, AddressologyError> {
+    let mut contract = contract;
+    let res = resume_contract(conn, &mut contract).await;
+    if let Err(e) = &res {
+        record_deploy_failure(conn, contract, e).await;
+    }
+    res
+}
+
+async fn resume_contract(
+    conn: &PgPool,
+    contract: &mut ContractDbObj,
+) -> Result<DeployProgress, AddressologyError> {
+    let tx_hash = contract
+        .tx
+        .as_ref()
+        .ok_or_else(|| err_custom_create!("Contract has no transaction to follow"))?;
+    let tx_hash = H256::from_str(tx_hash)
+        .map_err(|e| err_custom_create!("Invalid tx hash {}: {}", tx_hash, e))?;
     let address = contract
         .address
-        .clone()
+        .as_ref()
         .ok_or_else(|| err_custom_create!("Address not found on db obj"))?;
+    let address = Address::from_str(address)
+        .map_err(|e| err_custom_create!("Failed to parse address: {}", e))?;
+    let (web3, _network) = connect_network(conn, &contract.network).await?;
+    track_deploy_tx(conn, &web3, contract, tx_hash, address).await
+}
+
+/// Everything needed to send deployment transaction of the contract
+pub struct PreparedDeploy {
+    pub web3: Web3<Http>,
+    pub network: NetworkDbObj,
+    pub key: SecretKey,
+    /// Factory or CREATE2 deployer the transaction is sent to
+    pub to: Address,
+    pub data: Vec<u8>,
+    pub address: Address,
+    /// Contract code is already at the address, nothing to send
+    pub already_deployed: bool,
+}
+
+/// Why the contract cannot be deployed
+#[derive(Debug)]
+pub enum DeployError {
+    /// Contract does not fit its address or factory, safe to show to the user
+    InvalidInput(String),
+    /// Network RPC failed, its error may contain the RPC url
+    Rpc(AddressologyError),
+    Failed(AddressologyError),
+}
+
+impl Display for DeployError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeployError::InvalidInput(msg) => write!(f, "{}", msg),
+            DeployError::Rpc(e) | DeployError::Failed(e) => write!(f, "{}", e.inner),
+        }
+    }
+}
+
+impl From<AddressologyError> for DeployError {
+    fn from(e: AddressologyError) -> Self {
+        DeployError::Failed(e)
+    }
+}
+
+impl From<DeployError> for AddressologyError {
+    fn from(e: DeployError) -> Self {
+        match e {
+            DeployError::InvalidInput(msg) => err_custom_create!("{}", msg),
+            DeployError::Rpc(e) | DeployError::Failed(e) => e,
+        }
+    }
+}
+
+/// Checks that the contract can be deployed to its fancy address and builds the transaction data
+pub async fn prepare_deploy(
+    conn: &PgPool,
+    contract: &ContractDbObj,
+) -> Result<PreparedDeploy, DeployError> {
+    let address = contract
+        .address
+        .clone()
+        .ok_or_else(|| DeployError::InvalidInput("Contract has no address assigned".to_string()))?;
 
     let address = DbAddress::from_str(&address)
-        .map_err(|e| err_custom_create!("Failed to parse address: {}", e))?;
+        .map_err(|e| DeployError::InvalidInput(format!("Failed to parse address: {}", e)))?;
 
     let fancy = fancy_get_by_address(conn, address)
         .await
-        .map_err(|_| err_custom_create!("Failed to get fancy address"))?
-        .ok_or_else(|| err_custom_create!("Fancy address not found"))?;
+        .map_err(|e| err_custom_create!("Failed to get fancy address: {}", e))?
+        .ok_or_else(|| DeployError::InvalidInput("Fancy address not found".to_string()))?;
+    if fancy.owner_id != Some(contract.user_id) {
+        return Err(DeployError::InvalidInput(format!(
+            "Address {} is not owned by contract user",
+            fancy.address
+        )));
+    }
 
-    let deploy_data = serde_json::from_str::<DeployData>(&contract.data)
-        .map_err(|e| err_custom_create!("Failed to parse deploy data: {}", e))?;
+    let factory_address = fancy.factory.ok_or_else(|| {
+        err_custom_create!("Factory not found on fancy address, it has to be there!")
+    })?;
+    let factory = get_factory_by_address(conn, factory_address)
+        .await
+        .map_err(|e| err_custom_create!("Failed to get factory: {}", e))?
+        .ok_or_else(|| {
+            DeployError::InvalidInput(format!("Factory {} not registered", factory_address))
+        })?;
+    if factory.create2_deployer != fancy.init_code_hash.is_some() {
+        return Err(DeployError::InvalidInput(format!(
+            "Address {} does not match kind of factory {}",
+            fancy.address, factory_address
+        )));
+    }
 
-    let command = format!(
-        "npx hardhat run deploy3Universal.ts --network {}",
-        contract.network
-    );
-    let command = if cfg!(windows) {
-        format!("cmd /C {}", command)
-    } else {
-        command.to_string()
-    };
-    let current_dir = if cfg!(windows) {
-        "C:/vglm/pretzel/locker"
-    } else {
-        "/addressology/pretzel/locker"
-    };
+    let (web3, network) = connect_network(conn, &contract.network)
+        .await
+        .map_err(DeployError::Rpc)?;
+    let network_factories = get_network_factories(conn, &network.name)
+        .await
+        .map_err(|e| err_custom_create!("Failed to get network factories: {}", e))?;
+    if !network_factories.contains(&factory_address) {
+        return Err(DeployError::InvalidInput(format!(
+            "Factory {} is not deployed on {}",
+            factory_address, network.name
+        )));
+    }
+    let key = parse_deployer_key(
+        &get_deployer_private_key(&network.deployer_key_env)
+            .ok_or_else(|| err_custom_create!("{} not set", network.deployer_key_env))?,
+    )?;
+    let deployer = SecretKeyRef::new(&key).address();
 
-    let args = if cfg!(windows) {
-        command.split_whitespace().collect::<Vec<&str>>()
+    // make sure factory kind still derives the address we sold
+    let factory_kind = if fancy.init_code_hash.is_none() {
+        let params = factory_create3_params(&factory)?;
+        let computed = compute_create3(
+            &format!("{:#x}", factory_address.addr()),
+            &fancy.salt,
+            &params,
+        )?;
+        if computed != format!("{:#x}", fancy.address.addr()) {
+            return Err(DeployError::InvalidInput(format!(
+                "Factory {} of kind {} computes {} instead of {}",
+                factory_address, factory.kind, computed, fancy.address
+            )));
+        }
+        // guarded salts are bound to the sender, so check what our deployer would get
+        if params.salt_guard != SaltGuard::None {
+            let params = Create3Params {
+                guard_sender: Some(format!("{:#x}", deployer)),
+                ..params
+            };
+            if compute_create3(
+                &format!("{:#x}", factory_address.addr()),
+                &fancy.salt,
+                &params,
+            )? != computed
+            {
+                return Err(DeployError::InvalidInput(format!(
+                    "Salt of {} is guarded, deployer {:#x} cannot deploy it",
+                    fancy.address, deployer
+                )));
+            }
+        }
+        Some(Create3FactoryKind::from_str(&factory.kind).map_err(|e| err_custom_create!("{}", e))?)
     } else {
-        vec!["/bin/bash", "-c", &command]
+        None
     };
 
-    let bytes =
+    let deploy_data = serde_json::from_str::<DeployData>(&contract.data)
+        .map_err(|e| DeployError::InvalidInput(format!("Failed to parse deploy data: {}", e)))?;
+
+    let args =
         hex::decode(deploy_data.constructor_args.replace("0x", "").trim_ascii()).map_err(|e| {
-            err_custom_create!(
+            DeployError::InvalidInput(format!(
                 "Failed to decode constructor args: {}. Args provided: {}",
-                e,
-                deploy_data.constructor_args
-            )
+                e, deploy_data.constructor_args
+            ))
         })?;
+    let bytecode = hex::decode(
+        deploy_data
+            .contract
+            .evm
+            .bytecode
+            .object
+            .trim_start_matches("0x"),
+    )
+    .map_err(|e| DeployError::InvalidInput(format!("Failed to decode bytecode: {}", e)))?;
+    let init_code = [bytecode, args].concat();
 
-    let total_bytes =
-        "0x".to_string() + &deploy_data.contract.evm.bytecode.object + &hex::encode(bytes);
-
-    let env_vars = vec![
-        ("ADDRESS", format!("{:#x}", fancy.address.addr())),
-        (
-            "FACTORY",
-            format!(
-                "{:#x}",
-                fancy
-                    .factory
-                    .ok_or_else(|| err_custom_create!(
-                        "Factory not found on fancy address, it has to be there!"
-                    ))?
-                    .addr()
-            ),
-        ),
-        ("SALT", fancy.salt.clone()),
-        ("BYTECODE", total_bytes.clone()),
-    ];
+    // CREATE2 addresses are bound to the init code, CREATE3 ones only to the salt
+    if let Some(init_code_hash) = &fancy.init_code_hash {
+        let bytecode_hash = format!("0x{}", hex::encode(keccak256(&init_code)));
+        if bytecode_hash != init_code_hash.to_lowercase() {
+            return Err(DeployError::InvalidInput(format!(
+                "Contract init code hash {} does not match CREATE2 address init code hash {}",
+                bytecode_hash, init_code_hash
+            )));
+        }
+    }
 
-    log::info!("{:?}", env_vars);
+    let salt: [u8; 32] = hex::decode(fancy.salt.trim_start_matches("0x"))
+        .map_err(|e| err_custom_create!("Failed to decode salt: {}", e))?
+        .try_into()
+        .map_err(|_| err_custom_create!("Salt has to be 32 bytes"))?;
+    let data = build_deploy_call(factory_kind, salt, &init_code);
 
-    {
-        let mut new_contract = contract.clone();
-        new_contract.deploy_sent = Some(chrono::Utc::now().naive_utc());
-        new_contract.deploy_status = crate::db::model::DeployStatus::TxSent;
-        update_contract_data(conn, new_contract)
-            .await
-            .map_err(|e| err_custom_create!("Failed to update contract: {}", e))?;
+    let existing_code = web3
+        .eth()
+        .code(fancy.address.addr(), None)
+        .await
+        .map_err(|e| DeployError::Rpc(err_custom_create!("Failed to get code: {}", e)))?;
+    // CREATE2 address is bound to our init code, CREATE3 code is compared with the runtime code
+    let already_deployed = !existing_code.0.is_empty();
+    if already_deployed {
+        let expected_code = deploy_data
+            .contract
+            .evm
+            .deployed_bytecode
+            .as_ref()
+            .and_then(|code| hex::decode(code.object.trim_start_matches("0x")).ok());
+        let is_expected = fancy.init_code_hash.is_some()
+            || expected_code.is_some_and(|code| code == existing_code.0);
+        if !is_expected {
+            return Err(DeployError::InvalidInput(format!(
+                "Address {} already has code on {}",
+                fancy.address, contract.network
+            )));
+        }
     }
 
-    log::info!("Running command: {:#?}", args);
-    let cmd = tokio::process::Command::new(args[0])
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .envs(env_vars)
-        .current_dir(current_dir)
-        .args(&args[1..])
-        .spawn()
-        .map_err(|e| err_custom_create!("Failed to spawn command: {}", e))?;
+    Ok(PreparedDeploy {
+        web3,
+        network,
+        key,
+        to: factory_address.addr(),
+        data,
+        address: fancy.address.addr(),
+        already_deployed,
+    })
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeployEstimate {
+    pub network: String,
+    pub chain_id: i64,
+    pub gas_limit: u64,
+    /// In wei
+    pub max_fee_per_gas: String,
+    /// Upper bound of the fee in wei, the actual one is usually lower
+    pub max_fee: String,
+    pub max_fee_native: f64,
+    /// Fee in tokens, when token price of native currency is configured
+    pub token_price: Option<i64>,
+}
 
-    let output = cmd
-        .wait_with_output()
+pub async fn estimate_deploy(
+    conn: &PgPool,
+    contract: &ContractDbObj,
+) -> Result<DeployEstimate, DeployError> {
+    let prepared = prepare_deploy(conn, contract).await?;
+    if prepared.already_deployed {
+        return Err(DeployError::InvalidInput(format!(
+            "Contract is already deployed at {:#x}",
+            prepared.address
+        )));
+    }
+    let from = SecretKeyRef::new(&prepared.key).address();
+    let gas = estimate_deploy_gas(
+        &prepared.web3,
+        &prepared.network,
+        from,
+        prepared.to,
+        &prepared.data,
+    )
+    .await
+    .map_err(DeployError::Rpc)?;
+    let fees = fetch_fee_params(&prepared.web3, &prepared.network)
         .await
-        .map_err(|e| err_custom_create!("Failed to wait for command: {}", e))?;
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    log::info!("Command output: {}", output_str);
-    if output.status.success() {
-        let mut new_contract = contract.clone();
-        new_contract.deployed = Some(chrono::Utc::now().naive_utc());
-        new_contract.deploy_status = crate::db::model::DeployStatus::Succeeded;
-        update_contract_data(conn, new_contract)
-            .await
-            .map_err(|e| err_custom_create!("Failed to update contract: {}", e))?;
+        .map_err(DeployError::Rpc)?;
+    let max_fee = gas * fees.max_fee_per_gas;
+    let max_fee_native = wei_to_native(max_fee);
+    Ok(DeployEstimate {
+        network: prepared.network.name,
+        chain_id: prepared.network.chain_id,
+        gas_limit: gas.as_u64(),
+        max_fee_per_gas: fees.max_fee_per_gas.to_string(),
+        max_fee: max_fee.to_string(),
+        max_fee_native,
+        token_price: get_deploy_tokens_per_native()
+            .map(|rate| (max_fee_native * rate).ceil() as i64),
+    })
+}
 
-        Ok(())
-    } else {
-        log::error!(
-            "Command failed: {}",
-            String::from_utf8_lossy(&output.stderr)
+async fn deploy_contract(
+    conn: &PgPool,
+    contract: &mut ContractDbObj,
+) -> Result<DeployProgress, AddressologyError> {
+    let prepared = prepare_deploy(conn, contract).await?;
+    if prepared.already_deployed {
+        log::info!(
+            "Contract {} is already deployed at {:#x} on {}",
+            contract.contract_id,
+            prepared.address,
+            contract.network
         );
-        Err(err_custom_create!(
-            "Command failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ))
+        finish_deploy(conn, contract).await?;
+        return Ok(DeployProgress::Succeeded);
     }
+    let deployer_lock = lock_deployer(SecretKeyRef::new(&prepared.key).address()).await;
+    let signed = sign_deploy_tx(
+        &prepared.web3,
+        &prepared.network,
+        &prepared.key,
+        prepared.to,
+        prepared.data,
+    )
+    .await?;
+    log::info!(
+        "Deploying {:#x} on {} from {:#x}, tx: {:#x}",
+        prepared.address,
+        contract.network,
+        SecretKeyRef::new(&prepared.key).address(),
+        signed.transaction_hash
+    );
+
+    // tx hash is stored before broadcast, so the transaction can always be tracked down
+    contract.tx = Some(format!("{:#x}", signed.transaction_hash));
+    contract.deploy_sent = Some(chrono::Utc::now().naive_utc());
+    save_deploy_status(conn, contract, DeployStatus::TxSent).await?;
 
-    //run command
+    let tx_hash = broadcast(&prepared.web3, &signed).await?;
+    drop(deployer_lock);
+    track_deploy_tx(conn, &prepared.web3, contract, tx_hash, prepared.address).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::compute_create2;
+
+    #[test]
+    fn test_decode_revert_reason() {
+        let error = [
+            &keccak256(b"Error(string)")[..4],
+            web3::ethabi::encode(&[Token::String("Ownable: caller is not the owner".into())])
+                .as_slice(),
+        ]
+        .concat();
+        assert_eq!(
+            decode_revert_reason(&error).as_deref(),
+            Some("Ownable: caller is not the owner")
+        );
+
+        let panic = [
+            &keccak256(b"Panic(uint256)")[..4],
+            web3::ethabi::encode(&[Token::Uint(0x11.into())]).as_slice(),
+        ]
+        .concat();
+        assert_eq!(decode_revert_reason(&panic).as_deref(), Some("Panic(0x11)"));
+
+        assert_eq!(
+            decode_revert_reason(&hex::decode("30cd7471").unwrap()).as_deref(),
+            Some("Custom error 0x30cd7471")
+        );
+        assert_eq!(decode_revert_reason(&[]), None);
+    }
+
+    #[test]
+    fn test_wei_to_native() {
+        assert_eq!(wei_to_native(U256::exp10(18)), 1.0);
+        assert_eq!(wei_to_native(U256::from(21000) * U256::exp10(9)), 0.000021);
+    }
+
+    #[test]
+    fn test_build_deploy_call() {
+        let salt = [0x11u8; 32];
+        let init_code = hex::decode("6080604052").unwrap();
+
+        let create2 = build_deploy_call(None, salt, &init_code);
+        assert_eq!(create2, [salt.as_slice(), init_code.as_slice()].concat());
+
+        for (kind, signature) in [
+            (Create3FactoryKind::ZeframLou, "deploy(bytes32,bytes)"),
+            (Create3FactoryKind::CreateX, "deployCreate3(bytes32,bytes)"),
+        ] {
+            let call = build_deploy_call(Some(kind), salt, &init_code);
+            assert_eq!(call[..4], keccak256(signature.as_bytes())[..4]);
+            let tokens = web3::ethabi::decode(
+                &[
+                    web3::ethabi::ParamType::FixedBytes(32),
+                    web3::ethabi::ParamType::Bytes,
+                ],
+                &call[4..],
+            )
+            .unwrap();
+            assert_eq!(tokens[0], Token::FixedBytes(salt.to_vec()));
+            assert_eq!(tokens[1], Token::Bytes(init_code.clone()));
+        }
+    }
+
+    /// Run with `anvil` in background and
+    /// `ANVIL_RPC_URL=http://127.0.0.1:8545 cargo test -- --ignored test_deploy_on_local_node`.
+    /// Uses the deterministic deployment proxy and first dev account that anvil provides.
+    #[actix_rt::test]
+    #[ignore = "requires local node, see ANVIL_RPC_URL"]
+    async fn test_deploy_on_local_node() {
+        let rpc_url = std::env::var("ANVIL_RPC_URL").unwrap();
+        let web3 = Web3::new(Http::new(&rpc_url).unwrap());
+        let key = parse_deployer_key(
+            "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+        )
+        .unwrap();
+        let deployer = Address::from_str("0x4e59b44847b379578588920ca78fbf26c0b4956c").unwrap();
+
+        // init code returning single STOP byte as runtime code
+        let init_code = hex::decode("6001600c60003960016000f300").unwrap();
+        let salt: [u8; 32] = rand::random();
+        let expected = compute_create2(
+            &format!("{:#x}", deployer),
+            &format!("0x{}", hex::encode(salt)),
+            &format!("0x{}", hex::encode(keccak256(&init_code))),
+        )
+        .unwrap();
+        let expected = Address::from_str(&expected).unwrap();
+
+        let network = NetworkDbObj {
+            name: "anvil".to_string(),
+            chain_id: 31337,
+            rpc_urls: vec![rpc_url.clone()],
+            explorer_url: None,
+            gas_strategy: GasStrategy::Eip1559.to_string(),
+            gas_multiplier: 1.2,
+            deployer_key_env: "DEPLOYER_PRIVATE_KEY".to_string(),
+            explorer_api_url: None,
+            explorer_key_env: "ETHERSCAN_API_KEY".to_string(),
+            enabled: true,
+            added: chrono::Utc::now().naive_utc(),
+        };
+        let data = build_deploy_call(None, salt, &init_code);
+        let signed = sign_deploy_tx(&web3, &network, &key, deployer, data)
+            .await
+            .unwrap();
+        let tx_hash = broadcast(&web3, &signed).await.unwrap();
+        let receipt = wait_for_receipt(&web3, tx_hash, Duration::from_secs(30))
+            .await
+            .unwrap()
+            .unwrap();
+        verify_deployed_code(&web3, expected).await.unwrap();
+        assert_eq!(
+            wait_for_confirmations(&web3, tx_hash, 0, Duration::from_secs(30))
+                .await
+                .unwrap(),
+            receipt.block_number
+        );
+        let tx = web3
+            .eth()
+            .transaction(web3::types::TransactionId::Hash(signed.transaction_hash))
+            .await
+            .unwrap();
+        assert!(tx.is_some());
+    }
 }
@@ -0,0 +1,279 @@
+//! Long-running deployment worker.
+//! Contracts are claimed with a lease, so any number of workers can share the database.
+
+use crate::config::get_deploy_claim_timeout;
+use crate::db::model::{ContractDbObj, DeployStatus};
+use crate::db::ops::{
+    contract_claim_deploy, contract_release_deploy_claim, get_contract_deploy_networks,
+    get_networks,
+};
+use crate::db::utils::get_current_utc_time;
+use crate::deploy::{handle_fancy_deploy, handle_fancy_deploy_resume, DeployProgress};
+use crate::err_custom_create;
+use crate::error::AddressologyError;
+use crate::verify::process_due_verifications;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::{Id, JoinError, JoinSet};
+
+#[derive(Debug, Clone)]
+pub struct DeployWorkerOptions {
+    /// Restricts worker to given networks, all enabled networks otherwise
+    pub networks: Vec<String>,
+    /// Deployments running at once on a single network. Transactions from one deployer key are
+    /// still sent one by one, run a single worker process per deployer key.
+    pub concurrency: usize,
+    pub poll_interval: Duration,
+    pub max_backoff: Duration,
+    /// Exit when there is nothing left to claim
+    pub once: bool,
+}
+
+/// Per network bookkeeping
+#[derive(Debug, Default)]
+struct NetworkState {
+    running: usize,
+    failures: u32,
+    paused_until: Option<Instant>,
+}
+
+/// Delay after consecutive failures, doubles from poll interval up to max backoff
+pub fn backoff_delay(failures: u32, poll_interval: Duration, max_backoff: Duration) -> Duration {
+    if failures == 0 {
+        return Duration::ZERO;
+    }
+    poll_interval
+        .saturating_mul(2u32.saturating_pow(failures - 1))
+        .min(max_backoff)
+}
+
+async fn process_claimed(conn: &PgPool, contract: ContractDbObj) -> Result<(), AddressologyError> {
+    let contract_id = contract.contract_id;
+    let progress = match contract.deploy_status {
+        DeployStatus::Requested => handle_fancy_deploy(conn, contract).await,
+        DeployStatus::TxSent | DeployStatus::Mined => {
+            handle_fancy_deploy_resume(conn, contract).await
+        }
+        status => Err(err_custom_create!(
+            "Claimed contract {} in unexpected state {:?}",
+            contract_id,
+            status
+        )),
+    };
+    // pending contracts are released as well, so the next round resumes them
+    if let Err(e) = contract_release_deploy_claim(conn, contract_id).await {
+        log::error!("Failed to release contract {}: {}", contract_id, e);
+    }
+    if progress? == DeployProgress::Pending {
+        log::info!("Contract {} is still pending, released", contract_id);
+    }
+    Ok(())
+}
+
+pub async fn run_deploy_worker(
+    conn: PgPool,
+    options: DeployWorkerOptions,
+) -> Result<(), AddressologyError> {
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let stop = stop.clone();
+        actix_rt::spawn(async move {
+            if actix_rt::signal::ctrl_c().await.is_ok() {
+                log::info!("Stopping deploy worker, waiting for running deployments...");
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+    }
+
+    let mut states: HashMap<String, NetworkState> = HashMap::new();
+    let mut tasks: JoinSet<Result<(), AddressologyError>> = JoinSet::new();
+    // network of every running task, so a panicked task still frees its slot
+    let mut task_networks: HashMap<Id, String> = HashMap::new();
+    let mut verification: Option<tokio::task::JoinHandle<()>> = None;
+    log::info!("Deploy worker started: {:?}", options);
+
+    while !stop.load(Ordering::Relaxed) {
+        let mut claimed_any = false;
+        let networks = match get_contract_deploy_networks(&conn).await {
+            Ok(networks) => networks,
+            Err(e) => {
+                log::error!("Failed to get networks to deploy: {}", e);
+                Vec::new()
+            }
+        };
+        let supported = match get_networks(&conn, true).await {
+            Ok(supported) => supported
+                .into_iter()
+                .map(|n| n.name)
+                .collect::<HashSet<_>>(),
+            Err(e) => {
+                log::error!("Failed to get network registry: {}", e);
+                HashSet::new()
+            }
+        };
+        for network in networks {
+            if !options.networks.is_empty() && !options.networks.contains(&network) {
+                continue;
+            }
+            if !supported.contains(&network) {
+                log::debug!(
+                    "Skipping {}, network is not registered or disabled",
+                    network
+                );
+                continue;
+            }
+            let state = states.entry(network.clone()).or_default();
+            if state
+                .paused_until
+                .is_some_and(|until| until > Instant::now())
+            {
+                continue;
+            }
+            while state.running < options.concurrency.max(1) {
+                let now = get_current_utc_time();
+                let claimed_until = now + chrono::Duration::seconds(get_deploy_claim_timeout());
+                let contract =
+                    match contract_claim_deploy(&conn, &network, now, claimed_until).await {
+                        Ok(Some(contract)) => contract,
+                        Ok(None) => break,
+                        Err(e) => {
+                            log::error!("Failed to claim contract on {}: {}", network, e);
+                            break;
+                        }
+                    };
+                log::info!(
+                    "Claimed contract {} on {} in state {:?}",
+                    contract.contract_id,
+                    network,
+                    contract.deploy_status
+                );
+                claimed_any = true;
+                state.running += 1;
+                let conn = conn.clone();
+                let task = tasks.spawn_local(async move { process_claimed(&conn, contract).await });
+                task_networks.insert(task.id(), network.clone());
+            }
+        }
+
+        // explorer requests run aside, so slow explorers do not hold deployments
+        if verification.as_ref().is_none_or(|task| task.is_finished()) {
+            let conn = conn.clone();
+            let networks = options.networks.clone();
+            verification = Some(tokio::task::spawn_local(async move {
+                process_due_verifications(&conn, &networks).await
+            }));
+        }
+
+        if options.once && !claimed_any && tasks.is_empty() {
+            break;
+        }
+
+        // wake up on finished deployment or next poll
+        if tasks.is_empty() {
+            tokio::time::sleep(options.poll_interval).await;
+        } else if let Ok(Some(joined)) =
+            tokio::time::timeout(options.poll_interval, tasks.join_next_with_id()).await
+        {
+            finish_task(&mut states, &mut task_networks, joined, &options);
+        }
+    }
+
+    while let Some(joined) = tasks.join_next_with_id().await {
+        finish_task(&mut states, &mut task_networks, joined, &options);
+    }
+    if let Some(verification) = verification {
+        if let Err(e) = verification.await {
+            log::error!("Verification task panicked: {}", e);
+        }
+    }
+    log::info!("Deploy worker stopped");
+    Ok(())
+}
+
+fn finish_task(
+    states: &mut HashMap<String, NetworkState>,
+    task_networks: &mut HashMap<Id, String>,
+    joined: Result<(Id, Result<(), AddressologyError>), JoinError>,
+    options: &DeployWorkerOptions,
+) {
+    let (id, res) = match joined {
+        Ok(joined) => joined,
+        Err(e) => (
+            e.id(),
+            // claim expires, so the contract is picked up again later
+            Err(err_custom_create!("Deployment task panicked: {}", e)),
+        ),
+    };
+    let Some(network) = task_networks.remove(&id) else {
+        log::error!("Finished deployment task {} is not tracked", id);
+        return;
+    };
+    let state = states.entry(network.clone()).or_default();
+    state.running = state.running.saturating_sub(1);
+    match res {
+        Ok(()) => {
+            log::info!("Deployment on {} finished", network);
+            state.failures = 0;
+            state.paused_until = None;
+        }
+        Err(e) => {
+            state.failures += 1;
+            let delay = backoff_delay(state.failures, options.poll_interval, options.max_backoff);
+            log::warn!(
+                "Deployment on {} failed ({} in a row), pausing network for {}s: {}",
+                network,
+                state.failures,
+                delay.as_secs(),
+                e
+            );
+            state.paused_until = Some(Instant::now() + delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let poll = Duration::from_secs(10);
+        let max = Duration::from_secs(600);
+        assert_eq!(backoff_delay(0, poll, max), Duration::ZERO);
+        assert_eq!(backoff_delay(1, poll, max), Duration::from_secs(10));
+        assert_eq!(backoff_delay(3, poll, max), Duration::from_secs(40));
+        assert_eq!(backoff_delay(7, poll, max), max);
+        assert_eq!(backoff_delay(100, poll, max), max);
+    }
+
+    #[actix_rt::test]
+    async fn test_panicked_task_frees_slot() {
+        let options = DeployWorkerOptions {
+            networks: Vec::new(),
+            concurrency: 1,
+            poll_interval: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(600),
+            once: true,
+        };
+        let mut states = HashMap::from([(
+            "holesky".to_string(),
+            NetworkState {
+                running: 1,
+                ..Default::default()
+            },
+        )]);
+        let mut tasks: JoinSet<Result<(), AddressologyError>> = JoinSet::new();
+        let task = tasks.spawn(async { panic!("deployment panicked") });
+        let mut task_networks = HashMap::from([(task.id(), "holesky".to_string())]);
+
+        let joined = tasks.join_next_with_id().await.unwrap();
+        assert!(joined.is_err());
+        finish_task(&mut states, &mut task_networks, joined, &options);
+        assert_eq!(states["holesky"].running, 0);
+        assert_eq!(states["holesky"].failures, 1);
+        assert!(task_networks.is_empty());
+    }
+}
@@ -1,16 +1,20 @@
 use crate::config::get_base_difficulty_price;
-use crate::db::model::FancyDbObj;
+use crate::db::model::{ContractFactoryDbObject, FancyDbObj, FancyScore};
 use crate::err_custom_create;
 use crate::error::AddressologyError;
 use crate::fancy::score_fancy;
-use crate::hash::{compute_address_command, compute_create3_command};
+use crate::hash::{
+    compute_address_command, compute_create2, compute_create3, Create3Params, SaltGuard,
+};
 use crate::types::DbAddress;
+use std::str::FromStr;
 use web3::types::Address;
 
+/// Parsers return the score as well, so it is not computed again for the breakdown
 pub fn parse_fancy_private(
     public_key_base: String,
     private_key_add: String,
-) -> Result<FancyDbObj, AddressologyError> {
+) -> Result<(FancyDbObj, FancyScore), AddressologyError> {
     /*let censor = censor::Standard + censor::Zealous + censor::Sex;
 
     //get rid of any weird characters from miner string
@@ -30,7 +34,7 @@ pub fn parse_fancy_private(
 
     let score = score_fancy(address.addr());
 
-    Ok(FancyDbObj {
+    let fancy = FancyDbObj {
         address,
         salt: private_key_add,
         factory: None,
@@ -38,13 +42,37 @@ pub fn parse_fancy_private(
         score: score.total_score,
         owner_id: None,
         price: (score.price_multiplier * get_base_difficulty_price() as f64) as i64,
-        category: score.category,
+        category: score.category.clone(),
         job_id: None,
         public_key_base: Some(public_key_base),
+        init_code_hash: None,
+    };
+    Ok((fancy, score))
+}
+
+/// How addresses are derived from salts for given factory
+pub fn factory_create3_params(
+    factory: &ContractFactoryDbObject,
+) -> Result<Create3Params, AddressologyError> {
+    if factory.create2_deployer {
+        return Err(err_custom_create!(
+            "Factory {} is registered as CREATE2 deployer",
+            factory.address
+        ));
+    }
+    Ok(Create3Params {
+        proxy_init_code_hash: factory.proxy_init_code_hash.clone(),
+        salt_guard: SaltGuard::from_str(&factory.salt_guard)
+            .map_err(|e| err_custom_create!("Factory {}: {}", factory.address, e))?,
+        guard_sender: factory.guard_sender.map(|s| format!("{:#x}", s.addr())),
     })
 }
 
-pub fn parse_fancy(salt: String, factory: Address) -> Result<FancyDbObj, AddressologyError> {
+pub fn parse_fancy_create3(
+    salt: String,
+    factory: Address,
+    params: &Create3Params,
+) -> Result<(FancyDbObj, FancyScore), AddressologyError> {
     /*let censor = censor::Standard + censor::Zealous + censor::Sex;
 
     //get rid of any weird characters from miner string
@@ -57,14 +85,14 @@ pub fn parse_fancy(salt: String, factory: Address) -> Result<FancyDbObj, Address
             censor.censor(&provider_name_filtered)
         });
     */
-    let address = compute_create3_command(&format!("{:#x}", factory), &salt)?;
+    let address = compute_create3(&format!("{:#x}", factory), &salt, params)?;
 
     let address =
         DbAddress::from_str(&address).map_err(|_| err_custom_create!("Failed to parse address"))?;
 
     let score = score_fancy(address.addr());
 
-    Ok(FancyDbObj {
+    let fancy = FancyDbObj {
         address,
         salt,
         factory: Some(DbAddress::wrap(factory)),
@@ -72,10 +100,41 @@ pub fn parse_fancy(salt: String, factory: Address) -> Result<FancyDbObj, Address
         score: score.total_score,
         owner_id: None,
         price: (score.price_multiplier * get_base_difficulty_price() as f64) as i64,
-        category: score.category,
+        category: score.category.clone(),
         job_id: None,
         public_key_base: None,
-    })
+        init_code_hash: None,
+    };
+    Ok((fancy, score))
+}
+
+pub fn parse_fancy_create2(
+    salt: String,
+    deployer: Address,
+    init_code_hash: String,
+) -> Result<(FancyDbObj, FancyScore), AddressologyError> {
+    let init_code_hash = "0x".to_string() + &init_code_hash.trim_start_matches("0x").to_lowercase();
+    let address = compute_create2(&format!("{:#x}", deployer), &salt, &init_code_hash)?;
+
+    let address =
+        DbAddress::from_str(&address).map_err(|_| err_custom_create!("Failed to parse address"))?;
+
+    let score = score_fancy(address.addr());
+
+    let fancy = FancyDbObj {
+        address,
+        salt,
+        factory: Some(DbAddress::wrap(deployer)),
+        created: chrono::Utc::now().naive_utc(),
+        score: score.total_score,
+        owner_id: None,
+        price: (score.price_multiplier * get_base_difficulty_price() as f64) as i64,
+        category: score.category.clone(),
+        job_id: None,
+        public_key_base: None,
+        init_code_hash: Some(init_code_hash),
+    };
+    Ok((fancy, score))
 }
 
 //test fancy
@@ -83,7 +142,6 @@ pub fn parse_fancy(salt: String, factory: Address) -> Result<FancyDbObj, Address
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::str::FromStr;
 
     #[test]
     fn test_parse_fancy() {
@@ -91,10 +149,11 @@ mod tests {
         let factory = Address::from_str("0x9E3F8eaE49E442A323EF2094f277Bf62752E6995").unwrap();
         //let miner = "shitty-miner v1.0.2";
 
-        let result = parse_fancy(salt.to_string(), factory);
+        let result = parse_fancy_create3(salt.to_string(), factory, &Create3Params::default());
         assert!(result.is_ok());
 
-        let parsed = result.unwrap();
+        let (parsed, score) = result.unwrap();
+        assert_eq!(parsed.score, score.total_score);
         //assert_eq!(parsed.miner, "****ty-miner v1.0.2");
 
         assert_eq!(
@@ -2,11 +2,17 @@ use web3::signing::keccak256;
 use web3::types::H160;
 #[allow(clippy::module_inception)]
 mod fancy;
+mod order;
+mod probability;
+mod rules;
 mod score;
 pub use fancy::*;
+pub use order::*;
+pub use probability::*;
+pub use rules::*;
 pub use score::*;
 
-fn address_to_mixed_case(address: &H160) -> String {
+pub fn address_to_mixed_case(address: &H160) -> String {
     let address_str = format!("{:x}", address);
     let hash = keccak256(address_str.as_bytes());
     let mut result = "0x".to_string();
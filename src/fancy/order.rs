@@ -0,0 +1,198 @@
+use crate::config::{get_base_difficulty, get_base_difficulty_price};
+use crate::err_custom_create;
+use crate::error::AddressologyError;
+use crate::fancy::{address_to_mixed_case, AddressForm, SimplePattern};
+use rand::Rng;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+use web3::types::Address;
+
+const MAX_PATTERN_LENGTH: usize = 100;
+// regexes that cannot be priced analytically are estimated by sampling random addresses
+const REGEX_ESTIMATE_SAMPLES: usize = 200_000;
+// expected number of hits needed for the sampled estimate to be trusted
+const REGEX_ESTIMATE_MIN_HITS: usize = 20;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderKind {
+    /// Case-insensitive hex prefix
+    Prefix,
+    /// Case-insensitive hex suffix
+    Suffix,
+    /// Checksum case-sensitive pattern built from hex characters, `.`, `{n}` and `^`/`$`
+    Pattern,
+    /// Any regex over the mixed-case address (without 0x)
+    Regex,
+}
+
+impl FromStr for OrderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prefix" => Ok(OrderKind::Prefix),
+            "suffix" => Ok(OrderKind::Suffix),
+            "pattern" => Ok(OrderKind::Pattern),
+            "regex" => Ok(OrderKind::Regex),
+            _ => Err(format!("Invalid order kind: {}", s)),
+        }
+    }
+}
+
+impl Display for OrderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderKind::Prefix => write!(f, "prefix"),
+            OrderKind::Suffix => write!(f, "suffix"),
+            OrderKind::Pattern => write!(f, "pattern"),
+            OrderKind::Regex => write!(f, "regex"),
+        }
+    }
+}
+
+/// Compiled order pattern, used both for pricing and for matching ingested addresses
+#[derive(Debug, Clone)]
+pub struct OrderMatcher {
+    pub kind: OrderKind,
+    pub pattern: String,
+    regex: Regex,
+    form: AddressForm,
+}
+
+impl OrderMatcher {
+    pub fn new(kind: OrderKind, pattern: &str) -> Result<OrderMatcher, AddressologyError> {
+        let pattern = pattern.trim();
+        if pattern.is_empty() || pattern.len() > MAX_PATTERN_LENGTH {
+            return Err(err_custom_create!(
+                "Pattern length has to be between 1 and {}",
+                MAX_PATTERN_LENGTH
+            ));
+        }
+        let (pattern, regex, form) = match kind {
+            OrderKind::Prefix | OrderKind::Suffix => {
+                let pattern = pattern.trim_start_matches("0x").to_lowercase();
+                if pattern.is_empty()
+                    || pattern.len() > 40
+                    || !pattern.chars().all(|c| c.is_ascii_hexdigit())
+                {
+                    return Err(err_custom_create!(
+                        "{} has to be between 1 and 40 hex characters",
+                        kind
+                    ));
+                }
+                let regex = if kind == OrderKind::Prefix {
+                    format!("^{}", pattern)
+                } else {
+                    format!("{}$", pattern)
+                };
+                (pattern, regex, AddressForm::Lower)
+            }
+            OrderKind::Pattern => {
+                if SimplePattern::parse(pattern).is_none() {
+                    return Err(err_custom_create!(
+                        "Pattern can contain only hex characters, '.', {{n}} and ^/$ anchors"
+                    ));
+                }
+                (pattern.to_string(), pattern.to_string(), AddressForm::Mixed)
+            }
+            OrderKind::Regex => (pattern.to_string(), pattern.to_string(), AddressForm::Mixed),
+        };
+        let regex = RegexBuilder::new(&regex)
+            .size_limit(1 << 20)
+            .build()
+            .map_err(|e| err_custom_create!("Invalid regex: {}", e))?;
+        Ok(OrderMatcher {
+            kind,
+            pattern,
+            regex,
+            form,
+        })
+    }
+
+    fn address_form(&self, address: Address) -> String {
+        match self.form {
+            AddressForm::Lower => format!("{:x}", address),
+            AddressForm::Mixed | AddressForm::Short => address_to_mixed_case(&address)
+                .trim_start_matches("0x")
+                .to_string(),
+        }
+    }
+
+    pub fn matches(&self, address: Address) -> bool {
+        self.regex.is_match(&self.address_form(address))
+    }
+
+    /// Expected number of hashes needed to find a matching address
+    pub fn difficulty(&self) -> Result<f64, AddressologyError> {
+        if let Some(simple) = SimplePattern::parse(self.regex.as_str()) {
+            let probability = simple.count_tail(self.form)[1];
+            if probability <= 0.0 {
+                return Err(err_custom_create!("Pattern can never match an address"));
+            }
+            return Ok(1.0 / probability);
+        }
+
+        let mut rng = rand::rng();
+        let mut hits = 0;
+        for _ in 0..REGEX_ESTIMATE_SAMPLES {
+            if self.matches(Address::from(rng.random::<[u8; 20]>())) {
+                hits += 1;
+            }
+        }
+        if hits < REGEX_ESTIMATE_MIN_HITS {
+            return Err(err_custom_create!(
+                "Regex is too rare to estimate its difficulty, use prefix, suffix or pattern order"
+            ));
+        }
+        Ok(REGEX_ESTIMATE_SAMPLES as f64 / hits as f64)
+    }
+}
+
+/// Price in tokens for an address of given difficulty, same scale as mined addresses
+pub fn order_price(difficulty: f64) -> i64 {
+    let price = (difficulty / get_base_difficulty() * get_base_difficulty_price() as f64) as i64;
+    price.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_matcher() {
+        let address = Address::from_str("0x99927777d11dDdFfFfF79b93bB00BBbB5fff5553").unwrap();
+
+        let prefix = OrderMatcher::new(OrderKind::Prefix, "0x999277").unwrap();
+        assert!(prefix.matches(address));
+        assert_eq!(prefix.difficulty().unwrap(), 16.0f64.powi(6));
+
+        let suffix = OrderMatcher::new(OrderKind::Suffix, "5553").unwrap();
+        assert!(suffix.matches(address));
+        assert!(!OrderMatcher::new(OrderKind::Suffix, "5554")
+            .unwrap()
+            .matches(address));
+
+        let pattern = OrderMatcher::new(OrderKind::Pattern, "dDdF").unwrap();
+        assert!(pattern.matches(address));
+        assert!(!OrderMatcher::new(OrderKind::Pattern, "DDDF")
+            .unwrap()
+            .matches(address));
+
+        let regex = OrderMatcher::new(OrderKind::Regex, "^[0-9]{3}").unwrap();
+        assert!(regex.matches(address));
+        // 10/16 ^ 3 chance for three leading digits
+        let difficulty = regex.difficulty().unwrap();
+        assert!(difficulty > 3.5 && difficulty < 4.7, "{}", difficulty);
+
+        assert!(OrderMatcher::new(OrderKind::Prefix, "xyz").is_err());
+        assert!(OrderMatcher::new(OrderKind::Pattern, "(0|1)").is_err());
+        assert!(OrderMatcher::new(OrderKind::Regex, "(").is_err());
+        assert!(OrderMatcher::new(OrderKind::Regex, "^[0-9]{30}")
+            .unwrap()
+            .difficulty()
+            .is_err());
+    }
+}
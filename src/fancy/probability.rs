@@ -0,0 +1,319 @@
+//! Analytical probabilities of address patterns.
+//!
+//! Address characters are modelled as independent and uniformly distributed hex digits.
+//! In the mixed (EIP-55) form the case of a letter is decided by a keccak bit, so every
+//! letter appears in lower and upper case with probability 1/32 each, while digits keep 1/16.
+//! The expected number of hashes needed to find an address is `1 / probability`.
+
+use crate::fancy::AddressForm;
+use std::collections::HashMap;
+
+/// Number of characters of the address in given form
+pub fn form_length(form: AddressForm) -> usize {
+    match form {
+        AddressForm::Lower | AddressForm::Mixed => 40,
+        AddressForm::Short => 17,
+    }
+}
+
+/// Probability that a single character of the address is equal to `c`
+pub fn char_probability(c: char, form: AddressForm) -> f64 {
+    match form {
+        AddressForm::Lower => {
+            if c.is_ascii_digit() || ('a'..='f').contains(&c) {
+                1.0 / 16.0
+            } else {
+                0.0
+            }
+        }
+        AddressForm::Mixed | AddressForm::Short => {
+            if c.is_ascii_digit() {
+                1.0 / 16.0
+            } else if c.is_ascii_hexdigit() {
+                1.0 / 32.0
+            } else {
+                0.0
+            }
+        }
+    }
+}
+
+/// Fixed length regex built only from hex characters, `.`, `{n}` repetitions and `^`/`$` anchors.
+/// Only such patterns can be priced analytically.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimplePattern {
+    pub anchored_start: bool,
+    pub anchored_end: bool,
+    /// None means any character
+    pub tokens: Vec<Option<char>>,
+}
+
+impl SimplePattern {
+    pub fn parse(regex: &str) -> Option<SimplePattern> {
+        let mut body = regex;
+        let anchored_start = body.starts_with('^');
+        if anchored_start {
+            body = &body[1..];
+        }
+        let anchored_end = body.ends_with('$');
+        if anchored_end {
+            body = &body[..body.len() - 1];
+        }
+
+        let mut tokens = Vec::new();
+        let mut chars = body.chars().peekable();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '.' => None,
+                c if c.is_ascii_hexdigit() => Some(c),
+                _ => return None,
+            };
+            let mut repeat = 1;
+            if chars.peek() == Some(&'{') {
+                chars.next();
+                let mut number = String::new();
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                    number.push(c);
+                }
+                repeat = number.parse::<usize>().ok()?;
+            }
+            for _ in 0..repeat {
+                tokens.push(token);
+            }
+        }
+        if tokens.is_empty() {
+            return None;
+        }
+        Some(SimplePattern {
+            anchored_start,
+            anchored_end,
+            tokens,
+        })
+    }
+
+    /// Returns `tail` where `tail[k]` is the probability of at least `k` non-overlapping
+    /// matches (counted the same way as `Regex::find_iter`) in a random address.
+    pub fn count_tail(&self, form: AddressForm) -> Vec<f64> {
+        self.count_tail_for_length(form, form_length(form))
+    }
+
+    pub fn count_tail_for_length(&self, form: AddressForm, len: usize) -> Vec<f64> {
+        let m = self.tokens.len();
+        if m > len {
+            return vec![1.0, 0.0];
+        }
+
+        // characters used by the pattern are tracked separately, all others only match `.`
+        let mut symbols: Vec<(Option<char>, f64)> = Vec::new();
+        for c in self.tokens.iter().flatten() {
+            if !symbols.iter().any(|(s, _)| *s == Some(*c)) {
+                symbols.push((Some(*c), char_probability(*c, form)));
+            }
+        }
+        let other_probability = 1.0 - symbols.iter().map(|(_, p)| p).sum::<f64>();
+        symbols.push((None, other_probability));
+
+        let max_count = len / m;
+        // state is a bitmask of matched prefix lengths still alive, value is count distribution
+        let mut states: HashMap<u64, Vec<f64>> = HashMap::new();
+        let mut initial = vec![0.0; max_count + 1];
+        initial[0] = 1.0;
+        states.insert(0, initial);
+
+        for i in 0..len {
+            let mut next_states: HashMap<u64, Vec<f64>> = HashMap::new();
+            for (mask, dist) in states.iter() {
+                for (symbol, p) in symbols.iter() {
+                    if *p == 0.0 {
+                        continue;
+                    }
+                    let mut new_mask = 0u64;
+                    let mut matched = false;
+                    let can_start = !self.anchored_start || i == 0;
+                    for j in 0..m {
+                        let alive = if j == 0 {
+                            can_start
+                        } else {
+                            mask & (1 << j) != 0
+                        };
+                        if !alive {
+                            continue;
+                        }
+                        let token_matches = match self.tokens[j] {
+                            None => true,
+                            Some(c) => *symbol == Some(c),
+                        };
+                        if token_matches {
+                            if j + 1 == m {
+                                matched = true;
+                            } else {
+                                new_mask |= 1 << (j + 1);
+                            }
+                        }
+                    }
+                    let counted = matched && (!self.anchored_end || i + 1 == len);
+                    let entry = next_states
+                        .entry(if counted { 0 } else { new_mask })
+                        .or_insert_with(|| vec![0.0; max_count + 1]);
+                    for (count, prob) in dist.iter().enumerate() {
+                        if *prob == 0.0 {
+                            continue;
+                        }
+                        let new_count = if counted { count + 1 } else { count };
+                        entry[new_count] += prob * p;
+                    }
+                }
+            }
+            states = next_states;
+        }
+
+        let mut dist = vec![0.0; max_count + 1];
+        for state_dist in states.values() {
+            for (count, prob) in state_dist.iter().enumerate() {
+                dist[count] += prob;
+            }
+        }
+        let mut tail = vec![0.0; max_count + 2];
+        for k in (0..=max_count).rev() {
+            tail[k] = tail[k + 1] + dist[k];
+        }
+        tail
+    }
+}
+
+/// Expected number of hashes needed to find `count` matches, based on `count_tail` result
+pub fn pattern_difficulty(tail: &[f64], count: usize) -> f64 {
+    if count == 0 {
+        return 1.0f64;
+    }
+    1.0 / tail[count.min(tail.len() - 1)]
+}
+
+/// Expected number of hashes needed to get a run of `run` identical leading symbols,
+/// when there are `symbols` possible symbols each with probability `symbol_probability`
+pub fn run_difficulty(run: f64, symbols: f64, symbol_probability: f64) -> f64 {
+    if run < 1.0 {
+        return 1.0f64;
+    }
+    1.0 / (symbols * symbol_probability.powf(run))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    const MIXED_ALPHABET: &str = "0123456789abcdefABCDEF";
+
+    // Enumerate every string of given length and sum probabilities of those matching `count`
+    fn brute_force_tail(regex: &str, len: usize, form: AddressForm) -> Vec<f64> {
+        let alphabet = match form {
+            AddressForm::Lower => "0123456789abcdef",
+            AddressForm::Mixed | AddressForm::Short => MIXED_ALPHABET,
+        }
+        .chars()
+        .collect::<Vec<char>>();
+        let re = Regex::new(regex).unwrap();
+        let mut dist = vec![0.0; len + 1];
+        let total = alphabet.len().pow(len as u32);
+        let mut s = String::with_capacity(len);
+        for i in 0..total {
+            s.clear();
+            let mut prob = 1.0;
+            let mut rest = i;
+            for _ in 0..len {
+                let c = alphabet[rest % alphabet.len()];
+                rest /= alphabet.len();
+                prob *= char_probability(c, form);
+                s.push(c);
+            }
+            dist[re.find_iter(&s).count()] += prob;
+        }
+        let mut tail = vec![0.0; len + 2];
+        for k in (0..=len).rev() {
+            tail[k] = tail[k + 1] + dist[k];
+        }
+        tail
+    }
+
+    #[test]
+    fn test_brute_force_patterns() {
+        let cases = [
+            ("0B", 4, AddressForm::Mixed),
+            ("00", 5, AddressForm::Mixed),
+            ("bB", 4, AddressForm::Mixed),
+            ("0.0", 5, AddressForm::Mixed),
+            ("^a.a", 4, AddressForm::Mixed),
+            ("aa$", 4, AddressForm::Mixed),
+            ("0{2}", 5, AddressForm::Lower),
+            ("a0a", 5, AddressForm::Lower),
+        ];
+        for (regex, len, form) in cases {
+            let pattern = SimplePattern::parse(regex).unwrap();
+            let math = pattern.count_tail_for_length(form, len);
+            let brute = brute_force_tail(regex, len, form);
+            for (k, expected) in math.iter().enumerate() {
+                assert!(
+                    (expected - brute[k]).abs() < 1e-12,
+                    "pattern {} count {}: {} vs {}",
+                    regex,
+                    k,
+                    expected,
+                    brute[k]
+                );
+            }
+            assert!((math[0] - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_brute_force_leading_letters() {
+        for len in 1..5 {
+            let alphabet = MIXED_ALPHABET.chars().collect::<Vec<char>>();
+            let mut runs = vec![0.0; len + 1];
+            for i in 0..alphabet.len().pow(len as u32) {
+                let mut rest = i;
+                let mut s = Vec::with_capacity(len);
+                let mut prob = 1.0;
+                for _ in 0..len {
+                    let c = alphabet[rest % alphabet.len()];
+                    rest /= alphabet.len();
+                    prob *= char_probability(c, AddressForm::Mixed);
+                    s.push(c);
+                }
+                let run = if s[0].is_alphabetic() {
+                    s.iter().take_while(|c| **c == s[0]).count()
+                } else {
+                    0
+                };
+                for r in runs.iter_mut().take(run + 1) {
+                    *r += prob;
+                }
+            }
+            for (run, prob) in runs.iter().enumerate().skip(1) {
+                let math = 1.0 / run_difficulty(run as f64, 12.0, 1.0 / 32.0);
+                assert!(
+                    (math - prob).abs() < 1e-12,
+                    "letters run {}/{}: {} vs {}",
+                    run,
+                    len,
+                    math,
+                    prob
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        let pattern = SimplePattern::parse("^00000.{3}00000").unwrap();
+        assert!(pattern.anchored_start);
+        assert_eq!(pattern.tokens.len(), 13);
+        assert_eq!(pattern.tokens[5], None);
+        assert!(SimplePattern::parse("0+").is_none());
+        assert!(SimplePattern::parse("(0BB5|BB50)").is_none());
+    }
+}
@@ -0,0 +1,561 @@
+use crate::config::get_score_rules_file;
+use crate::err_custom_create;
+use crate::error::AddressologyError;
+use crate::fancy::{
+    exactly_letters_combinations_difficulty, pattern_difficulty, run_difficulty, snake_difficulty,
+    total_combinations, SimplePattern,
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeSet;
+use web3::types::U256;
+
+/// Rule set shipped with the binary, it reproduces the original hard-coded scoring
+const DEFAULT_SCORE_RULES: &str = include_str!("default_score_rules.json");
+
+lazy_static! {
+    static ref SCORE_RULES: ScoreRuleSet =
+        ScoreRuleSet::load().unwrap_or_else(|e| panic!("Failed to load score rules: {}", e));
+}
+
+/// Returns rule set used for scoring, loaded from SCORE_RULES_FILE or the built-in default
+pub fn score_rules() -> &'static ScoreRuleSet {
+    &SCORE_RULES
+}
+
+/// Form of the address the rule is looking at (always without 0x prefix)
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AddressForm {
+    /// 40 lower case hex characters
+    Lower,
+    /// 40 characters with EIP-55 checksum casing
+    Mixed,
+    /// 17 characters visible in etherscan short form (first 8 and last 9, mixed case)
+    Short,
+}
+
+pub struct AddressForms {
+    pub lower: String,
+    pub mixed: String,
+    pub short: String,
+}
+
+impl AddressForms {
+    pub fn get(&self, form: AddressForm) -> &str {
+        match form {
+            AddressForm::Lower => &self.lower,
+            AddressForm::Mixed => &self.mixed,
+            AddressForm::Short => &self.short,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CharClass {
+    Letters,
+    Digits,
+}
+
+/// Regex compiled when the rule file is loaded
+#[derive(Debug, Clone)]
+pub struct RuleRegex(Regex);
+
+impl<'de> Deserialize<'de> for RuleRegex {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Regex::new(&s)
+            .map(RuleRegex)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Hex number written as string in the rule file, 0x prefix is optional
+#[derive(Debug, Clone, Copy)]
+pub struct HexNumber(U256);
+
+impl<'de> Deserialize<'de> for HexNumber {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        U256::from_str_radix(s.trim_start_matches("0x"), 16)
+            .map(HexNumber)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PatternRule {
+    pub regex: RuleRegex,
+    pub weight: f64,
+    /// Count every non-overlapping match instead of only checking if pattern is present
+    #[serde(default)]
+    pub count_all: bool,
+    /// Probability of at least k matches, filled on load when needed by patternRarity
+    #[serde(skip)]
+    pub count_tail: Vec<f64>,
+}
+
+impl PatternRule {
+    fn count(&self, s: &str) -> usize {
+        if self.count_all {
+            self.regex.0.find_iter(s).count()
+        } else {
+            self.regex.0.is_match(s) as usize
+        }
+    }
+}
+
+/// Measures some property of the address and returns it as a number
+#[derive(Deserialize, Debug, Clone)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Measure {
+    Constant {
+        value: f64,
+    },
+    /// Number of leading characters equal to `char` (or to the first character if not set)
+    LeadingRun {
+        source: AddressForm,
+        char: Option<char>,
+        #[serde(default)]
+        require_letter: bool,
+    },
+    /// Number of leading characters matching given prefix
+    Prefix {
+        source: AddressForm,
+        prefix: String,
+    },
+    /// Number of characters of given class anywhere in the address
+    CharCount {
+        source: AddressForm,
+        class: CharClass,
+    },
+    /// Number of characters equal to the previous one
+    Repetition {
+        source: AddressForm,
+    },
+    /// Number of letters continuing a run, the first character counts as continuing
+    RunLetters {
+        source: AddressForm,
+    },
+    /// Weighted sum of regex matches
+    Patterns {
+        source: AddressForm,
+        patterns: Vec<PatternRule>,
+    },
+}
+
+impl Measure {
+    pub fn evaluate(&self, forms: &AddressForms) -> f64 {
+        match self {
+            Measure::Constant { value } => *value,
+            Measure::LeadingRun {
+                source,
+                char,
+                require_letter,
+            } => {
+                let s = forms.get(*source);
+                let Some(first) = s.chars().next() else {
+                    return 0.0;
+                };
+                if *require_letter && !first.is_alphabetic() {
+                    return 0.0;
+                }
+                let run_char = char.unwrap_or(first);
+                s.chars().take_while(|c| *c == run_char).count() as f64
+            }
+            Measure::Prefix { source, prefix } => forms
+                .get(*source)
+                .chars()
+                .zip(prefix.trim_start_matches("0x").chars())
+                .take_while(|(a, b)| a == b)
+                .count() as f64,
+            Measure::CharCount { source, class } => forms
+                .get(*source)
+                .chars()
+                .filter(|c| match class {
+                    CharClass::Letters => c.is_alphabetic(),
+                    CharClass::Digits => c.is_numeric(),
+                })
+                .count() as f64,
+            Measure::Repetition { source } => {
+                let s = forms.get(*source);
+                s.chars()
+                    .zip(s.chars().skip(1))
+                    .filter(|(prev, c)| prev == c)
+                    .count() as f64
+            }
+            Measure::RunLetters { source } => {
+                let s = forms.get(*source);
+                let Some(mut prev_char) = s.chars().next() else {
+                    return 0.0;
+                };
+                let mut letters = 0;
+                for c in s.chars() {
+                    if c == prev_char {
+                        if c.is_alphabetic() {
+                            letters += 1;
+                        }
+                    } else {
+                        prev_char = c;
+                    }
+                }
+                letters as f64
+            }
+            Measure::Patterns { source, patterns } => {
+                let s = forms.get(*source);
+                let mut total = 0.0;
+                for pattern in patterns {
+                    total += pattern.count(s) as f64 * pattern.weight;
+                }
+                total
+            }
+        }
+    }
+}
+
+fn u256_to_float(u256: U256) -> f64 {
+    let u256_str = u256.to_string();
+    u256_str.parse::<f64>().unwrap()
+}
+
+/// Turns measured value into expected number of tries needed to find such address
+#[derive(Deserialize, Debug, Clone)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum DifficultyFormula {
+    Constant {
+        value: f64,
+    },
+    /// `range / (distance + distanceOffset) / divisor` where distance is the smallest
+    /// distance between the address (interpreted as number) and one of the targets.
+    /// Targets are hex patterns repeated to the length of the source.
+    NumericDistance {
+        source: AddressForm,
+        targets: Vec<String>,
+        range: HexNumber,
+        #[serde(default)]
+        value_offset: u64,
+        #[serde(default)]
+        distance_offset: u64,
+        #[serde(default = "default_one")]
+        divisor: f64,
+    },
+    /// For addresses consisting only of decimal digits, chance to get that close
+    /// to either end of the decimal range
+    DecimalRange {
+        source: AddressForm,
+    },
+    /// Chance to get at least `input` letters among `total` characters
+    LettersCombinations {
+        total: u64,
+    },
+    /// Chance to get at least `input` repeated characters among `total` characters
+    Snake {
+        total: u64,
+    },
+    /// `base ^ (input - offset)`
+    Power {
+        base: f64,
+        #[serde(default)]
+        offset: f64,
+    },
+    /// Chance to get `input` identical leading symbols, out of `symbols` possible symbols
+    /// each appearing with `symbolProbability`
+    Run {
+        symbols: f64,
+        symbol_probability: f64,
+    },
+    /// Chance to find the rarest of the patterns matched by the category score,
+    /// computed exactly for the number of times it was found (see probability.rs)
+    PatternRarity,
+    /// `input * factor` if input is at least `min`, otherwise 1
+    Linear {
+        factor: f64,
+        min: f64,
+    },
+}
+
+fn default_one() -> f64 {
+    1.0
+}
+
+impl DifficultyFormula {
+    pub fn evaluate(&self, input: f64, forms: &AddressForms, score_measure: &Measure) -> f64 {
+        match self {
+            DifficultyFormula::Constant { value } => *value,
+            DifficultyFormula::NumericDistance {
+                source,
+                targets,
+                range,
+                value_offset,
+                distance_offset,
+                divisor,
+            } => {
+                let s = forms.get(*source);
+                let current_number =
+                    U256::from_str_radix(s, 16).unwrap() + U256::from(*value_offset);
+                let mut min_difference = range.0;
+                for target in targets {
+                    let full_str = target.repeat(s.len().div_ceil(target.len()));
+                    let ideal_number = U256::from_str_radix(&full_str[0..s.len()], 16).unwrap();
+                    let difference = if ideal_number >= current_number {
+                        ideal_number - current_number
+                    } else {
+                        current_number - ideal_number
+                    };
+                    if difference < min_difference {
+                        min_difference = difference;
+                    }
+                }
+                u256_to_float(range.0)
+                    / u256_to_float(min_difference + U256::from(*distance_offset))
+                    / divisor
+            }
+            DifficultyFormula::DecimalRange { source } => {
+                let s = forms.get(*source);
+                if !s.chars().all(|c| c.is_numeric()) {
+                    return 1.0f64;
+                }
+                let number = s.parse::<f64>().unwrap();
+                let max_number = "9".repeat(s.len()).parse::<f64>().unwrap();
+                let difficulty1 = total_combinations(s.len() as f64)
+                    / 10.0f64.powf(s.len() as f64)
+                    / (number / max_number);
+                let difficulty2 = total_combinations(s.len() as f64)
+                    / 10.0f64.powf(s.len() as f64)
+                    / ((max_number - number) / max_number);
+                difficulty1.max(difficulty2)
+            }
+            DifficultyFormula::LettersCombinations { total } => {
+                exactly_letters_combinations_difficulty(input as u64, *total)
+            }
+            DifficultyFormula::Snake { total } => snake_difficulty(input as i64, *total),
+            DifficultyFormula::Power { base, offset } => base.powf(input - offset),
+            DifficultyFormula::Run {
+                symbols,
+                symbol_probability,
+            } => run_difficulty(input, *symbols, *symbol_probability),
+            DifficultyFormula::PatternRarity => {
+                let Measure::Patterns { source, patterns } = score_measure else {
+                    return 1.0f64;
+                };
+                let s = forms.get(*source);
+                let mut difficulty = 1.0f64;
+                for pattern in patterns {
+                    let pattern_difficulty =
+                        pattern_difficulty(&pattern.count_tail, pattern.count(s));
+                    if pattern_difficulty > difficulty {
+                        difficulty = pattern_difficulty;
+                    }
+                }
+                difficulty
+            }
+            DifficultyFormula::Linear { factor, min } => {
+                if input >= *min {
+                    input * factor
+                } else {
+                    1.0f64
+                }
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), AddressologyError> {
+        if let DifficultyFormula::NumericDistance { targets, .. } = self {
+            if targets.is_empty() {
+                return Err(err_custom_create!(
+                    "numericDistance needs at least one target"
+                ));
+            }
+            for target in targets {
+                let target = target.as_str();
+                if target.is_empty() || !target.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(err_custom_create!("Invalid hex target: {}", target));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreRule {
+    pub measure: Measure,
+    #[serde(default)]
+    pub offset: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DifficultyRule {
+    pub formula: DifficultyFormula,
+    /// Value fed to the formula, by default it is the category score
+    pub input: Option<Measure>,
+    #[serde(default = "default_one")]
+    pub multiplier: f64,
+    /// Difficulty is multiplied by `1 + bonus`
+    pub bonus: Option<Measure>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryRule {
+    pub key: String,
+    pub name: String,
+    pub description: String,
+    pub score: ScoreRule,
+    pub difficulty: DifficultyRule,
+}
+
+impl CategoryRule {
+    /// Returns (score, difficulty) of the address in this category
+    pub fn evaluate(&self, forms: &AddressForms) -> (f64, f64) {
+        let score = self.score.measure.evaluate(forms) + self.score.offset;
+        let input = match &self.difficulty.input {
+            Some(measure) => measure.evaluate(forms),
+            None => score,
+        };
+        let mut difficulty = self
+            .difficulty
+            .formula
+            .evaluate(input, forms, &self.score.measure);
+        if let Some(bonus) = &self.difficulty.bonus {
+            difficulty *= 1.0 + bonus.evaluate(forms);
+        }
+        (score, self.difficulty.multiplier * difficulty)
+    }
+}
+
+/// Ordered list of categories. Order matters - when two categories have the same difficulty
+/// the first one wins.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreRuleSet {
+    pub categories: Vec<CategoryRule>,
+}
+
+impl ScoreRuleSet {
+    pub fn from_json(json: &str) -> Result<Self, AddressologyError> {
+        let mut rule_set = serde_json::from_str::<ScoreRuleSet>(json)
+            .map_err(|e| err_custom_create!("Failed to parse score rules: {}", e))?;
+
+        if rule_set.categories.is_empty() {
+            return Err(err_custom_create!(
+                "Score rules have to define at least one category"
+            ));
+        }
+        let mut keys = BTreeSet::new();
+        for category in &rule_set.categories {
+            if !keys.insert(category.key.as_str()) {
+                return Err(err_custom_create!(
+                    "Duplicate category key: {}",
+                    category.key
+                ));
+            }
+            category.difficulty.formula.validate()?;
+        }
+        for category in rule_set.categories.iter_mut() {
+            if let DifficultyFormula::PatternRarity = category.difficulty.formula {
+                let Measure::Patterns { source, patterns } = &mut category.score.measure else {
+                    return Err(err_custom_create!(
+                        "patternRarity in {} requires patterns score measure",
+                        category.key
+                    ));
+                };
+                for pattern in patterns.iter_mut() {
+                    let simple = SimplePattern::parse(pattern.regex.0.as_str()).ok_or_else(|| {
+                        err_custom_create!(
+                            "Pattern {} in {} cannot be priced, only hex characters, '.', {{n}} and anchors are supported",
+                            pattern.regex.0.as_str(),
+                            category.key
+                        )
+                    })?;
+                    pattern.count_tail = simple.count_tail(*source);
+                }
+            }
+        }
+        Ok(rule_set)
+    }
+
+    pub fn load() -> Result<Self, AddressologyError> {
+        match get_score_rules_file() {
+            Some(path) => {
+                log::info!("Loading score rules from {}", path);
+                let json = std::fs::read_to_string(&path).map_err(|e| {
+                    err_custom_create!("Failed to read score rules file {}: {}", path, e)
+                })?;
+                Self::from_json(&json)
+            }
+            None => Self::default_rules(),
+        }
+    }
+
+    pub fn default_rules() -> Result<Self, AddressologyError> {
+        Self::from_json(DEFAULT_SCORE_RULES)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&CategoryRule> {
+        self.categories.iter().find(|c| c.key == key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forms(lower: &str) -> AddressForms {
+        AddressForms {
+            lower: lower.to_string(),
+            mixed: lower.to_string(),
+            short: lower[0..8].to_string() + &lower[31..40],
+        }
+    }
+
+    #[test]
+    fn test_custom_prefix_category() {
+        let rules = ScoreRuleSet::from_json(
+            r#"{"categories": [{
+                "key": "leading_deadbeef",
+                "name": "Leading deadbeef",
+                "description": "Address starting with deadbeef",
+                "score": {"measure": {"type": "prefix", "source": "lower", "prefix": "deadbeef"}},
+                "difficulty": {"formula": {"type": "power", "base": 16.0}}
+            }]}"#,
+        )
+        .unwrap();
+        let rule = rules.get("leading_deadbeef").unwrap();
+
+        let (score, difficulty) = rule.evaluate(&forms("deadbe0000000000000000000000000000000000"));
+        assert_eq!(score, 6.0);
+        assert_eq!(difficulty, 16.0f64.powf(6.0));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(ScoreRuleSet::from_json(r#"{"categories": []}"#).is_err());
+        let bad_regex = r#"{"categories": [{
+            "key": "bad", "name": "", "description": "",
+            "score": {"measure": {"type": "patterns", "source": "mixed",
+                "patterns": [{"regex": "(", "weight": 1.0}]}},
+            "difficulty": {"formula": {"type": "constant", "value": 1.0}}
+        }]}"#;
+        assert!(ScoreRuleSet::from_json(bad_regex).is_err());
+    }
+}
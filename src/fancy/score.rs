@@ -1,50 +1,33 @@
 use crate::config::get_base_difficulty;
-use crate::db::model::{FancyScore, FancyScoreEntry};
+use crate::db::model::{FancyScore, FancyScoreDbObj, FancyScoreEntry};
 use crate::fancy::address_to_mixed_case;
-use regex::Regex;
+use crate::fancy::{score_rules, AddressForms, ScoreRuleSet};
+use crate::types::DbAddress;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::str::FromStr;
-use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
-use web3::types::{Address, U256};
-
-#[derive(Serialize, Deserialize, EnumIter, PartialEq, Eq, Debug, Clone, Default)]
-pub enum FancyScoreCategory {
-    LeadingZeroes,
-    LeadingAny,
-    LettersHeavy,
-    LeadingPi,
-    NumbersOnly,
-    ShortLeadingZeroes,
-    ShortLeadingAny,
-    SnakeScoreNoCase,
-    SnakeScoreNeedCase,
-    SnakeScoreNeedLetters,
-
-    LeadingLetters,
-    PatternScore,
-    #[default]
-    Random,
+use web3::types::Address;
+
+/// Category key, valid keys are defined by the score rule set
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(transparent)]
+pub struct FancyScoreCategory(String);
+
+impl FancyScoreCategory {
+    pub fn new(key: &str) -> Self {
+        FancyScoreCategory(key.to_string())
+    }
+}
+
+impl Default for FancyScoreCategory {
+    fn default() -> Self {
+        FancyScoreCategory::new("random")
+    }
 }
 
 impl Display for FancyScoreCategory {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            FancyScoreCategory::LeadingZeroes => write!(f, "leading_zeroes"),
-            FancyScoreCategory::LeadingAny => write!(f, "leading_any"),
-            FancyScoreCategory::LettersHeavy => write!(f, "letters_heavy"),
-            FancyScoreCategory::LeadingPi => write!(f, "leading_pi"),
-            FancyScoreCategory::NumbersOnly => write!(f, "numbers_only"),
-            FancyScoreCategory::ShortLeadingZeroes => write!(f, "short_leading_zeroes"),
-            FancyScoreCategory::ShortLeadingAny => write!(f, "short_leading_any"),
-            FancyScoreCategory::SnakeScoreNoCase => write!(f, "snake_score_no_case"),
-            FancyScoreCategory::SnakeScoreNeedCase => write!(f, "snake_score_need_case"),
-            FancyScoreCategory::SnakeScoreNeedLetters => write!(f, "snake_score_need_letters"),
-            FancyScoreCategory::LeadingLetters => write!(f, "leading_letters"),
-            FancyScoreCategory::PatternScore => write!(f, "pattern_score"),
-            FancyScoreCategory::Random => write!(f, "random"),
-        }
+        write!(f, "{}", self.0)
     }
 }
 
@@ -52,21 +35,9 @@ impl FromStr for FancyScoreCategory {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "leading_zeroes" => Ok(FancyScoreCategory::LeadingZeroes),
-            "leading_any" => Ok(FancyScoreCategory::LeadingAny),
-            "letters_heavy" => Ok(FancyScoreCategory::LettersHeavy),
-            "leading_pi" => Ok(FancyScoreCategory::LeadingPi),
-            "numbers_only" => Ok(FancyScoreCategory::NumbersOnly),
-            "short_leading_zeroes" => Ok(FancyScoreCategory::ShortLeadingZeroes),
-            "short_leading_any" => Ok(FancyScoreCategory::ShortLeadingAny),
-            "snake_score_no_case" => Ok(FancyScoreCategory::SnakeScoreNoCase),
-            "snake_score_need_case" => Ok(FancyScoreCategory::SnakeScoreNeedCase),
-            "snake_score_need_letters" => Ok(FancyScoreCategory::SnakeScoreNeedLetters),
-            "leading_letters" => Ok(FancyScoreCategory::LeadingLetters),
-            "pattern_score" => Ok(FancyScoreCategory::PatternScore),
-            "random" => Ok(FancyScoreCategory::Random),
-            _ => Err(()),
+        match score_rules().get(s) {
+            Some(rule) => Ok(FancyScoreCategory::new(&rule.key)),
+            None => Err(()),
         }
     }
 }
@@ -79,82 +50,15 @@ pub struct FancyCategoryInfo {
 }
 
 pub fn list_score_categories() -> Vec<FancyCategoryInfo> {
-    let mut categories = Vec::new();
-
-    for category in FancyScoreCategory::iter() {
-        match category {
-            FancyScoreCategory::LeadingZeroes => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Leading Zeroes".to_string(),
-                description: "The number of leading zeroes in the address.".to_string(),
-            }),
-            FancyScoreCategory::LeadingAny => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Leading Any".to_string(),
-                description: "The number of leading characters that are the same.".to_string(),
-            }),
-            FancyScoreCategory::ShortLeadingZeroes => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Short Leading Zeroes".to_string(),
-                description: "The number of leading zeroes in the address.".to_string(),
-            }),
-            FancyScoreCategory::ShortLeadingAny => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Short Leading Any".to_string(),
-                description: "The number of leading characters that are the same.".to_string(),
-            }),
-            FancyScoreCategory::LettersHeavy => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Letters Heavy".to_string(),
-                description: "The number of letters in the address (ciphers can be different)."
-                    .to_string(),
-            }),
-            FancyScoreCategory::LeadingPi => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Leading Pi".to_string(),
-                description: "The number of leading pi digits in the address.".to_string(),
-            }),
-            FancyScoreCategory::NumbersOnly => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Smallest decimal".to_string(),
-                description: "Only cyphers, score determine by smallest decimal".to_string(),
-            }),
-            FancyScoreCategory::Random => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Random".to_string(),
-                description: "Randomness of the address.".to_string(),
-            }),
-            FancyScoreCategory::SnakeScoreNoCase => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Snake Score".to_string(),
-                description: "The number of repeating characters in the address. Case insensitive"
-                    .to_string(),
-            }),
-            FancyScoreCategory::SnakeScoreNeedCase => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Snake Score with Case".to_string(),
-                description: "The number of repeating characters in the address. Case sensitive"
-                    .to_string(),
-            }),
-            FancyScoreCategory::SnakeScoreNeedLetters => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Snake Score with Letters".to_string(),
-                description: "The number of repeating letters in the address.".to_string(),
-            }),
-            FancyScoreCategory::LeadingLetters => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Leading Letters".to_string(),
-                description: "The number of leading letters case sensitive in the address."
-                    .to_string(),
-            }),
-            FancyScoreCategory::PatternScore => categories.push(FancyCategoryInfo {
-                key: category.to_string(),
-                name: "Pattern Score".to_string(),
-                description: "Interesting patterns.".to_string(),
-            }),
-        }
-    }
-    categories
+    score_rules()
+        .categories
+        .iter()
+        .map(|category| FancyCategoryInfo {
+            key: category.key.clone(),
+            name: category.name.clone(),
+            description: category.description.clone(),
+        })
+        .collect()
 }
 
 pub fn total_combinations(n: f64) -> f64 {
@@ -248,8 +152,11 @@ async fn tx_test() {
     );
 }
 
-#[allow(clippy::vec_init_then_push)]
 pub fn score_fancy(address: Address) -> FancyScore {
+    score_fancy_with_rules(address, score_rules())
+}
+
+pub fn score_fancy_with_rules(address: Address, rules: &ScoreRuleSet) -> FancyScore {
     let mut score = FancyScore::default();
 
     score.address_lower_case = format!("{:#x}", address).to_lowercase();
@@ -257,349 +164,549 @@ pub fn score_fancy(address: Address) -> FancyScore {
     score.address_short_etherscan =
         score.address_mixed_case[0..10].to_string() + "..." + &score.address_mixed_case[33..42];
 
-    let mixed_address_str = score.address_mixed_case.trim_start_matches("0x");
-    let address_str = format!("{:#x}", address);
-    let address_str = address_str.trim_start_matches("0x");
-    let short_address_str = score
-        .address_short_etherscan
-        .trim_start_matches("0x")
-        .replace("...", "");
-    let mut leading_zeroes = 0;
-    for c in address_str.chars() {
-        if c == '0' {
-            leading_zeroes += 1;
-        } else {
-            break;
+    let forms = AddressForms {
+        lower: score
+            .address_lower_case
+            .trim_start_matches("0x")
+            .to_string(),
+        mixed: score
+            .address_mixed_case
+            .trim_start_matches("0x")
+            .to_string(),
+        short: score
+            .address_short_etherscan
+            .trim_start_matches("0x")
+            .replace("...", ""),
+    };
+
+    let score_entries = rules
+        .categories
+        .iter()
+        .map(|rule| {
+            let (category_score, difficulty) = rule.evaluate(&forms);
+            FancyScoreEntry {
+                category: FancyScoreCategory::new(&rule.key),
+                score: category_score,
+                difficulty,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    score.scores = score_entries
+        .iter()
+        .map(|entry| (entry.category.to_string(), entry.clone()))
+        .collect();
+
+    let neutral_price_point = get_base_difficulty();
+
+    // This simple method is better than iterator, because of float NaN issues
+    let mut biggest_score = score_entries[0].clone();
+    for entry in score_entries.iter() {
+        if entry.difficulty > biggest_score.difficulty {
+            biggest_score = entry.clone();
         }
     }
 
-    let char_start = address_str.chars().next().unwrap();
-    let mut leading_any = 0;
-    for c in address_str.chars() {
-        if c == char_start {
-            leading_any += 1;
-        } else {
-            break;
+    let biggest_score_difficulty = biggest_score.difficulty;
+
+    let price_multiplier = if biggest_score_difficulty <= neutral_price_point {
+        1.0
+    } else {
+        biggest_score_difficulty / neutral_price_point
+    };
+
+    score.total_score = biggest_score_difficulty;
+    score.price_multiplier = price_multiplier;
+    score.category = biggest_score.category.to_string();
+    score
+}
+
+/// Rows stored for every category, winning category comes first and the rest by difficulty
+pub fn score_breakdown(address: DbAddress, score: &FancyScore) -> Vec<FancyScoreDbObj> {
+    let mut entries = score.scores.values().collect::<Vec<_>>();
+    entries.sort_by(|a, b| {
+        let a_wins = a.category.to_string() == score.category;
+        let b_wins = b.category.to_string() == score.category;
+        b_wins
+            .cmp(&a_wins)
+            .then(b.difficulty.total_cmp(&a.difficulty))
+    });
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(idx, entry)| FancyScoreDbObj {
+            address,
+            category: entry.category.to_string(),
+            score: entry.score,
+            difficulty: entry.difficulty,
+            category_rank: idx as i32 + 1,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fancy::run_difficulty;
+    use rand::Rng;
+    use regex::Regex;
+    use web3::types::{Address, U256};
+
+    /// Hard-coded scoring used before the rule engine, kept as reference for the default rules
+    #[allow(clippy::vec_init_then_push)]
+    fn legacy_score_fancy(address: Address) -> FancyScore {
+        let mut score = FancyScore::default();
+
+        score.address_lower_case = format!("{:#x}", address).to_lowercase();
+        score.address_mixed_case = address_to_mixed_case(&address);
+        score.address_short_etherscan =
+            score.address_mixed_case[0..10].to_string() + "..." + &score.address_mixed_case[33..42];
+
+        let mixed_address_str = score.address_mixed_case.trim_start_matches("0x");
+        let address_str = format!("{:#x}", address);
+        let address_str = address_str.trim_start_matches("0x");
+        let short_address_str = score
+            .address_short_etherscan
+            .trim_start_matches("0x")
+            .replace("...", "");
+        let mut leading_zeroes = 0;
+        for c in address_str.chars() {
+            if c == '0' {
+                leading_zeroes += 1;
+            } else {
+                break;
+            }
         }
-    }
 
-    let mut leading_letters = 0;
-    let mixed_char_start = mixed_address_str.chars().next().unwrap();
-    if mixed_char_start.is_alphabetic() {
-        for c in mixed_address_str.chars() {
-            if c == mixed_char_start {
-                leading_letters += 1;
+        let char_start = address_str.chars().next().unwrap();
+        let mut leading_any = 0;
+        for c in address_str.chars() {
+            if c == char_start {
+                leading_any += 1;
             } else {
                 break;
             }
         }
-    }
 
-    let mut extra_letter_bonus = 1;
-    let mut snake_score_mixed = 0;
-    let first_char = mixed_address_str.chars().next().unwrap();
-    let mut prev_char = first_char;
-    for c in mixed_address_str.chars() {
-        if c == prev_char {
-            snake_score_mixed += 1;
-            if c.is_alphabetic() {
-                extra_letter_bonus += 1;
+        let mut leading_letters = 0;
+        let mixed_char_start = mixed_address_str.chars().next().unwrap();
+        if mixed_char_start.is_alphabetic() {
+            for c in mixed_address_str.chars() {
+                if c == mixed_char_start {
+                    leading_letters += 1;
+                } else {
+                    break;
+                }
             }
-        } else {
-            prev_char = c;
         }
-    }
 
-    let mut letters_heavy = 0;
-    for c in address_str.chars() {
-        if c.is_alphabetic() {
-            letters_heavy += 1;
+        let mut extra_letter_bonus = 1;
+        let mut snake_score_mixed = 0;
+        let first_char = mixed_address_str.chars().next().unwrap();
+        let mut prev_char = first_char;
+        for c in mixed_address_str.chars() {
+            if c == prev_char {
+                snake_score_mixed += 1;
+                if c.is_alphabetic() {
+                    extra_letter_bonus += 1;
+                }
+            } else {
+                prev_char = c;
+            }
         }
-    }
 
-    let mut numbers_only = 0;
-    for c in address_str.chars() {
-        if c.is_numeric() {
-            numbers_only += 1;
+        let mut letters_heavy = 0;
+        for c in address_str.chars() {
+            if c.is_alphabetic() {
+                letters_heavy += 1;
+            }
         }
-    }
 
-    let mut short_leading_zeroes = 0;
-    for c in short_address_str.chars() {
-        if c == '0' {
-            short_leading_zeroes += 1;
-        } else {
-            break;
+        let mut numbers_only = 0;
+        for c in address_str.chars() {
+            if c.is_numeric() {
+                numbers_only += 1;
+            }
         }
-    }
 
-    let mut short_leading_any = 0;
-    let char_start = short_address_str.chars().next().unwrap();
-    for c in short_address_str.chars() {
-        if c == char_start {
-            short_leading_any += 1;
-        } else {
-            break;
+        let mut short_leading_zeroes = 0;
+        for c in short_address_str.chars() {
+            if c == '0' {
+                short_leading_zeroes += 1;
+            } else {
+                break;
+            }
         }
-    }
 
-    let mut snake_score_no_case: i64 = 0;
-    let mut prev_char = address_str.chars().next().unwrap();
-    for c in address_str.chars() {
-        if c == prev_char {
-            snake_score_no_case += 1;
-        } else {
-            prev_char = c;
+        let mut short_leading_any = 0;
+        let char_start = short_address_str.chars().next().unwrap();
+        for c in short_address_str.chars() {
+            if c == char_start {
+                short_leading_any += 1;
+            } else {
+                break;
+            }
         }
-    }
 
-    let mut score_entries = Vec::new();
+        let mut snake_score_no_case: i64 = 0;
+        let mut prev_char = address_str.chars().next().unwrap();
+        for c in address_str.chars() {
+            if c == prev_char {
+                snake_score_no_case += 1;
+            } else {
+                prev_char = c;
+            }
+        }
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::Random,
-        score: 1.0f64,
-        difficulty: 1000.0f64,
-    });
+        let mut score_entries = Vec::new();
 
-    //for leading zeroes difficulty is a chance to get the smallest number interpreted as hex number
-    let difficulty_leading_zeroes = {
-        let number = U256::from_str_radix(address_str, 16).unwrap() + U256::from(1);
-        let max_number =
-            U256::from_str_radix("0xffffffffffffffffffffffffffffffffffffffff", 16).unwrap();
+        score_entries.push(FancyScoreEntry {
+            category: FancyScoreCategory::new("random"),
+            score: 1.0f64,
+            difficulty: 1000.0f64,
+        });
 
-        let u256_to_float = |u256: U256| -> f64 {
-            let u256_str = u256.to_string();
-            u256_str.parse::<f64>().unwrap()
+        //for leading zeroes difficulty is a chance to get the smallest number interpreted as hex number
+        let difficulty_leading_zeroes = {
+            let number = U256::from_str_radix(address_str, 16).unwrap() + U256::from(1);
+            let max_number =
+                U256::from_str_radix("0xffffffffffffffffffffffffffffffffffffffff", 16).unwrap();
+
+            let u256_to_float = |u256: U256| -> f64 {
+                let u256_str = u256.to_string();
+                u256_str.parse::<f64>().unwrap()
+            };
+            let float_number = u256_to_float(number);
+            let float_max_number = u256_to_float(max_number);
+            float_max_number / float_number
         };
-        let float_number = u256_to_float(number);
-        let float_max_number = u256_to_float(max_number);
-        float_max_number / float_number
-    };
-    //for leading zeroes difficulty is a chance to get the smallest number interpreted as hex number
-    let difficulty_leading_pi = {
-        let target_number =
-            U256::from_str_radix("0x3141592653589793238462643383279502884197", 16).unwrap();
-        let current_number = U256::from_str_radix(address_str, 16).unwrap();
-        let number = if target_number >= current_number {
-            target_number - current_number
-        } else {
-            current_number - target_number
+        //for leading zeroes difficulty is a chance to get the smallest number interpreted as hex number
+        let difficulty_leading_pi = {
+            let target_number =
+                U256::from_str_radix("0x3141592653589793238462643383279502884197", 16).unwrap();
+            let current_number = U256::from_str_radix(address_str, 16).unwrap();
+            let number = if target_number >= current_number {
+                target_number - current_number
+            } else {
+                current_number - target_number
+            };
+            let max_number =
+                U256::from_str_radix("0xffffffffffffffffffffffffffffffffffffffff", 16).unwrap();
+            let u256_to_float = |u256: U256| -> f64 {
+                let u256_str = u256.to_string();
+                u256_str.parse::<f64>().unwrap()
+            };
+            let float_number = u256_to_float(number);
+            let float_max_number = u256_to_float(max_number);
+            float_max_number / float_number
         };
-        let max_number =
-            U256::from_str_radix("0xffffffffffffffffffffffffffffffffffffffff", 16).unwrap();
         let u256_to_float = |u256: U256| -> f64 {
             let u256_str = u256.to_string();
             u256_str.parse::<f64>().unwrap()
         };
-        let float_number = u256_to_float(number);
-        let float_max_number = u256_to_float(max_number);
-        float_max_number / float_number
-    };
-    let u256_to_float = |u256: U256| -> f64 {
-        let u256_str = u256.to_string();
-        u256_str.parse::<f64>().unwrap()
-    };
-    let difficulty_leading_any = {
-        let max_number = U256::from_str_radix("0x1111111111111111111111111111111111111111", 16)
-            .unwrap()
-            / U256::from(2);
-        let mut min_difference = max_number;
-        for i in [
-            '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
-        ]
-        .iter()
-        {
-            let full_str = i.to_string().repeat(40);
-            let ideal_number = U256::from_str_radix(&full_str, 16).unwrap();
+        let difficulty_leading_any = {
+            let max_number = U256::from_str_radix("0x1111111111111111111111111111111111111111", 16)
+                .unwrap()
+                / U256::from(2);
+            let mut min_difference = max_number;
+            for i in [
+                '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
+            ]
+            .iter()
+            {
+                let full_str = i.to_string().repeat(40);
+                let ideal_number = U256::from_str_radix(&full_str, 16).unwrap();
+
+                let current_number = U256::from_str_radix(address_str, 16).unwrap();
+
+                let difference = if ideal_number >= current_number {
+                    ideal_number - current_number
+                } else {
+                    current_number - ideal_number
+                };
+                if difference < min_difference {
+                    min_difference = difference;
+                }
+            }
+            u256_to_float(max_number) / u256_to_float(min_difference + U256::from(1)) / 15.0
+        };
 
-            let current_number = U256::from_str_radix(address_str, 16).unwrap();
+        score_entries.push(FancyScoreEntry {
+            category: FancyScoreCategory::new("leading_zeroes"),
+            score: leading_zeroes as f64,
+            difficulty: difficulty_leading_zeroes,
+        });
 
-            let difference = if ideal_number >= current_number {
-                ideal_number - current_number
-            } else {
-                current_number - ideal_number
-            };
-            if difference < min_difference {
-                min_difference = difference;
-            }
+        score_entries.push(FancyScoreEntry {
+            category: FancyScoreCategory::new("leading_pi"),
+            score: 40.0f64,
+            difficulty: difficulty_leading_pi,
+        });
+
+        score_entries.push(FancyScoreEntry {
+            category: FancyScoreCategory::new("leading_any"),
+            score: leading_any as f64 - 1.0_f64,
+            difficulty: difficulty_leading_any,
+        });
+
+        score_entries.push(FancyScoreEntry {
+            category: FancyScoreCategory::new("letters_heavy"),
+            score: letters_heavy as f64,
+            difficulty: exactly_letters_combinations_difficulty(letters_heavy, 40),
+        });
+
+        if numbers_only == 40 {
+            let number = address_str.parse::<f64>().unwrap();
+            let max_number = 9999999999999999999999999999999999999999f64;
+            let difficulty1 = total_combinations(40.0)
+                / 10.0f64.powf(numbers_only as f64)
+                / (number / max_number);
+            let difficulty2 = total_combinations(40.0)
+                / 10.0f64.powf(numbers_only as f64)
+                / ((max_number - number) / max_number);
+            score_entries.push(FancyScoreEntry {
+                category: FancyScoreCategory::new("numbers_only"),
+                score: numbers_only as f64,
+                difficulty: difficulty1.max(difficulty2),
+            });
+        } else {
+            score_entries.push(FancyScoreEntry {
+                category: FancyScoreCategory::new("numbers_only"),
+                score: numbers_only as f64,
+                difficulty: 1.0f64,
+            });
         }
-        u256_to_float(max_number) / u256_to_float(min_difference + U256::from(1)) / 15.0
-    };
+        let difficulty_short_leading_zeroes = {
+            //important to add 1 to avoid division by zero and get proper result when address is exactly zero
+            let number = U256::from_str_radix(&short_address_str, 16).unwrap() + U256::from(1);
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::LeadingZeroes,
-        score: leading_zeroes as f64,
-        difficulty: difficulty_leading_zeroes,
-    });
+            let max_number = U256::from_str_radix("0xfffffffffffffffff", 16).unwrap();
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::LeadingPi,
-        score: 40.0f64,
-        difficulty: difficulty_leading_pi,
-    });
+            let float_number = u256_to_float(number);
+            let float_max_number = u256_to_float(max_number);
+            float_max_number / float_number
+        };
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::LeadingAny,
-        score: leading_any as f64 - 1.0_f64,
-        difficulty: difficulty_leading_any,
-    });
+        score_entries.push(FancyScoreEntry {
+            category: FancyScoreCategory::new("short_leading_zeroes"),
+            score: short_leading_zeroes as f64,
+            difficulty: difficulty_short_leading_zeroes,
+        });
+        let short_difficulty_leading_any = {
+            let max_number =
+                U256::from_str_radix("0x11111111111111111", 16).unwrap() / U256::from(2);
+            let mut min_difference = max_number;
+            let current_number =
+                U256::from_str_radix(&short_address_str, 16).unwrap() + U256::from(1);
+            for i in [
+                '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
+            ]
+            .iter()
+            {
+                let full_str = i.to_string().repeat(17);
+                let ideal_number = U256::from_str_radix(&full_str, 16).unwrap();
+
+                let difference = if ideal_number >= current_number {
+                    ideal_number - current_number
+                } else {
+                    current_number - ideal_number
+                };
+                if difference < min_difference {
+                    min_difference = difference;
+                }
+            }
+            u256_to_float(max_number) / u256_to_float(min_difference + U256::from(1)) / 15.0
+        };
+        score_entries.push(FancyScoreEntry {
+            category: FancyScoreCategory::new("short_leading_any"),
+            score: short_leading_any as f64,
+            difficulty: short_difficulty_leading_any,
+        });
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::LettersHeavy,
-        score: letters_heavy as f64,
-        difficulty: exactly_letters_combinations_difficulty(letters_heavy, 40),
-    });
+        score_entries.push(FancyScoreEntry {
+            category: FancyScoreCategory::new("snake_score_no_case"),
+            score: (snake_score_no_case - 1) as f64,
+            difficulty: snake_difficulty(snake_score_no_case - 1, 40),
+        });
 
-    if numbers_only == 40 {
-        let number = address_str.parse::<f64>().unwrap();
-        let max_number = 9999999999999999999999999999999999999999f64;
-        let difficulty1 =
-            total_combinations(40.0) / 10.0f64.powf(numbers_only as f64) / (number / max_number);
-        let difficulty2 = total_combinations(40.0)
-            / 10.0f64.powf(numbers_only as f64)
-            / ((max_number - number) / max_number);
         score_entries.push(FancyScoreEntry {
-            category: FancyScoreCategory::NumbersOnly,
-            score: numbers_only as f64,
-            difficulty: difficulty1.max(difficulty2),
+            category: FancyScoreCategory::new("snake_score_need_case"),
+            score: (snake_score_no_case - 1) as f64,
+            difficulty: 5.0f64 * snake_difficulty(snake_score_mixed - 1, 40),
         });
-    } else {
+
         score_entries.push(FancyScoreEntry {
-            category: FancyScoreCategory::NumbersOnly,
-            score: numbers_only as f64,
-            difficulty: 1.0f64,
+            category: FancyScoreCategory::new("snake_score_need_letters"),
+            score: (snake_score_no_case - 1) as f64,
+            difficulty: (extra_letter_bonus as f64) * snake_difficulty(snake_score_no_case - 1, 40),
         });
-    }
-    let difficulty_short_leading_zeroes = {
-        //important to add 1 to avoid division by zero and get proper result when address is exactly zero
-        let number = U256::from_str_radix(&short_address_str, 16).unwrap() + U256::from(1);
 
-        let max_number = U256::from_str_radix("0xfffffffffffffffff", 16).unwrap();
+        score_entries.push(FancyScoreEntry {
+            category: FancyScoreCategory::new("leading_letters"),
+            score: leading_letters as f64,
+            difficulty: 32.0f64.powf(leading_letters as f64 - (15. / 16.)),
+        });
 
-        let float_number = u256_to_float(number);
-        let float_max_number = u256_to_float(max_number);
-        float_max_number / float_number
-    };
+        let mut pattern_score_difficulty = 1.0f64;
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::ShortLeadingZeroes,
-        score: short_leading_zeroes as f64,
-        difficulty: difficulty_short_leading_zeroes,
-    });
-    let short_difficulty_leading_any = {
-        let max_number = U256::from_str_radix("0x11111111111111111", 16).unwrap() / U256::from(2);
-        let mut min_difference = max_number;
-        let current_number = U256::from_str_radix(&short_address_str, 16).unwrap() + U256::from(1);
-        for i in [
-            '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
-        ]
-        .iter()
-        {
-            let full_str = i.to_string().repeat(17);
-            let ideal_number = U256::from_str_radix(&full_str, 16).unwrap();
+        let count_0bb50 = mixed_address_str.matches("0BB50").count();
+        let count_0bb5 = mixed_address_str.matches("0BB5").count() - count_0bb50;
+        let count_bb50 = mixed_address_str.matches("BB50").count() - count_0bb50;
+        let mut pattern_score = count_0bb50 * 2 + count_0bb5 + count_bb50;
 
-            let difference = if ideal_number >= current_number {
-                ideal_number - current_number
-            } else {
-                current_number - ideal_number
-            };
-            if difference < min_difference {
-                min_difference = difference;
-            }
+        let pattern5zeroes_start = Regex::new(r"^00000.{3}00000").unwrap();
+        let pattern6zeroes_any = Regex::new(r"000000.{3}000000").unwrap();
+        if pattern5zeroes_start.is_match(mixed_address_str) {
+            pattern_score += 1000;
+        }
+        if pattern6zeroes_any.is_match(mixed_address_str) {
+            pattern_score += 20000;
+        }
+        if mixed_address_str.contains("0000BB50000") {
+            pattern_score += 500;
+        }
+        if pattern_score >= 6 {
+            pattern_score_difficulty = pattern_score as f64 * 1.0E10;
         }
-        u256_to_float(max_number) / u256_to_float(min_difference + U256::from(1)) / 15.0
-    };
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::ShortLeadingAny,
-        score: short_leading_any as f64,
-        difficulty: short_difficulty_leading_any,
-    });
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::SnakeScoreNoCase,
-        score: (snake_score_no_case - 1) as f64,
-        difficulty: snake_difficulty(snake_score_no_case - 1, 40),
-    });
+        score_entries.push(FancyScoreEntry {
+            category: FancyScoreCategory::new("pattern_score"),
+            score: pattern_score as f64,
+            difficulty: pattern_score_difficulty,
+        });
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::SnakeScoreNeedCase,
-        score: (snake_score_no_case - 1) as f64,
-        difficulty: 5.0f64 * snake_difficulty(snake_score_mixed - 1, 40),
-    });
+        score.scores = score_entries
+            .iter()
+            .map(|entry| (entry.category.to_string(), entry.clone()))
+            .collect();
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::SnakeScoreNeedLetters,
-        score: (snake_score_no_case - 1) as f64,
-        difficulty: (extra_letter_bonus as f64) * snake_difficulty(snake_score_no_case - 1, 40),
-    });
+        let neutral_price_point = get_base_difficulty();
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::LeadingLetters,
-        score: leading_letters as f64,
-        difficulty: 32.0f64.powf(leading_letters as f64 - (15. / 16.)),
-    });
+        // This simple method is better than iterator, because of float NaN issues
+        let mut biggest_score = score_entries[0].clone();
+        for entry in score_entries.iter() {
+            if entry.difficulty > biggest_score.difficulty {
+                biggest_score = entry.clone();
+            }
+        }
 
-    let mut pattern_score_difficulty = 1.0f64;
+        let biggest_score_difficulty = biggest_score.difficulty;
 
-    let count_0bb50 = mixed_address_str.matches("0BB50").count();
-    let count_0bb5 = mixed_address_str.matches("0BB5").count() - count_0bb50;
-    let count_bb50 = mixed_address_str.matches("BB50").count() - count_0bb50;
-    let mut pattern_score = count_0bb50 * 2 + count_0bb5 + count_bb50;
+        let price_multiplier = if biggest_score_difficulty <= neutral_price_point {
+            1.0
+        } else {
+            biggest_score_difficulty / neutral_price_point
+        };
 
-    let pattern5zeroes_start = Regex::new(r"^00000.{3}00000").unwrap();
-    let pattern6zeroes_any = Regex::new(r"000000.{3}000000").unwrap();
-    if pattern5zeroes_start.is_match(mixed_address_str) {
-        pattern_score += 1000;
-    }
-    if pattern6zeroes_any.is_match(mixed_address_str) {
-        pattern_score += 20000;
-    }
-    if mixed_address_str.contains("0000BB50000") {
-        pattern_score += 500;
-    }
-    if pattern_score >= 6 {
-        pattern_score_difficulty = pattern_score as f64 * 1.0E10;
+        score.total_score = biggest_score_difficulty;
+        score.price_multiplier = price_multiplier;
+        score.category = biggest_score.category.to_string();
+        score
     }
 
-    score_entries.push(FancyScoreEntry {
-        category: FancyScoreCategory::PatternScore,
-        score: pattern_score as f64,
-        difficulty: pattern_score_difficulty,
-    });
-
-    score.scores = score_entries
-        .iter()
-        .map(|entry| (entry.category.to_string(), entry.clone()))
-        .collect();
+    #[test]
+    fn test_default_rules_match_legacy_scoring() {
+        let rules = ScoreRuleSet::default_rules().unwrap();
+        let mut addresses = vec![
+            "0x0000000000000000000000000000000000000000",
+            "0x0000000000000000000000000000000000000001",
+            "0x00000a1c00000e8a1fa9ec8dd8e4b9e4fd2e4c10",
+            "0x3141592653589793238462643383279502884197",
+            "0x3141592653589793238462643383279502884198",
+            "0x1111111111111111111111111111111111111111",
+            "0xffffffffffffffffffffffffffffffffffffffff",
+            "0x1234567890123456789012345678901234567890",
+            "0x9999999999999999999999999999999999999999",
+            "0xaAaAAaaaabbbbcccccddddeeeeeffffaaaaabbbb",
+            "0x99927777d11dDdFfFfF79b93bB00BBbB5fff5553",
+            "0x31585b5cd5557777376822555552bb555ee18882",
+            "0x0bb500bb500bb500bb500bb500bb500bb500bb50",
+            "0x00000123000000000456000000bb50000a000000",
+        ]
+        .into_iter()
+        .map(|a| Address::from_str(a).unwrap())
+        .collect::<Vec<_>>();
 
-    let neutral_price_point = get_base_difficulty();
+        let mut rng = rand::rng();
+        for _ in 0..20000 {
+            addresses.push(Address::from(rng.random::<[u8; 20]>()));
+        }
 
-    // This simple method is better than iterator, because of float NaN issues
-    let mut biggest_score = score_entries[0].clone();
-    for entry in score_entries.iter() {
-        if entry.difficulty > biggest_score.difficulty {
-            biggest_score = entry.clone();
+        // leading_letters and pattern_score are priced from exact probabilities now
+        let exact = ["leading_letters", "pattern_score"];
+        for address in addresses {
+            let score = score_fancy_with_rules(address, &rules);
+            let legacy = legacy_score_fancy(address);
+            assert_eq!(score.address_mixed_case, legacy.address_mixed_case);
+            assert_eq!(score.scores.len(), legacy.scores.len());
+            for (key, entry) in &score.scores {
+                let legacy_entry = &legacy.scores[key];
+                assert_eq!(
+                    entry.score, legacy_entry.score,
+                    "Score mismatch for {:#x} in {}",
+                    address, key
+                );
+                if !exact.contains(&key.as_str()) {
+                    assert_eq!(
+                        entry.difficulty, legacy_entry.difficulty,
+                        "Difficulty mismatch for {:#x} in {}",
+                        address, key
+                    );
+                }
+            }
         }
     }
 
-    let biggest_score_difficulty = biggest_score.difficulty;
-
-    let price_multiplier = if biggest_score_difficulty <= neutral_price_point {
-        1.0
-    } else {
-        biggest_score_difficulty / neutral_price_point
-    };
+    #[test]
+    fn test_exact_pattern_difficulty() {
+        let rules = ScoreRuleSet::default_rules().unwrap();
+        let plain = Address::from_str("0x1234567890123456789012345678901234567890").unwrap();
+        let score = score_fancy_with_rules(plain, &rules);
+        assert_eq!(score.scores["pattern_score"].difficulty, 1.0);
+        assert_eq!(score.scores["leading_letters"].difficulty, 1.0);
+
+        // one 0BB5 and one BB50 occurrence, priced by the rarer of the two (equal here)
+        let single = Address::from_str("0x1230bb5012345678901234567890123456789012").unwrap();
+        let score = score_fancy_with_rules(single, &rules);
+        let difficulty = score.scores["pattern_score"].difficulty;
+        // 37 positions with probability 1/16 * (1/32)^2 * 1/16, about 1 in 7085
+        assert!(difficulty > 6000.0 && difficulty < 8000.0, "{}", difficulty);
+
+        let letters = Address::from_str("0xaaaa567890123456789012345678901234567890").unwrap();
+        let score = score_fancy_with_rules(letters, &rules);
+        let entry = &score.scores["leading_letters"];
+        assert_eq!(
+            entry.difficulty,
+            run_difficulty(entry.score, 12.0, 1.0 / 32.0)
+        );
+    }
 
-    score.total_score = biggest_score_difficulty;
-    score.price_multiplier = price_multiplier;
-    score.category = biggest_score.category.to_string();
-    score
-}
+    #[test]
+    fn test_default_rules_categories() {
+        let keys = list_score_categories()
+            .into_iter()
+            .map(|c| c.key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys.len(), 13);
+        assert!(FancyScoreCategory::from_str("leading_zeroes").is_ok());
+        assert!(FancyScoreCategory::from_str("leading_deadbeef").is_err());
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use web3::types::Address;
+    #[test]
+    fn test_score_breakdown() {
+        let address = Address::from_str("0x99927777d11dDdFfFfF79b93bB00BBbB5fff5553").unwrap();
+        let score = score_fancy(address);
+        let rows = score_breakdown(DbAddress::wrap(address), &score);
+        assert_eq!(rows.len(), score.scores.len());
+        assert_eq!(rows[0].category, score.category);
+        assert_eq!(rows[0].difficulty, score.total_score);
+        for (idx, row) in rows.iter().enumerate() {
+            assert_eq!(row.category_rank, idx as i32 + 1);
+        }
+        assert!(rows[1..]
+            .windows(2)
+            .all(|pair| pair[0].difficulty >= pair[1].difficulty));
+    }
 
     #[test]
     fn test_score_fancy() {
@@ -1,32 +1,171 @@
 use crate::err_custom_create;
 use crate::error::AddressologyError;
-use secp256k1::{PublicKey, Secp256k1};
+use secp256k1::ecdsa::Signature;
+use secp256k1::{Message, PublicKey, Scalar, Secp256k1, SecretKey};
+use std::fmt::Display;
+use std::str::FromStr;
 use tiny_keccak::{Hasher, Keccak};
-pub fn salt_to_guarded_salt(salt: &[u8]) -> [u8; 32] {
-    //take last 32 bytes
-    let mut result = [0; 32];
-    result.copy_from_slice(&salt[0..32]);
-    result
+
+/// keccak256 of the minimal CREATE3 proxy `0x67363d3d37363d34f03d5260086018f3`
+pub const DEFAULT_PROXY_INIT_CODE_HASH: &str =
+    "0x21c35dbe1b344a2488cf3321d6ce542f8e9f305544ff09e4993a62319a497c1f";
+
+/// How factory transforms user provided salt before deploying the proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaltGuard {
+    /// Salt is used as is
+    None,
+    /// keccak256(abi.encodePacked(msg.sender, salt))
+    SenderPacked,
+    /// CreateX `_guard`, permissioned salts require guard sender
+    CreateX,
 }
 
-pub fn compute_create3_command(factory: &str, salt: &str) -> Result<String, AddressologyError> {
-    log::debug!("Computing create3 for factory: {}, salt: {}", factory, salt);
+impl FromStr for SaltGuard {
+    type Err = String;
 
-    /*if let Some(caller) = &caller {
-        log::info!("Also checking for caller: {}", caller);
-    }*/
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(SaltGuard::None),
+            "senderpacked" => Ok(SaltGuard::SenderPacked),
+            "createx" => Ok(SaltGuard::CreateX),
+            _ => Err(format!("Invalid salt guard: {}", s)),
+        }
+    }
+}
 
-    /*
-    let caller_bytes = if let Some(caller) = &caller {
-        match hex::decode(caller.replace("0x", "")) {
-            Ok(bytes) => Some(bytes),
-            Err(e) => {
-                return Err(err_custom_create!("Failed to decode caller: {}", e));
+impl Display for SaltGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaltGuard::None => write!(f, "none"),
+            SaltGuard::SenderPacked => write!(f, "senderpacked"),
+            SaltGuard::CreateX => write!(f, "createx"),
+        }
+    }
+}
+
+/// Known CREATE3 factory implementations, each with its default salt guard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Create3FactoryKind {
+    Solady,
+    Sequence,
+    ZeframLou,
+    CreateX,
+    Custom,
+}
+
+impl Create3FactoryKind {
+    pub fn default_salt_guard(&self) -> SaltGuard {
+        match self {
+            Create3FactoryKind::Solady | Create3FactoryKind::Sequence => SaltGuard::None,
+            Create3FactoryKind::ZeframLou => SaltGuard::SenderPacked,
+            Create3FactoryKind::CreateX => SaltGuard::CreateX,
+            Create3FactoryKind::Custom => SaltGuard::None,
+        }
+    }
+}
+
+impl FromStr for Create3FactoryKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "solady" => Ok(Create3FactoryKind::Solady),
+            "sequence" => Ok(Create3FactoryKind::Sequence),
+            "zeframlou" => Ok(Create3FactoryKind::ZeframLou),
+            "createx" => Ok(Create3FactoryKind::CreateX),
+            "custom" => Ok(Create3FactoryKind::Custom),
+            _ => Err(format!("Invalid factory kind: {}", s)),
+        }
+    }
+}
+
+impl Display for Create3FactoryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Create3FactoryKind::Solady => write!(f, "solady"),
+            Create3FactoryKind::Sequence => write!(f, "sequence"),
+            Create3FactoryKind::ZeframLou => write!(f, "zeframlou"),
+            Create3FactoryKind::CreateX => write!(f, "createx"),
+            Create3FactoryKind::Custom => write!(f, "custom"),
+        }
+    }
+}
+
+/// Factory specific part of CREATE3 address computation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Create3Params {
+    pub proxy_init_code_hash: String,
+    pub salt_guard: SaltGuard,
+    /// Account calling the factory, needed by sender based guards
+    pub guard_sender: Option<String>,
+}
+
+impl Default for Create3Params {
+    fn default() -> Self {
+        Create3Params {
+            proxy_init_code_hash: DEFAULT_PROXY_INIT_CODE_HASH.to_string(),
+            salt_guard: SaltGuard::None,
+            guard_sender: None,
+        }
+    }
+}
+
+fn keccak(data: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    for d in data {
+        hasher.update(d);
+    }
+    let mut result = [0; 32];
+    hasher.finalize(&mut result);
+    result
+}
+
+pub fn salt_to_guarded_salt(
+    salt: &[u8],
+    salt_guard: SaltGuard,
+    guard_sender: Option<&[u8]>,
+) -> Result<[u8; 32], AddressologyError> {
+    let mut salt_bytes = [0; 32];
+    salt_bytes.copy_from_slice(&salt[0..32]);
+    match salt_guard {
+        SaltGuard::None => Ok(salt_bytes),
+        SaltGuard::SenderPacked => {
+            let sender = guard_sender
+                .ok_or_else(|| err_custom_create!("Salt guard requires guard sender"))?;
+            Ok(keccak(&[sender, &salt_bytes]))
+        }
+        SaltGuard::CreateX => {
+            let sender_match = guard_sender.map(|s| s == &salt_bytes[0..20]) == Some(true);
+            let zero_prefix = salt_bytes[0..20].iter().all(|b| *b == 0);
+            match salt_bytes[20] {
+                0x01 if sender_match || zero_prefix => Err(err_custom_create!(
+                    "Cross-chain protected CreateX salts are not supported"
+                )),
+                0x00 if sender_match => {
+                    let mut sender_word = [0; 32];
+                    sender_word[12..].copy_from_slice(&salt_bytes[0..20]);
+                    Ok(keccak(&[&sender_word, &salt_bytes]))
+                }
+                _ if sender_match => Err(err_custom_create!(
+                    "Invalid CreateX salt, permissioned salt needs 0x00 or 0x01 flag"
+                )),
+                0x00 => Ok(keccak(&[&salt_bytes])),
+                _ if zero_prefix => Err(err_custom_create!(
+                    "Invalid CreateX salt, zero address prefix needs 0x00 or 0x01 flag"
+                )),
+                _ => Ok(keccak(&[&salt_bytes])),
             }
         }
-    } else {
-        None
-    };*/
+    }
+}
+
+pub fn compute_create3(
+    factory: &str,
+    salt: &str,
+    params: &Create3Params,
+) -> Result<String, AddressologyError> {
+    log::debug!("Computing create3 for factory: {}, salt: {}", factory, salt);
 
     let factory_bytes = match hex::decode(factory.replace("0x", "")) {
         Ok(bytes) => bytes,
@@ -41,6 +180,24 @@ pub fn compute_create3_command(factory: &str, salt: &str) -> Result<String, Addr
             return Err(err_custom_create!("Failed to decode salt: {}", e));
         }
     };
+    let proxy_hash_bytes = match hex::decode(params.proxy_init_code_hash.replace("0x", "")) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return Err(err_custom_create!("Failed to decode proxy hash: {}", e));
+        }
+    };
+    let guard_sender_bytes = match &params.guard_sender {
+        Some(sender) => match hex::decode(sender.replace("0x", "")) {
+            Ok(bytes) if bytes.len() == 20 => Some(bytes),
+            Ok(_) => {
+                return Err(err_custom_create!("Guard sender has to be 20 bytes"));
+            }
+            Err(e) => {
+                return Err(err_custom_create!("Failed to decode guard sender: {}", e));
+            }
+        },
+        None => None,
+    };
 
     if factory_bytes.len() != 20 {
         return Err(err_custom_create!(
@@ -52,57 +209,67 @@ pub fn compute_create3_command(factory: &str, salt: &str) -> Result<String, Addr
             "Salt len has to be 32 bytes (64 characters)"
         ));
     }
-    let guarded_hash_bytes = salt_to_guarded_salt(&salt_bytes);
+    if proxy_hash_bytes.len() != 32 {
+        return Err(err_custom_create!(
+            "Proxy hash len has to be 32 bytes (64 characters)"
+        ));
+    }
+    let guarded_hash_bytes = salt_to_guarded_salt(
+        &salt_bytes,
+        params.salt_guard,
+        guard_sender_bytes.as_deref(),
+    )?;
 
     log::trace!("Guarded hash: 0x{}", hex::encode(guarded_hash_bytes));
 
-    let mut mem = Vec::new();
-
-    mem.extend_from_slice(&[0; 12]);
-    mem[0xb] = 0xff;
+    // proxy = CREATE2(factory, guarded salt, proxy init code)
+    let proxy = keccak(&[
+        &[0xff],
+        &factory_bytes,
+        &guarded_hash_bytes,
+        &proxy_hash_bytes,
+    ]);
 
-    mem.extend_from_slice(&factory_bytes);
+    log::trace!("Proxy: 0x{}", hex::encode(&proxy[12..]));
 
-    //at this point mem should have length
+    // contract = CREATE(proxy, nonce 1), rlp([proxy, 1]) = 0xd6 0x94 proxy 0x01
+    let result = keccak(&[&[0xd6, 0x94], &proxy[12..], &[0x01]]);
 
-    mem.extend_from_slice(&guarded_hash_bytes[0..0x20]);
-
-    let const_hex = "0x21c35dbe1b344a2488cf3321d6ce542f8e9f305544ff09e4993a62319a497c1f";
-    let const_bytes = match hex::decode(const_hex.replace("0x", "")) {
-        Ok(bytes) => bytes,
-        Err(e) => {
-            return Err(err_custom_create!("Failed to decode const: {}", e));
-        }
-    };
-    mem.extend_from_slice(&const_bytes);
-
-    assert_eq!(mem.len(), 96);
-    assert_eq!(mem.len(), 0xb + 0x55);
-
-    // keccak last 0x55 bytes
-
-    let mut hasher = tiny_keccak::Keccak::v256();
-    hasher.update(&mem[0xb..]);
-    let mut result = [0; 32];
-    hasher.finalize(&mut result);
-
-    log::trace!("0x{}", hex::encode(result));
-    //result goes to 0x14 bytes
-    //copy result into 0x14 mem location
+    Ok(format!("0x{}", hex::encode(&result[12..])))
+}
 
-    mem.as_mut_slice()[0x14..0x14 + 0x20].copy_from_slice(&result);
+/// CREATE2 address: keccak256(0xff ++ deployer ++ salt ++ keccak256(init_code))[12..]
+pub fn compute_create2(
+    deployer: &str,
+    salt: &str,
+    init_code_hash: &str,
+) -> Result<String, AddressologyError> {
+    let deployer_bytes = hex::decode(deployer.trim_start_matches("0x"))
+        .map_err(|e| err_custom_create!("Failed to decode deployer: {}", e))?;
+    let salt_bytes = hex::decode(salt.trim_start_matches("0x"))
+        .map_err(|e| err_custom_create!("Failed to decode salt: {}", e))?;
+    let init_code_hash_bytes = hex::decode(init_code_hash.trim_start_matches("0x"))
+        .map_err(|e| err_custom_create!("Failed to decode init code hash: {}", e))?;
 
-    log::trace!("0x{}", hex::encode(mem.as_slice()));
+    if deployer_bytes.len() != 20 {
+        return Err(err_custom_create!(
+            "Deployer len has to be 20 bytes (40 characters)"
+        ));
+    }
+    if salt_bytes.len() != 32 {
+        return Err(err_custom_create!(
+            "Salt len has to be 32 bytes (64 characters)"
+        ));
+    }
+    if init_code_hash_bytes.len() != 32 {
+        return Err(err_custom_create!(
+            "Init code hash len has to be 32 bytes (64 characters)"
+        ));
+    }
 
-    mem[0x1e] = 0xd6;
-    mem[0x1f] = 0x94;
-    mem[0x34] = 0x01;
-    let mut hasher = tiny_keccak::Keccak::v256();
-    hasher.update(&mem[0x1e..(0x1e + 0x17)]);
-    let mut result = [0; 32];
-    hasher.finalize(&mut result);
+    let result = keccak(&[&[0xff], &deployer_bytes, &salt_bytes, &init_code_hash_bytes]);
 
-    Ok(format!("0x{}", hex::encode(&result.as_slice()[12..])))
+    Ok(format!("0x{}", hex::encode(&result[12..])))
 }
 
 pub fn compute_address_command(
@@ -157,6 +324,82 @@ pub fn compute_address_command(
     Ok(format!("0x{}", hex::encode(&hash[12..])))
 }
 
+/// Validates uncompressed public key (64 bytes, without 04 prefix) and returns it in canonical form
+pub fn normalize_public_key_base(public_key_base: &str) -> Result<String, AddressologyError> {
+    let bytes = hex::decode(public_key_base.trim().trim_start_matches("0x"))
+        .map_err(|e| err_custom_create!("Failed to decode public key: {}", e))?;
+    if bytes.len() != 64 {
+        return Err(err_custom_create!(
+            "Invalid public key length, should be 64 bytes: {}",
+            bytes.len()
+        ));
+    }
+    PublicKey::from_slice(&[&[4u8], bytes.as_slice()].concat())
+        .map_err(|e| err_custom_create!("Public key is not a valid secp256k1 point: {}", e))?;
+    Ok(format!("0x{}", hex::encode(bytes)))
+}
+
+/// Checks Ethereum personal_sign signature (65 bytes r, s, v) of message made by the private key
+/// behind public key base, proving the customer owns it.
+pub fn verify_public_key_signature(
+    public_key_base: &str,
+    message: &str,
+    signature: &str,
+) -> Result<(), AddressologyError> {
+    let public_key_base = normalize_public_key_base(public_key_base)?;
+    let public_key = PublicKey::from_slice(
+        &[
+            &[4u8],
+            hex::decode(&public_key_base[2..])
+                .map_err(|e| err_custom_create!("Failed to decode public key: {}", e))?
+                .as_slice(),
+        ]
+        .concat(),
+    )
+    .map_err(|e| err_custom_create!("Public key is not a valid secp256k1 point: {}", e))?;
+    let signature = hex::decode(signature.trim().trim_start_matches("0x"))
+        .map_err(|e| err_custom_create!("Failed to decode signature: {}", e))?;
+    if signature.len() != 65 {
+        return Err(err_custom_create!(
+            "Invalid signature length, should be 65 bytes: {}",
+            signature.len()
+        ));
+    }
+    let mut signature = Signature::from_compact(&signature[..64])
+        .map_err(|e| err_custom_create!("Invalid signature: {}", e))?;
+    signature.normalize_s();
+
+    let mut hasher = Keccak::v256();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+    hasher.update(message.as_bytes());
+    let mut hash = [0u8; 32];
+    hasher.finalize(&mut hash);
+    Secp256k1::verification_only()
+        .verify_ecdsa(&Message::from_digest(hash), &signature, &public_key)
+        .map_err(|_| err_custom_create!("Signature does not match public key base"))
+}
+
+/// Final private key of split-key address, done by the customer who owns the private key base
+pub fn combine_private_key(
+    private_key_base: &str,
+    private_key_add: &str,
+) -> Result<String, AddressologyError> {
+    let decode = |key: &str| -> Result<[u8; 32], AddressologyError> {
+        hex::decode(key.trim_start_matches("0x"))
+            .map_err(|e| err_custom_create!("Failed to decode private key: {}", e))?
+            .try_into()
+            .map_err(|_| err_custom_create!("Private key has to be 32 bytes"))
+    };
+    let base = SecretKey::from_byte_array(&decode(private_key_base)?)
+        .map_err(|e| err_custom_create!("Invalid private key base: {}", e))?;
+    let add = Scalar::from_be_bytes(decode(private_key_add)?)
+        .map_err(|e| err_custom_create!("Invalid private key addition: {}", e))?;
+    let combined = base
+        .add_tweak(&add)
+        .map_err(|e| err_custom_create!("Failed to combine private keys: {}", e))?;
+    Ok(format!("0x{}", hex::encode(combined.secret_bytes())))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,16 +421,197 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_combine_private_key() {
+        let secp = Secp256k1::new();
+        let private_key_base = SecretKey::from_byte_array(&[0x11u8; 32]).unwrap();
+        let public_key_base = normalize_public_key_base(&hex::encode(
+            &PublicKey::from_secret_key(&secp, &private_key_base).serialize_uncompressed()[1..],
+        ))
+        .unwrap();
+        let private_key_add = format!("0x{}", "22".repeat(32));
+
+        let combined = combine_private_key(
+            &format!("0x{}", hex::encode(private_key_base.secret_bytes())),
+            &private_key_add,
+        )
+        .unwrap();
+        // combined key controls the address computed from public data only
+        let combined_key =
+            SecretKey::from_byte_array(&hex::decode(&combined[2..]).unwrap().try_into().unwrap())
+                .unwrap();
+        let mut hasher = Keccak::v256();
+        hasher.update(
+            &PublicKey::from_secret_key(&secp, &combined_key).serialize_uncompressed()[1..],
+        );
+        let mut hash = [0u8; 32];
+        hasher.finalize(&mut hash);
+        assert_eq!(
+            format!("0x{}", hex::encode(&hash[12..])),
+            compute_address_command(&public_key_base, &private_key_add).unwrap()
+        );
+
+        assert!(normalize_public_key_base("0x1234").is_err());
+        assert!(normalize_public_key_base(&"ab".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_verify_public_key_signature() {
+        let secp = Secp256k1::new();
+        let private_key_base = SecretKey::from_byte_array(&[0x11u8; 32]).unwrap();
+        let public_key_base = hex::encode(
+            &PublicKey::from_secret_key(&secp, &private_key_base).serialize_uncompressed()[1..],
+        );
+        let message = "challenge";
+        let mut hasher = Keccak::v256();
+        hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+        hasher.update(message.as_bytes());
+        let mut hash = [0u8; 32];
+        hasher.finalize(&mut hash);
+        let signature = secp
+            .sign_ecdsa(&Message::from_digest(hash), &private_key_base)
+            .serialize_compact();
+        let signature = format!("0x{}1b", hex::encode(signature));
+
+        assert!(verify_public_key_signature(&public_key_base, message, &signature).is_ok());
+        assert!(verify_public_key_signature(&public_key_base, "other", &signature).is_err());
+        let other_key = SecretKey::from_byte_array(&[0x22u8; 32]).unwrap();
+        let other_public_key = hex::encode(
+            &PublicKey::from_secret_key(&secp, &other_key).serialize_uncompressed()[1..],
+        );
+        assert!(verify_public_key_signature(&other_public_key, message, &signature).is_err());
+        assert!(verify_public_key_signature(&public_key_base, message, "0x1234").is_err());
+    }
+
+    #[test]
+    fn test_compute_create2() {
+        // examples from EIP-1014, init code 0x00
+        let init_code_hash = "0xbc36789e7a1e281436464229828f817d6612f7b477d66591ff96a9e064bcc98a";
+        let zero_salt = format!("0x{}", "00".repeat(32));
+
+        let result = compute_create2(
+            "0x0000000000000000000000000000000000000000",
+            &zero_salt,
+            init_code_hash,
+        );
+        assert_eq!(
+            result.unwrap(),
+            "0x4D1A2e2bB4F88F0250f26Ffff098B0b30B26BF38".to_lowercase()
+        );
+
+        let result = compute_create2(
+            "0xdeadbeef00000000000000000000000000000000",
+            &zero_salt,
+            init_code_hash,
+        );
+        assert_eq!(
+            result.unwrap(),
+            "0xB928f69Bb1D91Cd65274e3c79d8986362984fDA3".to_lowercase()
+        );
+
+        assert!(compute_create2("0xdeadbeef", &zero_salt, init_code_hash).is_err());
+        assert!(compute_create2(
+            "0xdeadbeef00000000000000000000000000000000",
+            "0x00",
+            init_code_hash
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_compute_create3_command() {
         let factory = "0x9E3F8eaE49E442A323EF2094f277Bf62752E6995".to_string();
         let salt = "0x9a07547b2ac4220006e585000000000000000000000000000000000000000000".to_string();
 
-        let result = compute_create3_command(&factory, &salt);
+        let result = compute_create3(&factory, &salt, &Create3Params::default());
         assert!(result.is_ok());
         assert_eq!(
             result.unwrap(),
             "0x31585b5cd5557777376822555552bb555ee18882".to_string()
         );
     }
+
+    #[test]
+    fn test_compute_create3_salt_guards() {
+        let factory = "0x9E3F8eaE49E442A323EF2094f277Bf62752E6995";
+        let sender = "0x1111111111111111111111111111111111111111";
+        let sender_bytes = hex::decode(&sender[2..]).unwrap();
+        let unguarded = |salt: &[u8]| {
+            compute_create3(
+                factory,
+                &format!("0x{}", hex::encode(salt)),
+                &Create3Params::default(),
+            )
+            .unwrap()
+        };
+
+        // ZeframLou style factory mixes sender into the salt
+        let salt = [0x42u8; 32];
+        let params = Create3Params {
+            salt_guard: SaltGuard::SenderPacked,
+            guard_sender: Some(sender.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            compute_create3(factory, &format!("0x{}", hex::encode(salt)), &params).unwrap(),
+            unguarded(&keccak(&[&sender_bytes, &salt]))
+        );
+        let no_sender = Create3Params {
+            guard_sender: None,
+            ..params.clone()
+        };
+        assert!(compute_create3(factory, &format!("0x{}", hex::encode(salt)), &no_sender).is_err());
+
+        let createx = Create3Params {
+            salt_guard: SaltGuard::CreateX,
+            guard_sender: Some(sender.to_string()),
+            ..Default::default()
+        };
+        // random salt is hashed
+        assert_eq!(
+            compute_create3(factory, &format!("0x{}", hex::encode(salt)), &createx).unwrap(),
+            unguarded(&keccak(&[&salt]))
+        );
+        // permissioned salt is hashed together with padded sender
+        let mut permissioned = [0x42u8; 32];
+        permissioned[0..20].copy_from_slice(&sender_bytes);
+        permissioned[20] = 0x00;
+        let mut sender_word = [0u8; 32];
+        sender_word[12..].copy_from_slice(&sender_bytes);
+        assert_eq!(
+            compute_create3(
+                factory,
+                &format!("0x{}", hex::encode(permissioned)),
+                &createx
+            )
+            .unwrap(),
+            unguarded(&keccak(&[&sender_word, &permissioned]))
+        );
+        permissioned[20] = 0x01;
+        assert!(compute_create3(
+            factory,
+            &format!("0x{}", hex::encode(permissioned)),
+            &createx
+        )
+        .is_err());
+
+        assert_eq!(
+            Create3FactoryKind::from_str("createx")
+                .unwrap()
+                .default_salt_guard(),
+            SaltGuard::CreateX
+        );
+        assert_eq!(
+            SaltGuard::from_str("senderPacked").unwrap(),
+            SaltGuard::SenderPacked
+        );
+        // stored values are lowercase like factory kinds
+        assert_eq!(SaltGuard::SenderPacked.to_string(), "senderpacked");
+        assert_eq!(
+            Create3FactoryKind::from_str("zeframLou")
+                .unwrap()
+                .to_string(),
+            "zeframlou"
+        );
+    }
 }
@@ -3,29 +3,42 @@ mod config;
 mod cookie;
 mod db;
 mod deploy;
+mod deploy_worker;
 mod email;
 mod error;
 mod fancy;
 mod hash;
+mod mine;
 mod oauth;
 mod solc;
 mod types;
 mod update;
+mod verify;
 
 use crate::api::scope::server_api_scope;
 use crate::config::get_base_difficulty_price;
 use crate::cookie::load_key_or_create;
 use crate::db::connection::create_pg_connection;
-use crate::db::model::DeployStatus;
+use crate::db::model::{
+    ContractFactoryDbObject, GasStrategy, LedgerAccount, NetworkDbObj, TokenLedgerKind,
+    TokenLedgerRefs,
+};
 use crate::db::ops::{
-    fancy_list_all, fancy_update_score, get_all_contracts_by_deploy_status_and_network,
-    insert_fancy_obj,
+    fancy_list_all, fancy_score_upsert, fancy_update_score, get_factory_by_address,
+    get_token_balance_mismatches, get_user_for_update, insert_fancy_obj, set_network_factories,
+    token_transfer, upsert_factory, upsert_network,
 };
 use crate::db::utils::get_current_utc_time;
-use crate::deploy::handle_fancy_deploy;
-use crate::fancy::parse_fancy;
-use crate::fancy::score_fancy;
-use crate::hash::{compute_address_command, compute_create3_command};
+use crate::deploy_worker::{run_deploy_worker, DeployWorkerOptions};
+use crate::error::AddressologyError;
+use crate::fancy::{factory_create3_params, parse_fancy_create2, parse_fancy_create3};
+use crate::fancy::{score_breakdown, score_fancy, score_rules};
+use crate::hash::{
+    combine_private_key, compute_address_command, compute_create2, compute_create3,
+    Create3FactoryKind, Create3Params, SaltGuard, DEFAULT_PROXY_INIT_CODE_HASH,
+};
+use crate::mine::{run_mine, MineOptions, MineTarget};
+use crate::solc::queue::{CompileLimits, CompileQueue};
 use crate::types::DbAddress;
 use actix_multipart::form::MultipartFormConfig;
 use actix_multipart::MultipartError;
@@ -43,7 +56,6 @@ use sqlx::PgPool;
 use std::env;
 use std::str::FromStr;
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
 fn get_allowed_emails() -> Vec<String> {
     let res = env::var("ALLOWED_EMAILS")
@@ -73,7 +85,9 @@ lazy_static! {
 }
 
 pub struct ServerData {
-    pub db_connection: Arc<Mutex<PgPool>>,
+    /// Pool is shared by all handlers, transactions are the only means of isolation
+    pub db_connection: PgPool,
+    pub compile_queue: Arc<CompileQueue>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -88,6 +102,8 @@ pub struct DeployDataContractEvmBytecode {
 #[serde(rename_all = "camelCase")]
 pub struct DeployDataContractEvm {
     pub bytecode: DeployDataContractEvmBytecode,
+    /// Runtime code, recognizes contract deployed by an earlier attempt
+    pub deployed_bytecode: Option<DeployDataContractEvmBytecode>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -222,15 +238,96 @@ enum Commands {
         #[arg(short, long)]
         last_day: bool,
     },
-    ProcessDeploy {
+    /// Deploy requested contracts, several workers can run at once
+    DeployWorker {
+        /// Only process given networks, all enabled networks by default
         #[arg(short, long)]
-        network: String,
+        network: Vec<String>,
+        /// Deployments running at once on a single network, sends from one deployer key are serialized
+        #[arg(long, default_value = "1")]
+        concurrency: usize,
+        /// Seconds between polls for new deployments
+        #[arg(long, default_value = "10")]
+        poll_interval: u64,
+        /// Maximum pause of a network after consecutive failures, in seconds
+        #[arg(long, default_value = "600")]
+        max_backoff: u64,
+        /// Exit when there is nothing left to deploy
+        #[arg(long)]
+        once: bool,
     },
     ComputeCreate3 {
         #[arg(short, long)]
         factory: String,
         #[arg(short, long)]
         salt: String,
+        /// solady, sequence, zeframlou, createx or custom
+        #[arg(short, long, default_value = "solady")]
+        kind: String,
+        #[arg(long)]
+        proxy_init_code_hash: Option<String>,
+        /// none, senderpacked or createx, defaults to the one of factory kind
+        #[arg(long)]
+        salt_guard: Option<String>,
+        #[arg(long)]
+        guard_sender: Option<String>,
+    },
+    /// Register CREATE3 factory or change its kind
+    AddFactory {
+        #[arg(short, long)]
+        address: String,
+        /// solady, sequence, zeframlou, createx or custom
+        #[arg(short, long, default_value = "solady")]
+        kind: String,
+        /// Register plain CREATE2 deployer instead, CREATE2 addresses are accepted only for these
+        #[arg(long)]
+        create2_deployer: bool,
+        #[arg(long)]
+        proxy_init_code_hash: Option<String>,
+        /// none, senderpacked or createx, defaults to the one of factory kind
+        #[arg(long)]
+        salt_guard: Option<String>,
+        #[arg(long)]
+        guard_sender: Option<String>,
+    },
+    /// Register network contracts can be deployed to or change its settings
+    AddNetwork {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short, long)]
+        chain_id: i64,
+        /// Tried in order, RPC_URL_<NETWORK> variable takes precedence
+        #[arg(short, long)]
+        rpc_url: Vec<String>,
+        #[arg(short, long)]
+        explorer_url: Option<String>,
+        /// legacy or eip1559
+        #[arg(long, default_value = "legacy")]
+        gas_strategy: String,
+        #[arg(long, default_value = "1.2")]
+        gas_multiplier: f64,
+        /// Environment variable holding the deployer key
+        #[arg(long, default_value = "DEPLOYER_PRIVATE_KEY")]
+        deployer_key_env: String,
+        /// Etherscan compatible api for source verification, e.g. https://api.etherscan.io/v2/api
+        #[arg(long)]
+        explorer_api_url: Option<String>,
+        /// Environment variable holding the explorer api key
+        #[arg(long, default_value = "ETHERSCAN_API_KEY")]
+        explorer_key_env: String,
+        /// Factories deployed on the network, replaces registered ones
+        #[arg(short, long)]
+        factory: Vec<String>,
+        #[arg(long)]
+        disabled: bool,
+    },
+    ComputeCreate2 {
+        #[arg(short, long)]
+        deployer: String,
+        #[arg(short, long)]
+        salt: String,
+        #[arg(short, long)]
+        init_code_hash: String,
     },
     ComputeAddress {
         #[arg(short = 'b', long)]
@@ -240,11 +337,69 @@ enum Commands {
         #[arg(short = 'e', long)]
         expected_address: Option<String>,
     },
+    /// Combine own private key base with bought private key addition
+    CombinePrivateKey {
+        #[arg(short = 'k', long)]
+        private_key_base: String,
+        #[arg(short = 'p', long)]
+        private_key_add: String,
+    },
     AddFancyAddress {
         #[arg(short, long)]
         factory: String,
         #[arg(short, long)]
         salt: String,
+        /// Add CREATE2 address, factory is then the deployer
+        #[arg(short, long)]
+        init_code_hash: Option<String>,
+    },
+    /// Grant tokens to user, negative amount takes them back as adjustment
+    GrantTokens {
+        #[arg(short, long)]
+        email: String,
+        #[arg(short, long, allow_hyphen_values = true)]
+        amount: i64,
+        #[arg(short, long)]
+        note: Option<String>,
+    },
+    /// List users whose token balance does not match the ledger
+    ReconcileTokens {},
+    /// Search for fancy addresses on CPU and submit them to the server
+    Mine {
+        #[arg(long, default_value = "http://localhost:80")]
+        server: String,
+        /// CREATE3 factory to mine salts for
+        #[arg(short, long)]
+        factory: Option<String>,
+        /// Public key base to mine private key additions for
+        #[arg(short = 'b', long)]
+        public_key_base: Option<String>,
+        /// Kind of --factory: solady, sequence, zeframlou, createx or custom.
+        /// Factories assigned by the server come with their own parameters.
+        #[arg(short, long, default_value = "solady")]
+        kind: String,
+        #[arg(long)]
+        proxy_init_code_hash: Option<String>,
+        /// none, senderpacked or createx, defaults to the one of factory kind
+        #[arg(long)]
+        salt_guard: Option<String>,
+        #[arg(long)]
+        guard_sender: Option<String>,
+        #[arg(short, long)]
+        threads: Option<usize>,
+        /// Defaults to the score assigned by the server
+        #[arg(long)]
+        min_score: Option<f64>,
+        #[arg(long, default_value = "0x0000000000000000000000000000000000000000")]
+        requestor_id: String,
+        #[arg(long, default_value = "addresser-cpu")]
+        miner_name: String,
+        /// Seconds between submissions
+        #[arg(long, default_value = "10")]
+        report_interval: u64,
+        /// Stop after given number of seconds
+        #[arg(long)]
+        duration: Option<u64>,
     },
     /// Start web server
     Server {
@@ -266,6 +421,25 @@ struct Cli {
     db: String,
 }
 
+fn create3_params_from_args(
+    kind: &str,
+    proxy_init_code_hash: Option<String>,
+    salt_guard: Option<String>,
+    guard_sender: Option<String>,
+) -> Result<Create3Params, String> {
+    let kind = Create3FactoryKind::from_str(kind)?;
+    let salt_guard = match salt_guard {
+        Some(salt_guard) => SaltGuard::from_str(&salt_guard)?,
+        None => kind.default_salt_guard(),
+    };
+    Ok(Create3Params {
+        proxy_init_code_hash: proxy_init_code_hash
+            .unwrap_or(DEFAULT_PROXY_INIT_CODE_HASH.to_string()),
+        salt_guard,
+        guard_sender,
+    })
+}
+
 fn handle_multipart_error(err: MultipartError, _req: &HttpRequest) -> actix_web::Error {
     log::error!("Multipart error: {}", err);
     actix_web::Error::from(err)
@@ -287,12 +461,15 @@ async fn main() -> std::io::Result<()> {
     match args.cmd {
         Commands::Server { addr, threads } => {
             let conn = create_pg_connection(true).await.unwrap();
+            log::info!("Loaded {} score categories", score_rules().categories.len());
+            let compile_queue = Arc::new(CompileQueue::new(CompileLimits::from_env()));
 
             HttpServer::new(move || {
                 let cors = actix_cors::Cors::permissive();
 
                 let server_data = web::Data::new(Box::new(ServerData {
-                    db_connection: Arc::new(Mutex::new(conn.clone())),
+                    db_connection: conn.clone(),
+                    compile_queue: compile_queue.clone(),
                 }));
                 let client = web::Data::new(Client::new());
                 let session_middleware =
@@ -328,6 +505,7 @@ async fn main() -> std::io::Result<()> {
         }
         Commands::ScoreFancy { last_day } => {
             let conn = create_pg_connection(true).await.unwrap();
+            log::info!("Loaded {} score categories", score_rules().categories.len());
 
             let fancies = if last_day {
                 fancy_list_all(
@@ -360,6 +538,13 @@ async fn main() -> std::io::Result<()> {
                     );
                 }
 
+                // breakdown is stored for every address, also filling rows that predate it
+                let breakdown = score_breakdown(fancy.address, &score);
+                if let Err(e) = fancy_score_upsert(&conn, fancy.address, &breakdown).await {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+
                 let new_price =
                     (score.price_multiplier * get_base_difficulty_price() as f64) as i64;
                 if fancy.score != score.total_score
@@ -386,41 +571,187 @@ async fn main() -> std::io::Result<()> {
             }
             Ok(())
         }
-        Commands::ProcessDeploy { network } => {
+        Commands::DeployWorker {
+            network,
+            concurrency,
+            poll_interval,
+            max_backoff,
+            once,
+        } => {
+            let conn = create_pg_connection(true).await.unwrap();
+            let options = DeployWorkerOptions {
+                networks: network,
+                concurrency,
+                poll_interval: std::time::Duration::from_secs(poll_interval),
+                max_backoff: std::time::Duration::from_secs(max_backoff),
+                once,
+            };
+            if let Err(e) = run_deploy_worker(conn, options).await {
+                log::error!("{}", e);
+                std::process::exit(1)
+            }
+            Ok(())
+        }
+        Commands::ComputeCreate3 {
+            factory,
+            salt,
+            kind,
+            proxy_init_code_hash,
+            salt_guard,
+            guard_sender,
+        } => {
+            let params = match create3_params_from_args(
+                &kind,
+                proxy_init_code_hash,
+                salt_guard,
+                guard_sender,
+            ) {
+                Ok(params) => params,
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let result = compute_create3(&factory, &salt, &params);
+            match result {
+                Ok(hash) => {
+                    log::info!("Computed create3 hash: {}", hash);
+                    println!("{}", hash);
+                }
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            Ok(())
+        }
+        Commands::AddFactory {
+            address,
+            kind,
+            create2_deployer,
+            proxy_init_code_hash,
+            salt_guard,
+            guard_sender,
+        } => {
             let conn = create_pg_connection(true).await.unwrap();
 
-            let contracts = get_all_contracts_by_deploy_status_and_network(
-                &conn,
-                DeployStatus::Requested,
-                network,
-            )
-            .await
-            .unwrap();
-
-            if let Some(contract) = contracts.first() {
-                log::info!("Processing contract: {:#?}", contract);
+            let params = match create3_params_from_args(
+                &kind,
+                proxy_init_code_hash,
+                salt_guard,
+                guard_sender,
+            ) {
+                Ok(params) => params,
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let address = DbAddress::from_str(&address).unwrap();
+            let guard_sender = params
+                .guard_sender
+                .map(|s| DbAddress::from_str(&s).unwrap());
+            let factory = ContractFactoryDbObject {
+                uid: uuid::Uuid::new_v4(),
+                address,
+                added: get_current_utc_time(),
+                user_id: None,
+                // validated above, stored in canonical lowercase form
+                kind: kind.to_lowercase(),
+                proxy_init_code_hash: params.proxy_init_code_hash,
+                salt_guard: params.salt_guard.to_string(),
+                guard_sender,
+                create2_deployer,
+            };
+            match upsert_factory(&conn, &factory).await {
+                Ok(factory) => {
+                    log::info!("Factory registered: {:?}", factory);
+                    Ok(())
+                }
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::AddNetwork {
+            name,
+            chain_id,
+            rpc_url,
+            explorer_url,
+            gas_strategy,
+            gas_multiplier,
+            deployer_key_env,
+            explorer_api_url,
+            explorer_key_env,
+            factory,
+            disabled,
+        } => {
+            let conn = create_pg_connection(true).await.unwrap();
 
-                match handle_fancy_deploy(&conn, contract.clone()).await {
-                    Ok(_) => {
-                        log::info!("Deployment successful");
-                        Ok(())
+            let gas_strategy = match GasStrategy::from_str(&gas_strategy) {
+                Ok(gas_strategy) => gas_strategy,
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let mut factories = Vec::new();
+            for factory in factory {
+                let address = DbAddress::from_str(&factory).unwrap();
+                match get_factory_by_address(&conn, address).await {
+                    Ok(Some(_)) => factories.push(address),
+                    Ok(None) => {
+                        log::error!("Factory {} not registered, use add-factory first", address);
+                        std::process::exit(1);
                     }
                     Err(e) => {
                         log::error!("{}", e);
-                        std::process::exit(1)
+                        std::process::exit(1);
                     }
                 }
-            } else {
-                log::info!("No contracts to process");
-                Ok(())
             }
+            let network = NetworkDbObj {
+                name,
+                chain_id,
+                rpc_urls: rpc_url,
+                explorer_url,
+                gas_strategy: gas_strategy.to_string(),
+                gas_multiplier,
+                deployer_key_env,
+                explorer_api_url,
+                explorer_key_env,
+                enabled: !disabled,
+                added: get_current_utc_time(),
+            };
+
+            let mut trans = conn.begin().await.unwrap();
+            let network = match upsert_network(&mut *trans, &network).await {
+                Ok(network) => network,
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            if !factories.is_empty() {
+                set_network_factories(&mut trans, &network.name, &factories)
+                    .await
+                    .unwrap();
+            }
+            trans.commit().await.unwrap();
+            log::info!("Network registered: {:?}", network);
+            Ok(())
         }
-        Commands::ComputeCreate3 { factory, salt } => {
-            let result = compute_create3_command(&factory, &salt);
+        Commands::ComputeCreate2 {
+            deployer,
+            salt,
+            init_code_hash,
+        } => {
+            let result = compute_create2(&deployer, &salt, &init_code_hash);
             match result {
-                Ok(hash) => {
-                    log::info!("Computed create3 hash: {}", hash);
-                    println!("{}", hash);
+                Ok(addr) => {
+                    log::info!("Computed create2 address: {}", addr);
+                    println!("{}", addr);
                 }
                 Err(e) => {
                     log::error!("{}", e);
@@ -465,12 +796,54 @@ async fn main() -> std::io::Result<()> {
             }
             Ok(())
         }
-        Commands::AddFancyAddress { factory, salt } => {
+        Commands::CombinePrivateKey {
+            private_key_base,
+            private_key_add,
+        } => {
+            match combine_private_key(&private_key_base, &private_key_add) {
+                Ok(private_key) => {
+                    println!("{}", private_key);
+                }
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            Ok(())
+        }
+        Commands::AddFancyAddress {
+            factory,
+            salt,
+            init_code_hash,
+        } => {
             let conn = create_pg_connection(true).await.unwrap();
 
             let factory = web3::types::Address::from_str(&factory).unwrap();
-            let result = match parse_fancy(salt, factory) {
-                Ok(fancy) => fancy,
+            let factory_db = match get_factory_by_address(&conn, DbAddress::wrap(factory)).await {
+                Ok(factory_db) => factory_db,
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let result = match init_code_hash {
+                Some(init_code_hash) => match &factory_db {
+                    Some(factory_db) if factory_db.create2_deployer => {
+                        parse_fancy_create2(salt, factory, init_code_hash)
+                    }
+                    _ => Err(err_custom_create!(
+                        "{:#x} is not a registered CREATE2 deployer, use add-factory --create2-deployer",
+                        factory
+                    )),
+                },
+                None => match &factory_db {
+                    Some(factory_db) => factory_create3_params(factory_db),
+                    None => Ok(Create3Params::default()),
+                }
+                .and_then(|params| parse_fancy_create3(salt, factory, &params)),
+            };
+            let (result, score) = match result {
+                Ok(parsed) => parsed,
                 Err(e) => {
                     log::error!("{}", e);
                     std::process::exit(1);
@@ -484,6 +857,7 @@ async fn main() -> std::io::Result<()> {
                 }
             };
 
+            let address = result.address;
             match insert_fancy_obj(&mut *db_trans, result).await {
                 Ok(_) => (),
                 Err(e) => {
@@ -491,6 +865,14 @@ async fn main() -> std::io::Result<()> {
                     std::process::exit(1);
                 }
             }
+            let breakdown = score_breakdown(address, &score);
+            match fancy_score_upsert(&mut *db_trans, address, &breakdown).await {
+                Ok(_) => (),
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
 
             match db_trans.commit().await {
                 Ok(_) => (),
@@ -502,6 +884,146 @@ async fn main() -> std::io::Result<()> {
 
             Ok(())
         }
+        Commands::GrantTokens {
+            email,
+            amount,
+            note,
+        } => {
+            let conn = create_pg_connection(true).await.unwrap();
+            let mut db_trans = conn.begin().await.unwrap();
+            let user = match get_user_for_update(&mut *db_trans, &email).await {
+                Ok(user) => user,
+                Err(e) => {
+                    log::error!("User {} not found: {}", email, e);
+                    std::process::exit(1);
+                }
+            };
+            let Some(transfer) = amount.checked_abs() else {
+                log::error!("Amount {} out of range", amount);
+                std::process::exit(1);
+            };
+            let balance = match user.tokens.checked_add(amount) {
+                Some(balance) if balance >= 0 => balance,
+                _ => {
+                    log::error!(
+                        "User {} has {} tokens, cannot change them by {}",
+                        email,
+                        user.tokens,
+                        amount
+                    );
+                    std::process::exit(1);
+                }
+            };
+            let (from, to, kind) = if amount >= 0 {
+                (
+                    LedgerAccount::Treasury,
+                    LedgerAccount::User(user.uid),
+                    TokenLedgerKind::Grant,
+                )
+            } else {
+                (
+                    LedgerAccount::User(user.uid),
+                    LedgerAccount::Treasury,
+                    TokenLedgerKind::Adjustment,
+                )
+            };
+            let refs = TokenLedgerRefs {
+                note,
+                ..Default::default()
+            };
+            if let Err(e) = token_transfer(&mut db_trans, from, to, transfer, kind, refs).await {
+                log::error!("{}", e);
+                std::process::exit(1);
+            }
+            db_trans.commit().await.unwrap();
+            log::info!(
+                "User {} tokens changed by {}, balance: {}",
+                email,
+                amount,
+                balance
+            );
+            Ok(())
+        }
+        Commands::ReconcileTokens {} => {
+            let conn = create_pg_connection(true).await.unwrap();
+            let mismatches = get_token_balance_mismatches(&conn).await.unwrap();
+            for mismatch in &mismatches {
+                println!(
+                    "{} {}: tokens {}, ledger {}",
+                    mismatch.uid, mismatch.email, mismatch.tokens, mismatch.ledger_balance
+                );
+            }
+            if !mismatches.is_empty() {
+                log::error!(
+                    "{} users with balance not matching ledger",
+                    mismatches.len()
+                );
+                std::process::exit(1);
+            }
+            log::info!("All balances match the ledger");
+            Ok(())
+        }
+        Commands::Mine {
+            server,
+            factory,
+            public_key_base,
+            kind,
+            proxy_init_code_hash,
+            salt_guard,
+            guard_sender,
+            threads,
+            min_score,
+            requestor_id,
+            miner_name,
+            report_interval,
+            duration,
+        } => {
+            let create3_params = match create3_params_from_args(
+                &kind,
+                proxy_init_code_hash,
+                salt_guard,
+                guard_sender,
+            ) {
+                Ok(params) => params,
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let target = match (factory, public_key_base) {
+                (Some(_), Some(_)) => {
+                    log::error!("Specify either factory or public key base, not both");
+                    std::process::exit(1);
+                }
+                (Some(factory), None) => Some(MineTarget::Create3 {
+                    factory,
+                    params: create3_params,
+                }),
+                (None, Some(public_key_base)) => Some(MineTarget::PublicKeyBase(public_key_base)),
+                (None, None) => None,
+            };
+            let options = MineOptions {
+                server: server.trim_end_matches('/').to_string(),
+                target,
+                threads: threads.unwrap_or(
+                    std::thread::available_parallelism()
+                        .map(|n| n.get())
+                        .unwrap_or(1),
+                ),
+                min_score,
+                requestor_id,
+                miner_name,
+                report_interval: std::time::Duration::from_secs(report_interval.max(1)),
+                duration: duration.map(std::time::Duration::from_secs),
+            };
+            match run_mine(options).await {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
         Commands::Test {} => {
             //test_command(conn).await;
             /*match compile_solc(
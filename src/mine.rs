@@ -0,0 +1,344 @@
+//! CPU cruncher, reference implementation of the mining protocol:
+//! `/api/job/new`, optional `/api/job/work`, `/api/fancy/new_many` and `/api/job/finish`.
+
+use crate::api::fancy::job::{JobWithMinerApi, JobWorkApi};
+use crate::err_custom_create;
+use crate::error::AddressologyError;
+use crate::fancy::{score_fancy, OrderKind, OrderMatcher};
+use crate::hash::{compute_address_command, compute_create3, Create3Params};
+use rand::Rng;
+use serde::Serialize;
+use serde_json::json;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use web3::types::Address;
+
+#[derive(Debug, Clone)]
+pub enum MineTarget {
+    Create3 {
+        factory: String,
+        params: Create3Params,
+    },
+    PublicKeyBase(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct MineOptions {
+    pub server: String,
+    pub target: Option<MineTarget>,
+    pub threads: usize,
+    pub min_score: Option<f64>,
+    pub requestor_id: String,
+    pub miner_name: String,
+    pub report_interval: Duration,
+    pub duration: Option<Duration>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FoundEntry {
+    pub salt: String,
+    pub factory: String,
+    pub address: String,
+}
+
+/// What a single search thread is looking for
+#[derive(Debug, Clone)]
+pub struct SearchSpec {
+    pub target: MineTarget,
+    pub salt_prefix: Vec<u8>,
+    pub min_score: f64,
+    pub patterns: Vec<OrderMatcher>,
+}
+
+impl SearchSpec {
+    /// Tries one random salt, returns entry if it is worth submitting
+    pub fn try_salt<R: Rng>(&self, rng: &mut R) -> Result<Option<FoundEntry>, AddressologyError> {
+        let mut salt = rng.random::<[u8; 32]>();
+        salt[..self.salt_prefix.len()].copy_from_slice(&self.salt_prefix);
+        let salt = format!("0x{}", hex::encode(salt));
+
+        let (address, factory) = match &self.target {
+            MineTarget::Create3 { factory, params } => {
+                (compute_create3(factory, &salt, params)?, factory.clone())
+            }
+            MineTarget::PublicKeyBase(public_key_base) => (
+                compute_address_command(public_key_base, &salt)?,
+                public_key_base.clone(),
+            ),
+        };
+        let addr = Address::from_str(&address)
+            .map_err(|e| err_custom_create!("Failed to parse address {}: {}", address, e))?;
+
+        if score_fancy(addr).total_score >= self.min_score
+            || self.patterns.iter().any(|p| p.matches(addr))
+        {
+            Ok(Some(FoundEntry {
+                salt,
+                factory,
+                address,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+fn search_thread(
+    spec: SearchSpec,
+    hashes: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    found: mpsc::Sender<FoundEntry>,
+) {
+    let mut rng = rand::rng();
+    while !stop.load(Ordering::Relaxed) {
+        for _ in 0..100 {
+            match spec.try_salt(&mut rng) {
+                Ok(Some(entry)) => {
+                    if found.send(entry).is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    log::error!("Search failed: {}", e);
+                    stop.store(true, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+        hashes.fetch_add(100, Ordering::Relaxed);
+    }
+}
+
+async fn post_json<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &str,
+    body: &serde_json::Value,
+) -> Result<T, AddressologyError> {
+    let resp = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| err_custom_create!("Request to {} failed: {}", url, e))?;
+    let status = resp.status();
+    let text = resp
+        .text()
+        .await
+        .map_err(|e| err_custom_create!("Failed to read response from {}: {}", url, e))?;
+    if !status.is_success() {
+        return Err(err_custom_create!("{} returned {}: {}", url, status, text));
+    }
+    serde_json::from_str::<T>(&text)
+        .map_err(|e| err_custom_create!("Failed to parse response from {}: {}", url, e))
+}
+
+async fn submit(
+    client: &reqwest::Client,
+    options: &MineOptions,
+    job_id: uuid::Uuid,
+    entries: Vec<FoundEntry>,
+    hashes: u64,
+) -> Result<(), AddressologyError> {
+    let count = entries.len();
+    let resp = post_json::<serde_json::Value>(
+        client,
+        &format!("{}/api/fancy/new_many", options.server),
+        &json!({
+            "data": entries,
+            "extra": {
+                "jobId": job_id,
+                "reportedHashes": hashes as f64,
+                "reportedCost": 0.0,
+            }
+        }),
+    )
+    .await?;
+    log::info!(
+        "Submitted {} entries, hashes: {}, response: {}",
+        count,
+        hashes,
+        resp
+    );
+    Ok(())
+}
+
+pub async fn run_mine(options: MineOptions) -> Result<(), AddressologyError> {
+    let client = reqwest::Client::new();
+
+    let job = post_json::<JobWithMinerApi>(
+        &client,
+        &format!("{}/api/job/new", options.server),
+        &json!({
+            "miner": {
+                "provName": options.miner_name,
+            },
+            "cruncherVer": format!("addresser-cpu {}", env!("CARGO_PKG_VERSION")),
+            "requestorId": options.requestor_id,
+        }),
+    )
+    .await?;
+    log::info!("Started job {}", job.uid);
+
+    // without explicit target ask the server what is in demand
+    let spec = match &options.target {
+        Some(target) => SearchSpec {
+            target: target.clone(),
+            salt_prefix: Vec::new(),
+            min_score: options
+                .min_score
+                .unwrap_or(crate::config::get_min_accepted_score()),
+            patterns: Vec::new(),
+        },
+        None => {
+            let work = post_json::<JobWorkApi>(
+                &client,
+                &format!("{}/api/job/work", options.server),
+                &json!({ "jobId": job.uid }),
+            )
+            .await?;
+            log::info!("Received work assignment: {:?}", work);
+            let target = match (work.factory, work.public_key_base) {
+                (Some(factory), _) => MineTarget::Create3 {
+                    factory: format!("{:#x}", factory.addr()),
+                    params: work
+                        .factory_params
+                        .ok_or_else(|| err_custom_create!("Factory assigned without parameters"))?
+                        .to_create3_params()?,
+                },
+                (None, Some(public_key_base)) => MineTarget::PublicKeyBase(public_key_base),
+                (None, None) => return Err(err_custom_create!("Work assignment without target")),
+            };
+            let patterns = work
+                .patterns
+                .iter()
+                .filter_map(|p| {
+                    OrderMatcher::new(OrderKind::from_str(&p.kind).ok()?, &p.pattern).ok()
+                })
+                .collect();
+            SearchSpec {
+                target,
+                salt_prefix: hex::decode(work.salt_prefix.trim_start_matches("0x"))
+                    .map_err(|e| err_custom_create!("Invalid salt prefix: {}", e))?,
+                min_score: options
+                    .min_score
+                    .unwrap_or(work.min_score)
+                    .max(work.min_score),
+                patterns,
+            }
+        }
+    };
+
+    let hashes = Arc::new(AtomicU64::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel::<FoundEntry>();
+    let mut handles = Vec::new();
+    for _ in 0..options.threads.max(1) {
+        let spec = spec.clone();
+        let hashes = hashes.clone();
+        let stop = stop.clone();
+        let sender = sender.clone();
+        handles.push(std::thread::spawn(move || {
+            search_thread(spec, hashes, stop, sender)
+        }));
+    }
+    drop(sender);
+
+    {
+        let stop = stop.clone();
+        actix_rt::spawn(async move {
+            if actix_rt::signal::ctrl_c().await.is_ok() {
+                log::info!("Stopping mining...");
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+    }
+
+    let started = Instant::now();
+    let mut result = Ok(());
+    loop {
+        tokio::time::sleep(options.report_interval).await;
+        if let Some(duration) = options.duration {
+            if started.elapsed() >= duration {
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+        let finished = stop.load(Ordering::Relaxed);
+        let entries = receiver.try_iter().collect::<Vec<_>>();
+        let total = hashes.load(Ordering::Relaxed);
+        log::info!(
+            "Hashes: {}, speed: {:.0} H/s, found: {}",
+            total,
+            total as f64 / started.elapsed().as_secs_f64(),
+            entries.len()
+        );
+        if let Err(e) = submit(&client, &options, job.uid, entries, total).await {
+            log::error!("{}", e);
+            stop.store(true, Ordering::Relaxed);
+            result = Err(e);
+            break;
+        }
+        if finished {
+            break;
+        }
+    }
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    post_json::<serde_json::Value>(
+        &client,
+        &format!("{}/api/job/finish/{}", options.server, job.uid),
+        &json!({}),
+    )
+    .await?;
+    log::info!("Job {} finished", job.uid);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fancy::parse_fancy_create3;
+
+    #[test]
+    fn test_try_salt() {
+        let factory = "0x9e3f8eae49e442a323ef2094f277bf62752e6995";
+        let spec = SearchSpec {
+            target: MineTarget::Create3 {
+                factory: factory.to_string(),
+                params: Create3Params::default(),
+            },
+            salt_prefix: vec![0xde, 0xad],
+            min_score: 0.0,
+            patterns: Vec::new(),
+        };
+        let mut rng = rand::rng();
+        let entry = spec.try_salt(&mut rng).unwrap().unwrap();
+        assert!(entry.salt.starts_with("0xdead"));
+        let (parsed, _) = parse_fancy_create3(
+            entry.salt.clone(),
+            Address::from_str(factory).unwrap(),
+            &Create3Params::default(),
+        )
+        .unwrap();
+        assert_eq!(format!("{:#x}", parsed.address.addr()), entry.address);
+
+        let strict = SearchSpec {
+            min_score: f64::MAX,
+            ..spec.clone()
+        };
+        assert!(strict.try_salt(&mut rng).unwrap().is_none());
+
+        // order patterns are submitted regardless of score
+        let with_pattern = SearchSpec {
+            patterns: vec![OrderMatcher::new(OrderKind::Regex, ".").unwrap()],
+            ..strict
+        };
+        assert!(with_pattern.try_salt(&mut rng).unwrap().is_some());
+    }
+}
@@ -1,7 +1,21 @@
+pub mod libraries;
+pub mod manager;
+pub mod queue;
+pub mod version;
+
+use crate::config::{get_compile_memory_limit_mb, get_compile_timeout, get_solc_lib_dir};
 use crate::err_custom_create;
 use crate::error::AddressologyError;
+use crate::solc::libraries::resolve_imports;
+use crate::solc::manager::{resolve_solc, source_requirements};
+use crate::solc::queue::CompileError;
+use crate::solc::version::SolcVersion;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::io::AsyncWriteExt;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -13,15 +27,185 @@ pub struct SolidityBytecode {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SolidityEvm {
     #[serde(rename = "bytecode")]
     pub bytecode: SolidityBytecode,
+    pub deployed_bytecode: Option<SolidityBytecode>,
+    pub method_identifiers: Option<BTreeMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SolidityContract {
     pub metadata: String,
     pub evm: SolidityEvm,
+    pub abi: Option<serde_json::Value>,
+    pub storage_layout: Option<serde_json::Value>,
+}
+
+/// Outputs always requested, the deployment needs them
+const DEFAULT_OUTPUTS: [&str; 2] = ["metadata", "evm.bytecode"];
+
+const ALLOWED_OUTPUTS: [&str; 5] = [
+    "abi",
+    "evm.deployedBytecode",
+    "evm.methodIdentifiers",
+    "storageLayout",
+    "devdoc",
+];
+
+const EVM_VERSIONS: [&str; 14] = [
+    "homestead",
+    "tangerineWhistle",
+    "spuriousDragon",
+    "byzantium",
+    "constantinople",
+    "petersburg",
+    "istanbul",
+    "berlin",
+    "london",
+    "paris",
+    "shanghai",
+    "cancun",
+    "prague",
+    "osaka",
+];
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizerSettings {
+    pub enabled: bool,
+    pub runs: u32,
+}
+
+impl Default for OptimizerSettings {
+    fn default() -> Self {
+        OptimizerSettings {
+            enabled: true,
+            runs: 2000,
+        }
+    }
+}
+
+/// Settings passed to solc, stored with the contract so the build can be reproduced
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompilerSettings {
+    #[serde(default)]
+    pub optimizer: OptimizerSettings,
+    /// Default of the compiler version when not set
+    pub evm_version: Option<String>,
+    #[serde(rename = "viaIR")]
+    pub via_ir: Option<bool>,
+    #[serde(default)]
+    pub remappings: Vec<String>,
+    /// Source file -> library name -> address
+    #[serde(default)]
+    pub libraries: BTreeMap<String, BTreeMap<String, String>>,
+    /// Extra outputs on top of metadata and bytecode
+    #[serde(default)]
+    pub output_selection: Vec<String>,
+    /// Versions of vendored packages used for imports, latest installed when not pinned
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
+}
+
+impl CompilerSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(evm_version) = &self.evm_version {
+            if !EVM_VERSIONS.contains(&evm_version.as_str()) {
+                return Err(format!("Unknown evm version: {}", evm_version));
+            }
+        }
+        for remapping in &self.remappings {
+            let target = remapping
+                .split_once('=')
+                .map(|(_, target)| target)
+                .ok_or_else(|| format!("Remapping has to be prefix=target: {}", remapping))?;
+            if target.starts_with('/') || target.split('/').any(|part| part == "..") {
+                return Err(format!(
+                    "Remapping target has to be relative: {}",
+                    remapping
+                ));
+            }
+        }
+        for (file, libraries) in &self.libraries {
+            for (name, address) in libraries {
+                let valid = address.len() == 42
+                    && address.starts_with("0x")
+                    && address[2..].chars().all(|c| c.is_ascii_hexdigit());
+                if !valid {
+                    return Err(format!(
+                        "Invalid address of library {}:{}: {}",
+                        file, name, address
+                    ));
+                }
+            }
+        }
+        for (package, version) in &self.dependencies {
+            let valid_name = |s: &str| {
+                !s.is_empty()
+                    && s.chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "@._-/".contains(c))
+                    && !s.split('/').any(|part| part.is_empty() || part == "..")
+            };
+            if !valid_name(package) || !valid_name(version) || version.contains('/') {
+                return Err(format!("Invalid dependency {}@{}", package, version));
+            }
+        }
+        for output in &self.output_selection {
+            if !ALLOWED_OUTPUTS.contains(&output.as_str())
+                && !DEFAULT_OUTPUTS.contains(&output.as_str())
+            {
+                return Err(format!("Output not supported: {}", output));
+            }
+        }
+        Ok(())
+    }
+
+    /// Standard JSON input of solc
+    pub fn solc_input(&self, sources: BTreeMap<String, String>) -> serde_json::Value {
+        let mut outputs = DEFAULT_OUTPUTS.map(String::from).to_vec();
+        for output in &self.output_selection {
+            if !outputs.contains(output) {
+                outputs.push(output.clone());
+            }
+        }
+        // literal sources are embedded in the metadata, so verification has every import
+        let mut settings = json!({
+            "optimizer": self.optimizer,
+            "metadata": {
+                "useLiteralContent": true
+            },
+            "outputSelection": {
+                "*": {
+                    "*": outputs
+                }
+            }
+        });
+        if let Some(evm_version) = &self.evm_version {
+            settings["evmVersion"] = json!(evm_version);
+        }
+        if let Some(via_ir) = self.via_ir {
+            settings["viaIR"] = json!(via_ir);
+        }
+        if !self.remappings.is_empty() {
+            settings["remappings"] = json!(self.remappings);
+        }
+        if !self.libraries.is_empty() {
+            settings["libraries"] = json!(self.libraries);
+        }
+        let sources = sources
+            .into_iter()
+            .map(|(name, content)| (name, json!({ "content": content })))
+            .collect::<serde_json::Map<_, _>>();
+        json!({
+            "language": "Solidity",
+            "sources": sources,
+            "settings": settings,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -43,89 +227,110 @@ pub struct SolidityError {
 pub struct SolidityJsonResponse {
     pub errors: Option<Vec<SolidityError>>,
     pub contracts: Option<BTreeMap<String, SoliditySourceFile>>,
+    /// Not part of solc output, filled with the version used
+    #[serde(default)]
+    pub compiler_version: Option<SolcVersion>,
+    /// Not part of solc output, versions of vendored packages the imports were resolved from
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
 }
 
-pub async fn compile_solc(
-    sources: BTreeMap<String, String>,
-    solidity_version: &str,
-) -> Result<SolidityJsonResponse, AddressologyError> {
-    let bin = if solidity_version == "0.8.28" {
-        if cfg!(target_os = "linux") {
-            "/addressology/bin/solc_0.8.28"
-        } else if cfg!(target_os = "windows") {
-            ".\\solc-windows.exe"
-        } else {
-            return Err(err_custom_create!(
-                "Unsupported solidity version: {}",
-                solidity_version
-            ));
-        }
-    } else {
-        return Err(err_custom_create!(
-            "Unsupported solidity version: {}",
-            solidity_version
-        ));
-    };
+/// Standard-json input together with the compiler resolved for it
+#[derive(Debug, Clone)]
+pub struct SolcJob {
+    pub version: SolcVersion,
+    pub bin: PathBuf,
+    pub input: String,
+    /// Vendored packages added to the sources
+    pub dependencies: BTreeMap<String, String>,
+}
 
-    let mut cmd = match tokio::process::Command::new(bin)
-        .arg("--standard-json")
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-    {
-        Ok(cmd) => cmd,
-        Err(err) => return Err(err_custom_create!("Error starting solc: {} {}", bin, err)),
-    };
-    let optimized_str = "true";
-    let optimizer_runs_str = "2000";
-    let sol_input_json = r#"
-{
-    "language": "Solidity",
-    "sources": {
-    },
-    "settings": {
-        "optimizer": {
-            "enabled": %ENABLED%,
-            "runs": %RUNS%
-        },
-        "outputSelection": {
-            "*": {
-                "*": [
-                    "metadata",
-                    "evm.bytecode"
-                ]
-            }
-        }
+impl SolcJob {
+    /// Identifies the job output, same input compiled with the same version gives the same result
+    pub fn cache_key(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.version.to_string().as_bytes());
+        hasher.update(b"\n");
+        hasher.update(self.input.as_bytes());
+        hex::encode(hasher.finalize())
     }
 }
-"#
-    .replace("%ENABLED%", optimized_str)
-    .replace("%RUNS%", optimizer_runs_str);
 
-    let mut sol_input_json = serde_json::from_str::<serde_json::Value>(&sol_input_json)
-        .map_err(|err| err_custom_create!("Error parsing solc input json: {}", err))?;
-
-    for (source_name, source_code) in sources {
+/// Validates settings, adds imported library files and resolves solc matching pragmas
+/// of the sources, or explicitly requested version
+pub async fn prepare_solc_job(
+    mut sources: BTreeMap<String, String>,
+    solidity_version: Option<&str>,
+    settings: &CompilerSettings,
+    allow_download: bool,
+) -> Result<SolcJob, CompileError> {
+    settings
+        .validate()
+        .map_err(|e| CompileError::InvalidInput(format!("Invalid compiler settings: {}", e)))?;
+    let lib_dir = PathBuf::from(get_solc_lib_dir());
+    let dependencies = resolve_imports(&lib_dir, &mut sources, settings).await?;
+    let requirements = source_requirements(&sources, solidity_version)
+        .map_err(|e| CompileError::InvalidInput(e.inner.to_string()))?;
+    let (version, bin) = resolve_solc(&requirements, allow_download).await?;
+    for source_name in sources.keys() {
         log::info!("Compiling source: {}", source_name);
-        sol_input_json["sources"][source_name]["content"] = serde_json::Value::String(source_code);
     }
+    Ok(SolcJob {
+        version,
+        bin,
+        input: settings.solc_input(sources).to_string(),
+        dependencies,
+    })
+}
 
-    {
-        let stdin = cmd
-            .stdin
-            .as_mut()
-            .ok_or_else(|| err_custom_create!("Error getting stdin"))?;
+fn solc_command(bin: &Path) -> tokio::process::Command {
+    let memory_limit_mb = get_compile_memory_limit_mb();
+    if cfg!(unix) && memory_limit_mb > 0 {
+        // limit address space of solc only, the server itself is not affected
+        let mut cmd = tokio::process::Command::new("/bin/sh");
+        cmd.arg("-c")
+            .arg(r#"ulimit -v "$1" && exec "$0" --standard-json"#)
+            .arg(bin)
+            .arg((memory_limit_mb * 1024).to_string());
+        cmd
+    } else {
+        let mut cmd = tokio::process::Command::new(bin);
+        cmd.arg("--standard-json");
+        cmd
+    }
+}
 
-        stdin
-            .write_all(sol_input_json.to_string().as_bytes())
+/// Runs solc on prepared job, killing it when it exceeds the compile timeout
+pub async fn run_solc(job: &SolcJob) -> Result<SolidityJsonResponse, AddressologyError> {
+    log::info!("Using solc {} from {}", job.version, job.bin.display());
+    let mut cmd = solc_command(&job.bin)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| err_custom_create!("Error starting solc: {} {}", job.bin.display(), err))?;
+
+    let timeout = Duration::from_secs(get_compile_timeout());
+    let run = async {
+        {
+            let stdin = cmd
+                .stdin
+                .as_mut()
+                .ok_or_else(|| err_custom_create!("Error getting stdin"))?;
+
+            stdin
+                .write_all(job.input.as_bytes())
+                .await
+                .map_err(|err| err_custom_create!("Error writing to stdin: {}", err))?;
+        }
+        cmd.wait_with_output()
             .await
-            .map_err(|err| err_custom_create!("Error writing to stdin: {}", err))?;
-    }
-    let output = cmd
-        .wait_with_output()
-        .await
-        .map_err(|err| err_custom_create!("Error waiting for solc: {}", err))?;
+            .map_err(|err| err_custom_create!("Error waiting for solc: {}", err))
+    };
+    let output = tokio::time::timeout(timeout, run).await.map_err(|_| {
+        err_custom_create!("Compilation exceeded time limit of {}s", timeout.as_secs())
+    })??;
 
     #[allow(clippy::match_single_binding)]
     match output {
@@ -141,9 +346,11 @@ pub async fn compile_solc(
                 ));
             }
 
-            log::info!("{}", String::from_utf8_lossy(&stdout));
+            log::debug!("{}", String::from_utf8_lossy(&stdout));
             match serde_json::from_slice::<SolidityJsonResponse>(stdout.as_slice()) {
-                Ok(json) => {
+                Ok(mut json) => {
+                    json.compiler_version = Some(job.version);
+                    json.dependencies = job.dependencies.clone();
                     if let Some(_errors) = &json.errors {
                         log::info!("Solidity compilation failed");
                     } else if let Some(contracts_map) = &json.contracts {
@@ -263,3 +470,63 @@ pub async fn compile_solc(
 
     Ok(())
 }*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solc_input() {
+        let sources = BTreeMap::from([("A.sol".to_string(), "contract A {}".to_string())]);
+        let input = CompilerSettings::default().solc_input(sources.clone());
+        assert_eq!(input["settings"]["optimizer"]["runs"], 2000);
+        assert_eq!(
+            input["settings"]["outputSelection"]["*"]["*"],
+            json!(["metadata", "evm.bytecode"])
+        );
+        assert_eq!(input["sources"]["A.sol"]["content"], "contract A {}");
+        assert!(input["settings"].get("viaIR").is_none());
+        assert_eq!(input["settings"]["metadata"]["useLiteralContent"], true);
+
+        let settings = serde_json::from_value::<CompilerSettings>(json!({
+            "optimizer": {"enabled": false, "runs": 200},
+            "evmVersion": "paris",
+            "viaIR": true,
+            "remappings": ["@oz/=lib/openzeppelin/"],
+            "libraries": {"A.sol": {"Math": "0x1111111111111111111111111111111111111111"}},
+            "outputSelection": ["abi", "evm.deployedBytecode", "metadata"]
+        }))
+        .unwrap();
+        settings.validate().unwrap();
+        let input = settings.solc_input(sources);
+        assert_eq!(input["settings"]["optimizer"]["enabled"], false);
+        assert_eq!(input["settings"]["evmVersion"], "paris");
+        assert_eq!(input["settings"]["viaIR"], true);
+        assert_eq!(
+            input["settings"]["libraries"]["A.sol"]["Math"],
+            "0x1111111111111111111111111111111111111111"
+        );
+        assert_eq!(
+            input["settings"]["outputSelection"]["*"]["*"],
+            json!(["metadata", "evm.bytecode", "abi", "evm.deployedBytecode"])
+        );
+    }
+
+    #[test]
+    fn test_compiler_settings_validate() {
+        let invalid = [
+            json!({"evmVersion": "future"}),
+            json!({"remappings": ["no-target"]}),
+            json!({"remappings": ["@oz/=/etc/"]}),
+            json!({"remappings": ["@oz/=lib/../../etc/"]}),
+            json!({"libraries": {"A.sol": {"Math": "0x1234"}}}),
+            json!({"outputSelection": ["evm.assembly"]}),
+            json!({"dependencies": {"@openzeppelin/contracts": "../5.1.0"}}),
+            json!({"dependencies": {"../contracts": "5.1.0"}}),
+        ];
+        for settings in invalid {
+            let settings = serde_json::from_value::<CompilerSettings>(settings.clone()).unwrap();
+            assert!(settings.validate().is_err(), "{:?}", settings);
+        }
+    }
+}
@@ -0,0 +1,378 @@
+//! Vendored solidity packages, e.g. `@openzeppelin/contracts`.
+//! Each version of a package is unpacked to `<lib dir>/<name>@<version>`, imports of compiled
+//! sources are resolved recursively and added to standard-json sources under their import path.
+
+use crate::config::get_solc_lib_dir;
+use crate::err_custom_create;
+use crate::error::AddressologyError;
+use crate::solc::queue::CompileError;
+use crate::solc::version::{strip_comments, SolcVersion};
+use crate::solc::CompilerSettings;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Library files added to a single compilation at most
+const MAX_RESOLVED_SOURCES: usize = 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SolidityLibrary {
+    pub name: String,
+    /// Oldest first
+    pub versions: Vec<String>,
+}
+
+/// `solmate@6.2.0` -> (`solmate`, `6.2.0`)
+fn parse_package_dir(name: &str) -> Option<(&str, &str)> {
+    let (package, version) = name.rsplit_once('@')?;
+    if package.is_empty() || version.is_empty() {
+        return None;
+    }
+    Some((package, version))
+}
+
+fn sort_versions(versions: &mut [String]) {
+    versions.sort_by_key(|v| (SolcVersion::from_str(v).ok(), v.clone()));
+}
+
+async fn read_dir_names(dir: &Path) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::debug!("Cannot read library directory {}: {}", dir.display(), e);
+            return res;
+        }
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if entry.file_type().await.is_ok_and(|t| t.is_dir()) {
+            if let Some(name) = entry.file_name().to_str() {
+                res.push(name.to_string());
+            }
+        }
+    }
+    res
+}
+
+/// Package name -> installed versions, scoped packages are kept in their scope directory
+pub async fn installed_libraries(dir: &Path) -> BTreeMap<String, Vec<String>> {
+    let mut res: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for name in read_dir_names(dir).await {
+        if name.starts_with('@') {
+            for scoped in read_dir_names(&dir.join(&name)).await {
+                if let Some((package, version)) = parse_package_dir(&scoped) {
+                    res.entry(format!("{}/{}", name, package))
+                        .or_default()
+                        .push(version.to_string());
+                }
+            }
+        } else if let Some((package, version)) = parse_package_dir(&name) {
+            res.entry(package.to_string())
+                .or_default()
+                .push(version.to_string());
+        }
+    }
+    for versions in res.values_mut() {
+        sort_versions(versions);
+    }
+    res
+}
+
+pub async fn list_libraries() -> Vec<SolidityLibrary> {
+    installed_libraries(Path::new(&get_solc_lib_dir()))
+        .await
+        .into_iter()
+        .map(|(name, versions)| SolidityLibrary { name, versions })
+        .collect()
+}
+
+/// Paths of all import statements in the source
+pub fn parse_imports(source: &str) -> Vec<String> {
+    lazy_static! {
+        static ref IMPORT: Regex =
+            Regex::new(r#"import\s+(?:[^;"']*?\s+from\s+)?["']([^"']+)["']"#).unwrap();
+    }
+    IMPORT
+        .captures_iter(&strip_comments(source))
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+/// Source unit name of the import, relative imports are resolved against the importing unit.
+/// None when relative import points above the root.
+fn normalize_import(importer: &str, path: &str) -> Option<String> {
+    if !path.starts_with("./") && !path.starts_with("../") {
+        return Some(path.to_string());
+    }
+    let mut parts = importer.split('/').collect::<Vec<_>>();
+    parts.pop();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Applies `[context:]prefix=target` remappings the way solc does, longest prefix wins
+fn apply_remappings(remappings: &[String], importer: &str, path: &str) -> String {
+    let mut best: Option<(&str, &str)> = None;
+    for remapping in remappings {
+        let Some((from, target)) = remapping.split_once('=') else {
+            continue;
+        };
+        let (context, prefix) = from.split_once(':').unwrap_or(("", from));
+        if !importer.starts_with(context) || prefix.is_empty() || !path.starts_with(prefix) {
+            continue;
+        }
+        if best.is_none_or(|(best_prefix, _)| prefix.len() > best_prefix.len()) {
+            best = Some((prefix, target));
+        }
+    }
+    match best {
+        Some((prefix, target)) => format!("{}{}", target, &path[prefix.len()..]),
+        None => path.to_string(),
+    }
+}
+
+/// Splits import path into installed package and path inside it
+fn split_package<'a>(
+    path: &'a str,
+    libraries: &BTreeMap<String, Vec<String>>,
+) -> Option<(&'a str, &'a str)> {
+    libraries
+        .keys()
+        .filter_map(|package| {
+            let rest = path.strip_prefix(package.as_str())?.strip_prefix('/')?;
+            Some((&path[..package.len()], rest))
+        })
+        .max_by_key(|(package, _)| package.len())
+}
+
+async fn read_library_file(root: &Path, rest: &str) -> Result<Option<String>, AddressologyError> {
+    if rest.split('/').any(|part| part == "..") {
+        return Ok(None);
+    }
+    let root = match tokio::fs::canonicalize(root).await {
+        Ok(root) => root,
+        Err(_) => return Ok(None),
+    };
+    let file = match tokio::fs::canonicalize(root.join(rest)).await {
+        Ok(file) => file,
+        Err(_) => return Ok(None),
+    };
+    // symlinks must not lead outside of the package
+    if !file.starts_with(&root) {
+        return Ok(None);
+    }
+    tokio::fs::read_to_string(&file)
+        .await
+        .map(Some)
+        .map_err(|e| err_custom_create!("Failed to read {}: {}", file.display(), e))
+}
+
+/// Adds library files imported by sources, directly or through other library files.
+/// Returns versions of packages used. Imports that cannot be resolved are left for solc to report.
+pub async fn resolve_imports(
+    dir: &Path,
+    sources: &mut BTreeMap<String, String>,
+    settings: &CompilerSettings,
+) -> Result<BTreeMap<String, String>, CompileError> {
+    let libraries = installed_libraries(dir).await;
+    for (package, version) in &settings.dependencies {
+        if !libraries.get(package).is_some_and(|v| v.contains(version)) {
+            return Err(CompileError::InvalidInput(format!(
+                "Library {}@{} is not available",
+                package, version
+            )));
+        }
+    }
+
+    let mut used = BTreeMap::new();
+    if libraries.is_empty() {
+        return Ok(used);
+    }
+    let mut resolved = 0;
+    let mut pending = sources.keys().cloned().collect::<VecDeque<_>>();
+    while let Some(unit) = pending.pop_front() {
+        for import in parse_imports(&sources[&unit]) {
+            let Some(path) = normalize_import(&unit, &import) else {
+                continue;
+            };
+            let path = apply_remappings(&settings.remappings, &unit, &path);
+            if sources.contains_key(&path) {
+                continue;
+            }
+            let Some((package, rest)) = split_package(&path, &libraries) else {
+                continue;
+            };
+            let version = match settings.dependencies.get(package) {
+                Some(version) => version,
+                None => match libraries[package].last() {
+                    Some(version) => version,
+                    None => continue,
+                },
+            };
+            let root = dir.join(format!("{}@{}", package, version));
+            let Some(content) = read_library_file(&root, rest)
+                .await
+                .map_err(CompileError::Failed)?
+            else {
+                continue;
+            };
+            resolved += 1;
+            if resolved > MAX_RESOLVED_SOURCES {
+                return Err(CompileError::InvalidInput(format!(
+                    "Too many imported files, at most {} allowed",
+                    MAX_RESOLVED_SOURCES
+                )));
+            }
+            used.insert(package.to_string(), version.clone());
+            sources.insert(path.clone(), content);
+            pending.push_back(path);
+        }
+    }
+    Ok(used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_imports() {
+        let source = r#"
+            import "@openzeppelin/contracts/token/ERC20/ERC20.sol";
+            import {Ownable} from '@openzeppelin/contracts/access/Ownable.sol';
+            import * as Math from "./Math.sol";
+            import "../utils/Strings.sol" as Strings;
+            // import "commented/Out.sol";
+        "#;
+        assert_eq!(
+            parse_imports(source),
+            [
+                "@openzeppelin/contracts/token/ERC20/ERC20.sol",
+                "@openzeppelin/contracts/access/Ownable.sol",
+                "./Math.sol",
+                "../utils/Strings.sol",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_paths() {
+        let importer = "@openzeppelin/contracts/token/ERC20/ERC20.sol";
+        assert_eq!(
+            normalize_import(importer, "../../utils/Context.sol").as_deref(),
+            Some("@openzeppelin/contracts/utils/Context.sol")
+        );
+        assert_eq!(
+            normalize_import(importer, "./IERC20.sol").as_deref(),
+            Some("@openzeppelin/contracts/token/ERC20/IERC20.sol")
+        );
+        assert_eq!(normalize_import("A.sol", "../B.sol"), None);
+
+        let remappings = [
+            "@oz/=@openzeppelin/contracts/".to_string(),
+            "@oz/token/=@openzeppelin/contracts/token/ERC20/".to_string(),
+            "other.sol:@oz/=@openzeppelin/contracts-upgradeable/".to_string(),
+        ];
+        assert_eq!(
+            apply_remappings(&remappings, "A.sol", "@oz/access/Ownable.sol"),
+            "@openzeppelin/contracts/access/Ownable.sol"
+        );
+        assert_eq!(
+            apply_remappings(&remappings, "A.sol", "@oz/token/ERC20.sol"),
+            "@openzeppelin/contracts/token/ERC20/ERC20.sol"
+        );
+        assert_eq!(apply_remappings(&remappings, "A.sol", "B.sol"), "B.sol");
+
+        let libraries = BTreeMap::from([
+            (
+                "@openzeppelin/contracts".to_string(),
+                vec!["5.1.0".to_string()],
+            ),
+            ("solmate".to_string(), vec!["6.2.0".to_string()]),
+        ]);
+        assert_eq!(
+            split_package("@openzeppelin/contracts/access/Ownable.sol", &libraries),
+            Some(("@openzeppelin/contracts", "access/Ownable.sol"))
+        );
+        assert_eq!(
+            split_package("@openzeppelin/contracts-upgradeable/A.sol", &libraries),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_imports() {
+        let dir = std::env::temp_dir().join(format!("solc-lib-{}", uuid::Uuid::new_v4()));
+        let write = |path: &str, content: &str| {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(
+            "@openzeppelin/contracts@5.0.0/access/Ownable.sol",
+            "contract Ownable {}",
+        );
+        write(
+            "@openzeppelin/contracts@5.1.0/access/Ownable.sol",
+            r#"import {Context} from "../utils/Context.sol";"#,
+        );
+        write(
+            "@openzeppelin/contracts@5.1.0/utils/Context.sol",
+            "contract Context {}",
+        );
+        write("secret.sol", "contract Secret {}");
+
+        let libraries = installed_libraries(&dir).await;
+        assert_eq!(
+            libraries.get("@openzeppelin/contracts"),
+            Some(&vec!["5.0.0".to_string(), "5.1.0".to_string()])
+        );
+
+        let mut sources = BTreeMap::from([(
+            "A.sol".to_string(),
+            r#"import "@openzeppelin/contracts/access/Ownable.sol";
+            import "@openzeppelin/contracts/../secret.sol";
+            import "./Missing.sol";"#
+                .to_string(),
+        )]);
+        let used = resolve_imports(&dir, &mut sources, &CompilerSettings::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            used,
+            BTreeMap::from([("@openzeppelin/contracts".to_string(), "5.1.0".to_string())])
+        );
+        assert_eq!(
+            sources.keys().collect::<Vec<_>>(),
+            [
+                "@openzeppelin/contracts/access/Ownable.sol",
+                "@openzeppelin/contracts/utils/Context.sol",
+                "A.sol",
+            ]
+        );
+
+        let settings = CompilerSettings {
+            dependencies: BTreeMap::from([(
+                "@openzeppelin/contracts".to_string(),
+                "4.9.0".to_string(),
+            )]),
+            ..Default::default()
+        };
+        assert!(resolve_imports(&dir, &mut sources, &settings)
+            .await
+            .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,293 @@
+//! Local cache of solc binaries.
+//! Binaries are named `solc-<version>` and can be pre-seeded for offline use,
+//! missing ones are downloaded from the official solc-bin list and verified by SHA-256.
+
+use crate::config::{get_solc_bin_url, get_solc_dir, get_solc_offline};
+use crate::err_custom_create;
+use crate::error::AddressologyError;
+use crate::solc::queue::CompileError;
+use crate::solc::version::{parse_pragmas, resolve_version, SolcVersion, SolcVersionReq};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+const REMOTE_LIST_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SolcBuild {
+    pub path: String,
+    pub version: String,
+    pub prerelease: Option<String>,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SolcBuildList {
+    builds: Vec<SolcBuild>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SolcVersions {
+    pub installed: Vec<SolcVersion>,
+    /// Empty when running offline
+    pub available: Vec<SolcVersion>,
+}
+
+lazy_static! {
+    static ref REMOTE_BUILDS: Mutex<Option<(Instant, Vec<SolcBuild>)>> = Mutex::new(None);
+}
+
+/// Directory name of the platform in solc-bin
+fn solc_platform() -> Option<&'static str> {
+    if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        Some("linux-amd64")
+    } else if cfg!(target_os = "macos") {
+        Some("macosx-amd64")
+    } else if cfg!(target_os = "windows") {
+        Some("windows-amd64")
+    } else {
+        None
+    }
+}
+
+fn binary_name(version: &SolcVersion) -> String {
+    if cfg!(target_os = "windows") {
+        format!("solc-{}.exe", version)
+    } else {
+        format!("solc-{}", version)
+    }
+}
+
+/// Version of a cached binary, `solc_<version>` is accepted for older installations
+fn parse_binary_name(name: &str) -> Option<SolcVersion> {
+    let name = name.strip_suffix(".exe").unwrap_or(name);
+    let version = name
+        .strip_prefix("solc-")
+        .or_else(|| name.strip_prefix("solc_"))?;
+    SolcVersion::from_str(version).ok()
+}
+
+pub async fn installed_versions(dir: &Path) -> BTreeMap<SolcVersion, PathBuf> {
+    let mut res = BTreeMap::new();
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::debug!("Cannot read solc directory {}: {}", dir.display(), e);
+            return res;
+        }
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Some(version) = entry.file_name().to_str().and_then(parse_binary_name) {
+            res.insert(version, entry.path());
+        }
+    }
+    res
+}
+
+/// Release builds from solc-bin list, cached in memory
+async fn remote_builds() -> Result<Vec<SolcBuild>, AddressologyError> {
+    let mut cached = REMOTE_BUILDS.lock().await;
+    if let Some((fetched, builds)) = cached.as_ref() {
+        if fetched.elapsed() < REMOTE_LIST_TTL {
+            return Ok(builds.clone());
+        }
+    }
+    let platform =
+        solc_platform().ok_or_else(|| err_custom_create!("No solc builds for this platform"))?;
+    let url = format!("{}/{}/list.json", get_solc_bin_url(), platform);
+    let list = reqwest::get(&url)
+        .await
+        .map_err(|e| err_custom_create!("Failed to get {}: {}", url, e))?
+        .error_for_status()
+        .map_err(|e| err_custom_create!("Failed to get {}: {}", url, e))?
+        .text()
+        .await
+        .map_err(|e| err_custom_create!("Failed to read {}: {}", url, e))?;
+    let builds = serde_json::from_str::<SolcBuildList>(&list)
+        .map_err(|e| err_custom_create!("Failed to parse solc list: {}", e))?
+        .builds
+        .into_iter()
+        .filter(|b| b.prerelease.is_none())
+        .collect::<Vec<_>>();
+    *cached = Some((Instant::now(), builds.clone()));
+    Ok(builds)
+}
+
+pub fn verify_sha256(data: &[u8], expected: &str) -> Result<(), AddressologyError> {
+    let actual = hex::encode(Sha256::digest(data));
+    if actual != expected.trim_start_matches("0x").to_lowercase() {
+        return Err(err_custom_create!(
+            "Checksum mismatch, expected {}, got {}",
+            expected,
+            actual
+        ));
+    }
+    Ok(())
+}
+
+async fn download_solc(build: &SolcBuild, dir: &Path) -> Result<PathBuf, AddressologyError> {
+    let version = SolcVersion::from_str(&build.version).map_err(|e| err_custom_create!("{}", e))?;
+    let platform =
+        solc_platform().ok_or_else(|| err_custom_create!("No solc builds for this platform"))?;
+    let url = format!("{}/{}/{}", get_solc_bin_url(), platform, build.path);
+    log::info!("Downloading solc {} from {}", version, url);
+    let data = reqwest::get(&url)
+        .await
+        .map_err(|e| err_custom_create!("Failed to download {}: {}", url, e))?
+        .error_for_status()
+        .map_err(|e| err_custom_create!("Failed to download {}: {}", url, e))?
+        .bytes()
+        .await
+        .map_err(|e| err_custom_create!("Failed to download {}: {}", url, e))?;
+    verify_sha256(&data, &build.sha256)?;
+
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| err_custom_create!("Failed to create {}: {}", dir.display(), e))?;
+    // written under temporary name, so concurrent compilations never see partial binary
+    let path = dir.join(binary_name(&version));
+    let tmp_path = dir.join(format!(
+        ".{}.{}",
+        binary_name(&version),
+        uuid::Uuid::new_v4()
+    ));
+    tokio::fs::write(&tmp_path, &data)
+        .await
+        .map_err(|e| err_custom_create!("Failed to write {}: {}", tmp_path.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o755))
+            .await
+            .map_err(|e| err_custom_create!("Failed to set permissions: {}", e))?;
+    }
+    tokio::fs::rename(&tmp_path, &path)
+        .await
+        .map_err(|e| err_custom_create!("Failed to move {}: {}", tmp_path.display(), e))?;
+    Ok(path)
+}
+
+pub async fn list_solc_versions() -> Result<SolcVersions, AddressologyError> {
+    let installed = installed_versions(Path::new(&get_solc_dir()))
+        .await
+        .into_keys()
+        .collect();
+    let available = if get_solc_offline() {
+        Vec::new()
+    } else {
+        remote_builds()
+            .await?
+            .iter()
+            .filter_map(|b| SolcVersion::from_str(&b.version).ok())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    };
+    Ok(SolcVersions {
+        installed,
+        available,
+    })
+}
+
+/// Version required by pragmas of all sources, or explicitly requested one
+pub fn source_requirements(
+    sources: &BTreeMap<String, String>,
+    requested: Option<&str>,
+) -> Result<Vec<SolcVersionReq>, AddressologyError> {
+    let mut requirements = Vec::new();
+    for (name, source) in sources {
+        requirements
+            .extend(parse_pragmas(source).map_err(|e| err_custom_create!("{}: {}", name, e))?);
+    }
+    if let Some(requested) = requested {
+        requirements.push(
+            SolcVersionReq::from_str(&format!("={}", requested))
+                .map_err(|e| err_custom_create!("{}", e))?,
+        );
+    }
+    Ok(requirements)
+}
+
+/// Picks the highest solc satisfying the requirements, downloading it when allowed.
+/// Requirements no solc can satisfy are reported as invalid input.
+pub async fn resolve_solc(
+    requirements: &[SolcVersionReq],
+    allow_download: bool,
+) -> Result<(SolcVersion, PathBuf), CompileError> {
+    let dir = PathBuf::from(get_solc_dir());
+    let installed = installed_versions(&dir).await;
+
+    if !allow_download {
+        let version = resolve_version(requirements, installed.keys()).ok_or_else(|| {
+            CompileError::InvalidInput(
+                "No installed solc version satisfies the pragma of the sources, log in to download one"
+                    .to_string(),
+            )
+        })?;
+        let path = installed[&version].clone();
+        return Ok((version, path));
+    }
+
+    let remote = if get_solc_offline() {
+        Vec::new()
+    } else {
+        match remote_builds().await {
+            Ok(builds) => builds,
+            Err(e) => {
+                log::warn!("Using installed solc versions only: {}", e);
+                Vec::new()
+            }
+        }
+    };
+    let remote_versions = remote
+        .iter()
+        .filter_map(|b| Some((SolcVersion::from_str(&b.version).ok()?, b)))
+        .collect::<BTreeMap<_, _>>();
+
+    let version = resolve_version(requirements, installed.keys().chain(remote_versions.keys()))
+        .ok_or_else(|| {
+            CompileError::InvalidInput(
+                "No solc version satisfies the pragma of the sources".to_string(),
+            )
+        })?;
+
+    if let Some(path) = installed.get(&version) {
+        return Ok((version, path.clone()));
+    }
+    let build = remote_versions
+        .get(&version)
+        .ok_or_else(|| CompileError::InvalidInput(format!("Solc {} not available", version)))?;
+    let bin = download_solc(build, &dir)
+        .await
+        .map_err(CompileError::Failed)?;
+    Ok((version, bin))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_names() {
+        let version = SolcVersion::new(0, 8, 28);
+        assert_eq!(parse_binary_name(&binary_name(&version)), Some(version));
+        assert_eq!(parse_binary_name("solc_0.8.28"), Some(version));
+        assert_eq!(parse_binary_name("solc-0.8.28.exe"), Some(version));
+        assert_eq!(parse_binary_name(".solc-0.8.28.tmp"), None);
+        assert_eq!(parse_binary_name("solc-windows.exe"), None);
+    }
+
+    #[test]
+    fn test_verify_sha256() {
+        let expected = "0x2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert!(verify_sha256(b"hello", expected).is_ok());
+        assert!(verify_sha256(b"hello!", expected).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Compilation queue shared by all http workers.
+//! Limits solc processes running at once, rejects jobs when too many are waiting
+//! and caches results by hash of the standard-json input.
+
+use crate::config::{
+    get_compile_cache_size, get_compile_max_input_bytes, get_compile_max_sources,
+    get_compile_queue_size, get_compile_rate_limit, get_compile_workers,
+};
+use crate::err_custom_create;
+use crate::error::AddressologyError;
+use crate::solc::{prepare_solc_job, run_solc, CompilerSettings, SolidityJsonResponse};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct CompileLimits {
+    /// Total size of source names and contents
+    pub max_input_bytes: usize,
+    pub max_sources: usize,
+    /// Solc processes running at once
+    pub workers: usize,
+    /// Jobs waiting for a free worker before new ones are rejected
+    pub queue_size: usize,
+    /// Compilations per user within a minute
+    pub rate_limit: usize,
+    pub cache_size: usize,
+}
+
+impl CompileLimits {
+    pub fn from_env() -> Self {
+        CompileLimits {
+            max_input_bytes: get_compile_max_input_bytes(),
+            max_sources: get_compile_max_sources(),
+            workers: get_compile_workers().max(1),
+            queue_size: get_compile_queue_size(),
+            rate_limit: get_compile_rate_limit(),
+            cache_size: get_compile_cache_size(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CompileError {
+    InputTooLarge(String),
+    /// Sources or settings that cannot be compiled, e.g. no solc satisfies the pragma
+    InvalidInput(String),
+    RateLimited,
+    QueueFull,
+    Failed(AddressologyError),
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::InputTooLarge(msg) | CompileError::InvalidInput(msg) => {
+                write!(f, "{}", msg)
+            }
+            CompileError::RateLimited => write!(f, "Too many compilations, try again later"),
+            CompileError::QueueFull => write!(f, "Compiler is busy, try again later"),
+            CompileError::Failed(e) => write!(f, "{}", e.inner),
+        }
+    }
+}
+
+/// Results by job hash, oldest entries are evicted first
+#[derive(Debug, Default)]
+struct CompileCache {
+    entries: HashMap<String, Arc<SolidityJsonResponse>>,
+    order: VecDeque<String>,
+}
+
+impl CompileCache {
+    fn get(&self, key: &str) -> Option<Arc<SolidityJsonResponse>> {
+        self.entries.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: String, value: Arc<SolidityJsonResponse>, capacity: usize) {
+        if capacity == 0 || self.entries.contains_key(&key) {
+            return;
+        }
+        while self.entries.len() >= capacity {
+            match self.order.pop_front() {
+                Some(oldest) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+        self.order.push_back(key.clone());
+        self.entries.insert(key, value);
+    }
+}
+
+/// Sliding window of recent compilations per user
+#[derive(Debug, Default)]
+struct RateLimiter {
+    requests: HashMap<String, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    fn try_acquire(&mut self, key: &str, limit: usize, now: Instant) -> bool {
+        if self.requests.len() > 1024 {
+            self.requests.retain(|_, times| {
+                times
+                    .back()
+                    .is_some_and(|t| now.duration_since(*t) < RATE_WINDOW)
+            });
+        }
+        let times = self.requests.entry(key.to_string()).or_default();
+        while times
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= RATE_WINDOW)
+        {
+            times.pop_front();
+        }
+        if times.len() >= limit {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+
+    /// Gives back the last acquired compilation, e.g. when it was served from cache
+    fn release(&mut self, key: &str) {
+        if let Some(times) = self.requests.get_mut(key) {
+            times.pop_back();
+        }
+    }
+}
+
+pub struct CompileQueue {
+    limits: CompileLimits,
+    /// Running and waiting jobs
+    slots: Semaphore,
+    workers: Semaphore,
+    cache: Mutex<CompileCache>,
+    rates: Mutex<RateLimiter>,
+}
+
+impl CompileQueue {
+    pub fn new(limits: CompileLimits) -> Self {
+        CompileQueue {
+            slots: Semaphore::new(limits.workers + limits.queue_size),
+            workers: Semaphore::new(limits.workers),
+            cache: Mutex::new(CompileCache::default()),
+            rates: Mutex::new(RateLimiter::default()),
+            limits,
+        }
+    }
+
+    fn check_input(&self, sources: &BTreeMap<String, String>) -> Result<(), CompileError> {
+        if sources.len() > self.limits.max_sources {
+            return Err(CompileError::InputTooLarge(format!(
+                "Too many sources, at most {} allowed",
+                self.limits.max_sources
+            )));
+        }
+        let size = sources
+            .iter()
+            .map(|(name, source)| name.len() + source.len())
+            .sum::<usize>();
+        if size > self.limits.max_input_bytes {
+            return Err(CompileError::InputTooLarge(format!(
+                "Sources too large, at most {} bytes allowed",
+                self.limits.max_input_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    /// Compiles sources for given user, cached results do not count against the rate limit.
+    /// Rate limit and queue slot are taken before the job is prepared, as that may download solc.
+    pub async fn compile(
+        &self,
+        user_key: &str,
+        allow_download: bool,
+        sources: BTreeMap<String, String>,
+        solidity_version: Option<&str>,
+        settings: &CompilerSettings,
+    ) -> Result<Arc<SolidityJsonResponse>, CompileError> {
+        self.check_input(&sources)?;
+        if !self
+            .rates
+            .lock()
+            .unwrap()
+            .try_acquire(user_key, self.limits.rate_limit, Instant::now())
+        {
+            return Err(CompileError::RateLimited);
+        }
+        let _slot = self
+            .slots
+            .try_acquire()
+            .map_err(|_| CompileError::QueueFull)?;
+
+        let job = prepare_solc_job(sources, solidity_version, settings, allow_download).await?;
+        let key = job.cache_key();
+        if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+            log::debug!("Compilation {} served from cache", key);
+            self.rates.lock().unwrap().release(user_key);
+            return Ok(cached);
+        }
+
+        let _worker = self
+            .workers
+            .acquire()
+            .await
+            .map_err(|e| CompileError::Failed(err_custom_create!("{}", e)))?;
+
+        let res = Arc::new(run_solc(&job).await.map_err(CompileError::Failed)?);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key, res.clone(), self.limits.cache_size);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response() -> Arc<SolidityJsonResponse> {
+        Arc::new(SolidityJsonResponse {
+            errors: None,
+            contracts: None,
+            compiler_version: None,
+            dependencies: BTreeMap::new(),
+        })
+    }
+
+    #[test]
+    fn test_compile_cache_eviction() {
+        let mut cache = CompileCache::default();
+        for key in ["a", "b", "c"] {
+            cache.insert(key.to_string(), response(), 2);
+        }
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("b").is_some());
+        assert!(cache.get("c").is_some());
+        assert_eq!(cache.order.len(), 2);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::default();
+        let now = Instant::now();
+        assert!(limiter.try_acquire("user", 2, now));
+        assert!(limiter.try_acquire("user", 2, now + Duration::from_secs(1)));
+        assert!(!limiter.try_acquire("user", 2, now + Duration::from_secs(2)));
+        assert!(limiter.try_acquire("other", 2, now + Duration::from_secs(2)));
+        assert!(limiter.try_acquire("user", 2, now + RATE_WINDOW));
+        limiter.release("other");
+        assert!(limiter.try_acquire("other", 1, now + Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_input_limits() {
+        let queue = CompileQueue::new(CompileLimits {
+            max_input_bytes: 20,
+            max_sources: 2,
+            workers: 1,
+            queue_size: 0,
+            rate_limit: 1,
+            cache_size: 1,
+        });
+        let sources = |n: usize, len: usize| {
+            (0..n)
+                .map(|i| (format!("{}.sol", i), "x".repeat(len)))
+                .collect::<BTreeMap<_, _>>()
+        };
+        assert!(queue.check_input(&sources(2, 5)).is_ok());
+        assert!(matches!(
+            queue.check_input(&sources(3, 1)),
+            Err(CompileError::InputTooLarge(_))
+        ));
+        assert!(matches!(
+            queue.check_input(&sources(1, 20)),
+            Err(CompileError::InputTooLarge(_))
+        ));
+    }
+}
@@ -0,0 +1,337 @@
+//! Solidity versions and `pragma solidity` ranges, the semver subset accepted by solc.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SolcVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl SolcVersion {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        SolcVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for SolcVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().trim_start_matches('v');
+        // build metadata, e.g. 0.8.28+commit.7893614a
+        let s = s.split('+').next().unwrap_or_default();
+        let parts = s
+            .split('.')
+            .map(u64::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid solidity version {}: {}", s, e))?;
+        match parts.as_slice() {
+            [major, minor, patch] => Ok(SolcVersion::new(*major, *minor, *patch)),
+            _ => Err(format!("Invalid solidity version: {}", s)),
+        }
+    }
+}
+
+impl Display for SolcVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl Serialize for SolcVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for SolcVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        SolcVersion::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Partial versions are expanded, so `>` and `<=` are never needed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Lt,
+    Ge,
+    Eq,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparator {
+    op: Op,
+    version: SolcVersion,
+}
+
+impl Comparator {
+    fn matches(&self, v: &SolcVersion) -> bool {
+        match self.op {
+            Op::Lt => v < &self.version,
+            Op::Ge => v >= &self.version,
+            Op::Eq => v == &self.version,
+        }
+    }
+}
+
+/// Version with possibly missing or wildcard minor and patch, e.g. `0.8` or `0.8.x`
+#[derive(Debug, Clone, Copy)]
+struct PartialVersion {
+    major: u64,
+    minor: Option<u64>,
+    patch: Option<u64>,
+}
+
+impl PartialVersion {
+    fn parse(s: &str) -> Result<Self, String> {
+        let mut parts = s.trim_start_matches('v').split('.');
+        let mut next = |required: bool| -> Result<Option<u64>, String> {
+            match parts.next() {
+                None if required => Err(format!("Invalid version in range: {}", s)),
+                None | Some("x") | Some("X") | Some("*") => Ok(None),
+                Some(p) => u64::from_str(p)
+                    .map(Some)
+                    .map_err(|e| format!("Invalid version in range {}: {}", s, e)),
+            }
+        };
+        let major = next(true)?.ok_or_else(|| format!("Invalid version in range: {}", s))?;
+        let minor = next(false)?;
+        let patch = if minor.is_some() { next(false)? } else { None };
+        Ok(PartialVersion {
+            major,
+            minor,
+            patch,
+        })
+    }
+
+    fn floor(&self) -> SolcVersion {
+        SolcVersion::new(self.major, self.minor.unwrap_or(0), self.patch.unwrap_or(0))
+    }
+
+    /// First version above everything the partial version covers
+    fn ceil(&self) -> SolcVersion {
+        match (self.minor, self.patch) {
+            (None, _) => SolcVersion::new(self.major + 1, 0, 0),
+            (Some(minor), None) => SolcVersion::new(self.major, minor + 1, 0),
+            (Some(minor), Some(patch)) => SolcVersion::new(self.major, minor, patch + 1),
+        }
+    }
+}
+
+fn expand(op: &str, v: &str, out: &mut Vec<Comparator>) -> Result<(), String> {
+    if v == "*" || v == "x" || v == "X" {
+        return Ok(());
+    }
+    let p = PartialVersion::parse(v)?;
+    let mut push = |op, version| out.push(Comparator { op, version });
+    match op {
+        "" | "=" => {
+            if p.patch.is_some() {
+                push(Op::Eq, p.floor());
+            } else {
+                push(Op::Ge, p.floor());
+                push(Op::Lt, p.ceil());
+            }
+        }
+        ">=" => push(Op::Ge, p.floor()),
+        "<" => push(Op::Lt, p.floor()),
+        ">" => push(Op::Ge, p.ceil()),
+        "<=" => push(Op::Lt, p.ceil()),
+        "~" => {
+            push(Op::Ge, p.floor());
+            push(
+                Op::Lt,
+                match p.minor {
+                    Some(minor) => SolcVersion::new(p.major, minor + 1, 0),
+                    None => SolcVersion::new(p.major + 1, 0, 0),
+                },
+            );
+        }
+        "^" => {
+            push(Op::Ge, p.floor());
+            // leftmost non-zero component is kept
+            let upper = match (p.major, p.minor, p.patch) {
+                (0, Some(0), Some(patch)) => SolcVersion::new(0, 0, patch + 1),
+                (0, Some(minor), _) => SolcVersion::new(0, minor + 1, 0),
+                (major, _, _) => SolcVersion::new(major + 1, 0, 0),
+            };
+            push(Op::Lt, upper);
+        }
+        _ => return Err(format!("Invalid operator in range: {}", op)),
+    }
+    Ok(())
+}
+
+/// Version range from `pragma solidity`, alternatives separated by `||`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolcVersionReq {
+    alternatives: Vec<Vec<Comparator>>,
+}
+
+impl FromStr for SolcVersionReq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref OPERATOR: Regex = Regex::new(r"^(\^|~|>=|<=|>|<|=)?(.*)$").unwrap();
+        }
+        let mut alternatives = Vec::new();
+        for alternative in s.split("||") {
+            let tokens = alternative.split_whitespace().collect::<Vec<_>>();
+            let mut comparators = Vec::new();
+            if let [from, "-", to] = tokens.as_slice() {
+                expand(">=", from, &mut comparators)?;
+                expand("<=", to, &mut comparators)?;
+            } else {
+                let mut pending_op: Option<&str> = None;
+                for token in tokens {
+                    let caps = OPERATOR
+                        .captures(token)
+                        .ok_or_else(|| format!("Invalid range: {}", s))?;
+                    let op = caps.get(1).map(|m| m.as_str()).unwrap_or_default();
+                    let version = caps.get(2).map(|m| m.as_str()).unwrap_or_default();
+                    match (pending_op.take(), version.is_empty()) {
+                        // operator separated by space from its version
+                        (None, true) => pending_op = Some(op),
+                        (None, false) => expand(op, version, &mut comparators)?,
+                        (Some(op), false) if caps.get(1).is_none() => {
+                            expand(op, version, &mut comparators)?
+                        }
+                        _ => return Err(format!("Invalid range: {}", s)),
+                    }
+                }
+                if pending_op.is_some() {
+                    return Err(format!("Invalid range: {}", s));
+                }
+            }
+            alternatives.push(comparators);
+        }
+        Ok(SolcVersionReq { alternatives })
+    }
+}
+
+impl SolcVersionReq {
+    pub fn matches(&self, v: &SolcVersion) -> bool {
+        self.alternatives
+            .iter()
+            .any(|comparators| comparators.iter().all(|c| c.matches(v)))
+    }
+}
+
+/// Source without comments, so commented out pragmas and imports are ignored
+pub fn strip_comments(source: &str) -> Cow<'_, str> {
+    lazy_static! {
+        static ref COMMENTS: Regex = Regex::new(r"(?s)/\*.*?\*/|//[^\n]*").unwrap();
+    }
+    COMMENTS.replace_all(source, "")
+}
+
+/// Version ranges of all `pragma solidity` statements in the source
+pub fn parse_pragmas(source: &str) -> Result<Vec<SolcVersionReq>, String> {
+    lazy_static! {
+        static ref PRAGMA: Regex = Regex::new(r"pragma\s+solidity\s+([^;]+);").unwrap();
+    }
+    let source = strip_comments(source);
+    PRAGMA
+        .captures_iter(&source)
+        .map(|caps| SolcVersionReq::from_str(caps[1].trim()))
+        .collect()
+}
+
+/// Highest version satisfying all ranges
+pub fn resolve_version<'a>(
+    requirements: &[SolcVersionReq],
+    candidates: impl IntoIterator<Item = &'a SolcVersion>,
+) -> Option<SolcVersion> {
+    candidates
+        .into_iter()
+        .filter(|v| requirements.iter().all(|r| r.matches(v)))
+        .max()
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> SolcVersion {
+        SolcVersion::from_str(s).unwrap()
+    }
+
+    fn req(s: &str) -> SolcVersionReq {
+        SolcVersionReq::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_version_req() {
+        assert_eq!(v("v0.8.28+commit.7893614a"), SolcVersion::new(0, 8, 28));
+        assert!(SolcVersion::from_str("0.8").is_err());
+
+        let cases = [
+            ("^0.8.20", "0.8.28", true),
+            ("^0.8.20", "0.8.19", false),
+            ("^0.8.20", "0.9.0", false),
+            ("^0.0.3", "0.0.4", false),
+            ("~0.8.1", "0.8.28", true),
+            ("~0.8.1", "0.9.0", false),
+            ("0.8.28", "0.8.28", true),
+            ("=0.8.28", "0.8.27", false),
+            ("0.8", "0.8.5", true),
+            ("0.8.x", "0.9.0", false),
+            (">=0.6.0 <0.9.0", "0.8.28", true),
+            (">= 0.6.0 < 0.8.0", "0.8.0", false),
+            (">0.7", "0.7.6", false),
+            (">0.7", "0.8.0", true),
+            ("<=0.7", "0.7.6", true),
+            ("0.7.0 - 0.8.10", "0.8.10", true),
+            ("0.7.0 - 0.8.10", "0.8.11", false),
+            ("^0.7.0 || ^0.8.0", "0.8.1", true),
+            ("^0.7.0 || ^0.8.0", "0.6.12", false),
+            ("*", "0.4.11", true),
+        ];
+        for (range, version, expected) in cases {
+            assert_eq!(
+                req(range).matches(&v(version)),
+                expected,
+                "{range} {version}"
+            );
+        }
+        assert!(SolcVersionReq::from_str("^").is_err());
+        assert!(SolcVersionReq::from_str("=> 0.8.0").is_err());
+        assert!(SolcVersionReq::from_str("^0.8.a").is_err());
+    }
+
+    #[test]
+    fn test_resolve_from_pragmas() {
+        let sources = [
+            "// SPDX-License-Identifier: MIT\npragma solidity ^0.8.20;\ncontract A {}",
+            "/* pragma solidity 0.4.0; */\npragma solidity >=0.8.0 <0.8.27;\ncontract B {}",
+        ];
+        let requirements = sources
+            .iter()
+            .map(|s| parse_pragmas(s).unwrap())
+            .collect::<Vec<_>>()
+            .concat();
+        assert_eq!(requirements.len(), 2);
+
+        let candidates = ["0.8.19", "0.8.20", "0.8.26", "0.8.27", "0.8.28"].map(v);
+        assert_eq!(
+            resolve_version(&requirements, &candidates),
+            Some(v("0.8.26"))
+        );
+        assert_eq!(resolve_version(&[], &candidates), Some(v("0.8.28")));
+        assert_eq!(resolve_version(&[req("^0.7.0")], &candidates), None);
+    }
+}
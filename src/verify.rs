@@ -0,0 +1,709 @@
+//! Source verification of deployed contracts on block explorers.
+//! Etherscan compatible explorers verify standard-json input asynchronously and are polled by guid,
+//! Sourcify verifies solc metadata with sources in a single request.
+
+use crate::config::{
+    get_explorer_api_key, get_sourcify_url, get_verify_max_attempts, get_verify_retry_interval,
+};
+use crate::db::model::{ContractDbObj, ContractVerificationDbObj, Explorer, VerifyStatus};
+use crate::db::ops::{
+    claim_due_verifications, get_deployed_contract, get_network, insert_contract_verifications,
+    update_contract_verification,
+};
+use crate::db::utils::get_current_utc_time;
+use crate::err_custom_create;
+use crate::error::AddressologyError;
+use crate::DeployData;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Verifications left to other workers when this one stops in the middle, in seconds
+const VERIFY_CLAIM_TIMEOUT: i64 = 300;
+const EXPLORER_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct MetadataCompiler {
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct MetadataSource {
+    content: Option<String>,
+}
+
+/// Parts of solc metadata needed to reproduce the build
+#[derive(Deserialize)]
+struct SolcMetadata {
+    compiler: MetadataCompiler,
+    settings: serde_json::Map<String, Value>,
+    sources: BTreeMap<String, MetadataSource>,
+}
+
+/// Everything explorers need to rebuild the deployed contract
+#[derive(Debug, Clone)]
+pub struct VerificationInput {
+    /// Full version with commit, e.g. 0.8.28+commit.7893614a
+    pub compiler_version: String,
+    /// `<source file>:<contract name>`
+    pub contract_path: String,
+    pub metadata: String,
+    pub settings: serde_json::Map<String, Value>,
+    pub sources: BTreeMap<String, String>,
+    /// Hex without 0x prefix
+    pub constructor_args: String,
+}
+
+impl VerificationInput {
+    pub fn from_deploy_data(data: &DeployData) -> Result<Self, AddressologyError> {
+        let metadata = serde_json::from_str::<SolcMetadata>(&data.contract.metadata)
+            .map_err(|e| err_custom_create!("Failed to parse contract metadata: {}", e))?;
+        let (file, name) = metadata
+            .settings
+            .get("compilationTarget")
+            .and_then(Value::as_object)
+            .and_then(|target| target.iter().next())
+            .and_then(|(file, name)| Some((file.clone(), name.as_str()?.to_string())))
+            .ok_or_else(|| err_custom_create!("Metadata has no compilation target"))?;
+
+        let mut sources = BTreeMap::new();
+        for (path, source) in &metadata.sources {
+            let content = match &source.content {
+                Some(content) => content.clone(),
+                // only the compiled file is stored with the contract
+                None if *path == file => data.contract.single_file_code.clone(),
+                None => {
+                    return Err(err_custom_create!(
+                        "Source {} is not stored with the contract",
+                        path
+                    ))
+                }
+            };
+            sources.insert(path.clone(), content);
+        }
+
+        Ok(VerificationInput {
+            compiler_version: metadata.compiler.version,
+            contract_path: format!("{}:{}", file, name),
+            metadata: data.contract.metadata.clone(),
+            settings: metadata.settings,
+            sources,
+            constructor_args: data
+                .constructor_args
+                .trim()
+                .trim_start_matches("0x")
+                .to_lowercase(),
+        })
+    }
+
+    /// Standard-json input with settings taken from the metadata
+    pub fn standard_json(&self) -> Value {
+        let mut settings = self.settings.clone();
+        settings.remove("compilationTarget");
+        // metadata keeps libraries as "file:Name" -> address
+        if let Some(Value::Object(libraries)) = settings.remove("libraries") {
+            let mut by_file: BTreeMap<String, BTreeMap<String, Value>> = BTreeMap::new();
+            for (path, address) in libraries {
+                let (file, name) = path.rsplit_once(':').unwrap_or(("", &path));
+                by_file
+                    .entry(file.to_string())
+                    .or_default()
+                    .insert(name.to_string(), address);
+            }
+            settings.insert("libraries".to_string(), json!(by_file));
+        }
+        settings.insert(
+            "outputSelection".to_string(),
+            json!({"*": {"*": ["metadata", "evm.bytecode"]}}),
+        );
+        let sources = self
+            .sources
+            .iter()
+            .map(|(path, content)| (path.clone(), json!({ "content": content })))
+            .collect::<serde_json::Map<_, _>>();
+        json!({
+            "language": "Solidity",
+            "sources": sources,
+            "settings": settings,
+        })
+    }
+}
+
+/// Answer of an explorer, transient failures are returned as errors and retried
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExplorerResult {
+    /// Accepted for asynchronous verification with given guid
+    Submitted(String),
+    Pending,
+    Verified,
+    Rejected(String),
+}
+
+#[derive(Deserialize)]
+struct EtherscanResponse {
+    status: String,
+    result: Value,
+}
+
+impl EtherscanResponse {
+    fn result_text(&self) -> String {
+        match &self.result {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }
+    }
+}
+
+fn explorer_client() -> Result<reqwest::Client, AddressologyError> {
+    reqwest::Client::builder()
+        .timeout(EXPLORER_REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| err_custom_create!("Failed to create http client: {}", e))
+}
+
+async fn read_etherscan_response(
+    res: Result<reqwest::Response, reqwest::Error>,
+) -> Result<EtherscanResponse, AddressologyError> {
+    let text = res
+        .map_err(|e| err_custom_create!("Explorer request failed: {}", e))?
+        .error_for_status()
+        .map_err(|e| err_custom_create!("Explorer request failed: {}", e))?
+        .text()
+        .await
+        .map_err(|e| err_custom_create!("Failed to read explorer response: {}", e))?;
+    serde_json::from_str::<EtherscanResponse>(&text)
+        .map_err(|e| err_custom_create!("Invalid explorer response {}: {}", text, e))
+}
+
+pub async fn etherscan_submit(
+    api_url: &str,
+    api_key: Option<&str>,
+    chain_id: i64,
+    address: &str,
+    input: &VerificationInput,
+) -> Result<ExplorerResult, AddressologyError> {
+    let chain_id = chain_id.to_string();
+    let source_code = input.standard_json().to_string();
+    let compiler_version = format!("v{}", input.compiler_version);
+    let res = explorer_client()?
+        .post(api_url)
+        .query(&[
+            ("chainid", chain_id.as_str()),
+            ("module", "contract"),
+            ("action", "verifysourcecode"),
+        ])
+        .form(&[
+            ("apikey", api_key.unwrap_or_default()),
+            ("codeformat", "solidity-standard-json-input"),
+            ("sourceCode", source_code.as_str()),
+            ("contractaddress", address),
+            ("contractname", input.contract_path.as_str()),
+            ("compilerversion", compiler_version.as_str()),
+            // sic, misspelled in the api
+            ("constructorArguements", input.constructor_args.as_str()),
+        ])
+        .send()
+        .await;
+    let res = read_etherscan_response(res).await?;
+    let result = res.result_text();
+    if res.status == "1" {
+        return Ok(ExplorerResult::Submitted(result));
+    }
+    let lower = result.to_lowercase();
+    if lower.contains("already verified") {
+        Ok(ExplorerResult::Verified)
+    } else if lower.contains("unable to locate") || lower.contains("rate limit") {
+        // explorer has not indexed the contract yet or is throttling
+        Err(err_custom_create!("{}", result))
+    } else {
+        Ok(ExplorerResult::Rejected(result))
+    }
+}
+
+pub async fn etherscan_check(
+    api_url: &str,
+    api_key: Option<&str>,
+    chain_id: i64,
+    guid: &str,
+) -> Result<ExplorerResult, AddressologyError> {
+    let chain_id = chain_id.to_string();
+    let res = explorer_client()?
+        .get(api_url)
+        .query(&[
+            ("chainid", chain_id.as_str()),
+            ("module", "contract"),
+            ("action", "checkverifystatus"),
+            ("guid", guid),
+            ("apikey", api_key.unwrap_or_default()),
+        ])
+        .send()
+        .await;
+    let res = read_etherscan_response(res).await?;
+    let result = res.result_text();
+    let lower = result.to_lowercase();
+    if lower.contains("pending") {
+        Ok(ExplorerResult::Pending)
+    } else if lower.starts_with("pass") || lower.contains("already verified") {
+        Ok(ExplorerResult::Verified)
+    } else if lower.starts_with("fail") {
+        Ok(ExplorerResult::Rejected(result))
+    } else {
+        Err(err_custom_create!(
+            "Unexpected verification status: {}",
+            result
+        ))
+    }
+}
+
+#[derive(Deserialize)]
+struct SourcifyMatch {
+    status: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SourcifyResponse {
+    #[serde(default)]
+    result: Vec<SourcifyMatch>,
+    error: Option<String>,
+}
+
+pub async fn sourcify_verify(
+    url: &str,
+    chain_id: i64,
+    address: &str,
+    input: &VerificationInput,
+) -> Result<ExplorerResult, AddressologyError> {
+    let mut files = input.sources.clone();
+    files.insert("metadata.json".to_string(), input.metadata.clone());
+    let body = json!({
+        "address": address,
+        "chain": chain_id.to_string(),
+        "files": files,
+    });
+    let res = explorer_client()?
+        .post(format!("{}/verify", url))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| err_custom_create!("Sourcify request failed: {}", e))?;
+    let status = res.status();
+    let text = res
+        .text()
+        .await
+        .map_err(|e| err_custom_create!("Failed to read Sourcify response: {}", e))?;
+    if status.is_server_error() {
+        return Err(err_custom_create!("Sourcify error {}: {}", status, text));
+    }
+    let res = serde_json::from_str::<SourcifyResponse>(&text)
+        .map_err(|e| err_custom_create!("Invalid Sourcify response {}: {}", text, e))?;
+    if let Some(error) = res.error {
+        return Ok(ExplorerResult::Rejected(error));
+    }
+    match res.result.first().and_then(|m| m.status.as_deref()) {
+        Some("perfect") | Some("partial") => Ok(ExplorerResult::Verified),
+        other => Ok(ExplorerResult::Rejected(format!(
+            "Sourcify did not match the contract: {}",
+            other.unwrap_or("no result")
+        ))),
+    }
+}
+
+/// Queues verification on explorers configured for the contract network
+pub async fn request_verification(
+    conn: &PgPool,
+    contract: &ContractDbObj,
+) -> Result<Vec<ContractVerificationDbObj>, AddressologyError> {
+    let network = get_network(conn, &contract.network)
+        .await
+        .map_err(|e| err_custom_create!("Failed to get network: {}", e))?
+        .ok_or_else(|| err_custom_create!("Network {} is not supported", contract.network))?;
+    let mut explorers = Vec::new();
+    if network.explorer_api_url.is_some() {
+        explorers.push(Explorer::Etherscan.to_string());
+    }
+    if get_sourcify_url().is_some() {
+        explorers.push(Explorer::Sourcify.to_string());
+    }
+    if explorers.is_empty() {
+        return Ok(Vec::new());
+    }
+    insert_contract_verifications(
+        conn,
+        contract.contract_id,
+        &explorers,
+        get_current_utc_time(),
+    )
+    .await
+    .map_err(|e| err_custom_create!("Failed to queue verification: {}", e))
+}
+
+async fn run_verification(
+    conn: &PgPool,
+    verification: &ContractVerificationDbObj,
+) -> Result<ExplorerResult, AddressologyError> {
+    let contract = get_deployed_contract(conn, verification.contract_id)
+        .await
+        .map_err(|e| err_custom_create!("Failed to get contract: {}", e))?
+        .ok_or_else(|| err_custom_create!("Contract is not deployed"))?;
+    let address = contract
+        .address
+        .as_ref()
+        .ok_or_else(|| err_custom_create!("Address not found on db obj"))?;
+    let network = get_network(conn, &contract.network)
+        .await
+        .map_err(|e| err_custom_create!("Failed to get network: {}", e))?
+        .ok_or_else(|| err_custom_create!("Network {} is not supported", contract.network))?;
+
+    // missing sources do not get better with retries
+    let input = match serde_json::from_str::<DeployData>(&contract.data)
+        .map_err(|e| err_custom_create!("Failed to parse deploy data: {}", e))
+        .and_then(|data| VerificationInput::from_deploy_data(&data))
+    {
+        Ok(input) => input,
+        Err(e) => return Ok(ExplorerResult::Rejected(e.inner.to_string())),
+    };
+
+    match Explorer::from_str(&verification.explorer).map_err(|e| err_custom_create!("{}", e))? {
+        Explorer::Etherscan => {
+            let api_url = network.explorer_api_url.as_ref().ok_or_else(|| {
+                err_custom_create!("Network {} has no explorer api", network.name)
+            })?;
+            let api_key = get_explorer_api_key(&network.explorer_key_env);
+            match &verification.guid {
+                None => {
+                    etherscan_submit(
+                        api_url,
+                        api_key.as_deref(),
+                        network.chain_id,
+                        address,
+                        &input,
+                    )
+                    .await
+                }
+                Some(guid) => {
+                    etherscan_check(api_url, api_key.as_deref(), network.chain_id, guid).await
+                }
+            }
+        }
+        Explorer::Sourcify => {
+            let url = get_sourcify_url()
+                .ok_or_else(|| err_custom_create!("Sourcify verification is disabled"))?;
+            sourcify_verify(&url, network.chain_id, address, &input).await
+        }
+    }
+}
+
+/// Applies explorer answer, transient errors and pending results count as attempts
+fn apply_result(
+    verification: &mut ContractVerificationDbObj,
+    result: Result<ExplorerResult, AddressologyError>,
+    now: chrono::NaiveDateTime,
+) {
+    verification.updated = now;
+    verification.next_check = now + chrono::Duration::seconds(get_verify_retry_interval());
+    match result {
+        Ok(ExplorerResult::Submitted(guid)) => {
+            verification.status = VerifyStatus::Submitted;
+            verification.guid = Some(guid);
+            verification.error = None;
+        }
+        Ok(ExplorerResult::Verified) => {
+            verification.status = VerifyStatus::Verified;
+            verification.error = None;
+        }
+        Ok(ExplorerResult::Rejected(reason)) => {
+            verification.status = VerifyStatus::Failed;
+            verification.error = Some(reason);
+        }
+        Ok(ExplorerResult::Pending) => {
+            verification.attempts += 1;
+        }
+        Err(e) => {
+            verification.attempts += 1;
+            verification.error = Some(e.inner.to_string());
+        }
+    }
+    if matches!(
+        verification.status,
+        VerifyStatus::Pending | VerifyStatus::Submitted
+    ) && verification.attempts as i64 >= get_verify_max_attempts()
+    {
+        verification.status = VerifyStatus::Failed;
+        if verification.error.is_none() {
+            verification.error = Some("Timed out waiting for verification result".to_string());
+        }
+    }
+}
+
+/// Submits or checks all verifications that are due. Empty networks means all of them.
+pub async fn process_due_verifications(conn: &PgPool, networks: &[String]) {
+    loop {
+        let now = get_current_utc_time();
+        let claimed = match claim_due_verifications(
+            conn,
+            networks,
+            now,
+            now + chrono::Duration::seconds(VERIFY_CLAIM_TIMEOUT),
+            10,
+        )
+        .await
+        {
+            Ok(claimed) => claimed,
+            Err(e) => {
+                log::error!("Failed to claim verifications: {}", e);
+                return;
+            }
+        };
+        if claimed.is_empty() {
+            return;
+        }
+        for mut verification in claimed {
+            let result = run_verification(conn, &verification).await;
+            if let Err(e) = &result {
+                log::warn!(
+                    "Verification of {} on {} failed: {}",
+                    verification.contract_id,
+                    verification.explorer,
+                    e
+                );
+            }
+            apply_result(&mut verification, result, get_current_utc_time());
+            log::info!(
+                "Verification of {} on {} is {}",
+                verification.contract_id,
+                verification.explorer,
+                verification.status
+            );
+            if let Err(e) = update_contract_verification(conn, &verification).await {
+                log::error!("Failed to save verification: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+
+    fn deploy_data() -> DeployData {
+        let metadata = json!({
+            "compiler": {"version": "0.8.28+commit.7893614a"},
+            "language": "Solidity",
+            "settings": {
+                "compilationTarget": {"Token.sol": "Token"},
+                "evmVersion": "cancun",
+                "libraries": {"Math.sol:Math": "0x1111111111111111111111111111111111111111"},
+                "optimizer": {"enabled": true, "runs": 2000},
+                "remappings": []
+            },
+            "sources": {
+                "Token.sol": {"keccak256": "0x01"},
+                "Math.sol": {"keccak256": "0x02", "content": "library Math {}"}
+            },
+            "version": 1
+        });
+        serde_json::from_value(json!({
+            "name": "Token",
+            "contract": {
+                "evm": {"bytecode": {"object": "00", "opcodes": "", "sourceMap": ""}},
+                "metadata": metadata.to_string(),
+                "singleFileCode": "contract Token {}"
+            },
+            "constructorArgs": "0xABCD"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_verification_input() {
+        let input = VerificationInput::from_deploy_data(&deploy_data()).unwrap();
+        assert_eq!(input.compiler_version, "0.8.28+commit.7893614a");
+        assert_eq!(input.contract_path, "Token.sol:Token");
+        assert_eq!(input.constructor_args, "abcd");
+        assert_eq!(input.sources["Token.sol"], "contract Token {}");
+
+        let standard_json = input.standard_json();
+        assert_eq!(
+            standard_json["sources"]["Math.sol"]["content"],
+            "library Math {}"
+        );
+        assert_eq!(
+            standard_json["settings"]["libraries"]["Math.sol"]["Math"],
+            "0x1111111111111111111111111111111111111111"
+        );
+        assert_eq!(standard_json["settings"]["evmVersion"], "cancun");
+        assert!(standard_json["settings"].get("compilationTarget").is_none());
+
+        let mut missing = deploy_data();
+        let mut metadata = serde_json::from_str::<Value>(&missing.contract.metadata).unwrap();
+        metadata["sources"]["Math.sol"]
+            .as_object_mut()
+            .unwrap()
+            .remove("content");
+        missing.contract.metadata = metadata.to_string();
+        assert!(VerificationInput::from_deploy_data(&missing).is_err());
+    }
+
+    #[test]
+    fn test_apply_result() {
+        let now = get_current_utc_time();
+        let mut verification = ContractVerificationDbObj {
+            contract_id: uuid::Uuid::new_v4(),
+            explorer: Explorer::Etherscan.to_string(),
+            status: VerifyStatus::Pending,
+            guid: None,
+            attempts: 0,
+            error: None,
+            next_check: now,
+            created: now,
+            updated: now,
+        };
+        apply_result(
+            &mut verification,
+            Err(err_custom_create!("Unable to locate ContractCode")),
+            now,
+        );
+        assert_eq!(verification.status, VerifyStatus::Pending);
+        assert_eq!(verification.attempts, 1);
+        assert!(verification.next_check > now);
+
+        apply_result(
+            &mut verification,
+            Ok(ExplorerResult::Submitted("guid".to_string())),
+            now,
+        );
+        assert_eq!(verification.status, VerifyStatus::Submitted);
+        assert_eq!(verification.error, None);
+
+        verification.attempts = get_verify_max_attempts() as i32 - 1;
+        apply_result(&mut verification, Ok(ExplorerResult::Pending), now);
+        assert_eq!(verification.status, VerifyStatus::Failed);
+        assert!(verification.error.is_some());
+    }
+
+    async fn mock_etherscan(
+        query: web::Query<BTreeMap<String, String>>,
+        form: Option<web::Form<BTreeMap<String, String>>>,
+    ) -> HttpResponse {
+        match query.get("action").map(String::as_str) {
+            Some("verifysourcecode") => {
+                let form = form.unwrap();
+                let valid = form.get("contractname").map(String::as_str) == Some("Token.sol:Token")
+                    && form.get("compilerversion").map(String::as_str)
+                        == Some("v0.8.28+commit.7893614a")
+                    && form.get("constructorArguements").map(String::as_str) == Some("abcd")
+                    && serde_json::from_str::<Value>(&form["sourceCode"]).is_ok();
+                if form.get("contractaddress").map(String::as_str)
+                    == Some("0x2222222222222222222222222222222222222222")
+                {
+                    HttpResponse::Ok().json(json!({"status": "0", "message": "NOTOK", "result": "Unable to locate ContractCode at 0x2222"}))
+                } else if valid {
+                    HttpResponse::Ok()
+                        .json(json!({"status": "1", "message": "OK", "result": "guid-1"}))
+                } else {
+                    HttpResponse::Ok().json(
+                        json!({"status": "0", "message": "NOTOK", "result": "Invalid request"}),
+                    )
+                }
+            }
+            Some("checkverifystatus") => match query.get("guid").map(String::as_str) {
+                Some("guid-1") => HttpResponse::Ok()
+                    .json(json!({"status": "1", "message": "OK", "result": "Pass - Verified"})),
+                Some("guid-pending") => HttpResponse::Ok()
+                    .json(json!({"status": "0", "message": "NOTOK", "result": "Pending in queue"})),
+                _ => HttpResponse::Ok().json(
+                    json!({"status": "0", "message": "NOTOK", "result": "Fail - Unable to verify"}),
+                ),
+            },
+            _ => HttpResponse::BadRequest().finish(),
+        }
+    }
+
+    async fn mock_sourcify(body: web::Json<Value>) -> HttpResponse {
+        if body["files"]["metadata.json"].is_string() && body["files"]["Token.sol"].is_string() {
+            HttpResponse::Ok().json(json!({"result": [{"address": body["address"], "chainId": body["chain"], "status": "perfect"}]}))
+        } else {
+            HttpResponse::BadRequest().json(json!({"error": "Missing files"}))
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_mock_explorer() {
+        let server = HttpServer::new(|| {
+            App::new()
+                .route("/api", web::get().to(mock_etherscan))
+                .route("/api", web::post().to(mock_etherscan))
+                .route("/verify", web::post().to(mock_sourcify))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let base = format!("http://{}", server.addrs()[0]);
+        let handle = server.run();
+        let stop = handle.handle();
+        actix_rt::spawn(handle);
+
+        let api = format!("{}/api", base);
+        let input = VerificationInput::from_deploy_data(&deploy_data()).unwrap();
+        let address = "0x3333333333333333333333333333333333333333";
+        assert_eq!(
+            etherscan_submit(&api, Some("key"), 17000, address, &input)
+                .await
+                .unwrap(),
+            ExplorerResult::Submitted("guid-1".to_string())
+        );
+        assert!(etherscan_submit(
+            &api,
+            None,
+            17000,
+            "0x2222222222222222222222222222222222222222",
+            &input
+        )
+        .await
+        .is_err());
+        let mut wrong_args = input.clone();
+        wrong_args.constructor_args = "00".to_string();
+        assert_eq!(
+            etherscan_submit(&api, None, 17000, address, &wrong_args)
+                .await
+                .unwrap(),
+            ExplorerResult::Rejected("Invalid request".to_string())
+        );
+
+        assert_eq!(
+            etherscan_check(&api, None, 17000, "guid-1").await.unwrap(),
+            ExplorerResult::Verified
+        );
+        assert_eq!(
+            etherscan_check(&api, None, 17000, "guid-pending")
+                .await
+                .unwrap(),
+            ExplorerResult::Pending
+        );
+        assert!(matches!(
+            etherscan_check(&api, None, 17000, "guid-2").await.unwrap(),
+            ExplorerResult::Rejected(_)
+        ));
+
+        assert_eq!(
+            sourcify_verify(&base, 17000, address, &input)
+                .await
+                .unwrap(),
+            ExplorerResult::Verified
+        );
+        let mut no_sources = input.clone();
+        no_sources.sources.clear();
+        assert_eq!(
+            sourcify_verify(&base, 17000, address, &no_sources)
+                .await
+                .unwrap(),
+            ExplorerResult::Rejected("Missing files".to_string())
+        );
+        stop.stop(true).await;
+    }
+}